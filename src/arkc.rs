@@ -0,0 +1,579 @@
+//! The `.arkc` format: a compiled script or module, so it can be run
+//! without lexing, parsing and compiling it again.
+//!
+//! A file starts with the magic bytes `ARKC`, the format version and a hash
+//! of the source it was compiled from. Then follow the source files that
+//! spans point into, the names of the global slots, every chunk (each after
+//! the chunks of the functions in its constant pool, the script's last, and
+//! with the names of its locals and upvalues for the debugger), the
+//! function table and the names the module exports. Integers are LEB128
+//! varints; numbers are f64 bits. Code is stored as the VM runs it, and
+//! checked instruction by instruction when it is read back.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::bytecode::{Chunk, LocalInfo, OpCode};
+use crate::error::{ArkaanError, ErrorKind};
+use crate::span::{SourceFile, Span};
+use crate::symbol::Symbol;
+use crate::value::{Function, TypeConstructorDef, Value};
+
+const MAGIC: &[u8; 4] = b"ARKC";
+
+/// Bump whenever the layout below or the meaning of an opcode changes
+pub const FORMAT_VERSION: u16 = 3;
+
+/// A compiled script or module, as stored in an `.arkc` file
+#[derive(Debug, Clone)]
+pub struct CompiledFile {
+    pub chunk: Chunk,
+    pub functions: Vec<Rc<Chunk>>,
+    pub exports: Vec<String>,  // Names the module makes available with 'verskaf'
+    pub globals: Vec<String>,  // Name of each global slot the code refers to
+    pub source_hash: u64,      // See `source_hash`
+}
+
+/// FNV-1a hash of a source text. Unlike `DefaultHasher` it is the same in
+/// every build, so it can be stored to tell when a source has changed.
+pub fn source_hash(source: &str) -> u64 {
+    source.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+pub fn encode(file: &CompiledFile) -> Result<Vec<u8>, ArkaanError> {
+    // Number the chunks so a function's chunk always comes before the chunk
+    // that declares it, and the source files they point into
+    let mut order = ChunkOrder::default();
+    for function in &file.functions {
+        order.visit(function);
+    }
+    order.visit_constants(&file.chunk);
+    let mut chunks: Vec<&Chunk> = order.chunks.iter().map(|chunk| chunk.as_ref()).collect();
+    chunks.push(&file.chunk);
+
+    let mut files: Vec<&Rc<SourceFile>> = Vec::new();
+    for chunk in &chunks {
+        if let Some(source) = &chunk.file {
+            if !files.iter().any(|known| Rc::ptr_eq(known, source)) {
+                files.push(source);
+            }
+        }
+    }
+
+    let mut out = Writer::default();
+    out.bytes.extend_from_slice(MAGIC);
+    out.bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    out.bytes.extend_from_slice(&file.source_hash.to_le_bytes());
+
+    out.uint(files.len());
+    for source in &files {
+        out.string(&source.name);
+        out.string(&source.text);
+    }
+
+    out.uint(file.globals.len());
+    for name in &file.globals {
+        out.string(name);
+    }
+
+    out.uint(chunks.len());
+    for chunk in &chunks {
+        let file_index = chunk
+            .file
+            .as_ref()
+            .and_then(|source| files.iter().position(|known| Rc::ptr_eq(known, source)));
+        out.uint(file_index.map_or(0, |index| index + 1));
+        out.chunk(chunk, &order.indices)?;
+    }
+
+    out.uint(file.functions.len());
+    for function in &file.functions {
+        out.uint(order.indices[&Rc::as_ptr(function)]);
+    }
+
+    out.uint(file.exports.len());
+    for name in &file.exports {
+        out.string(name);
+    }
+
+    Ok(out.bytes)
+}
+
+pub fn decode(bytes: &[u8]) -> Result<CompiledFile, ArkaanError> {
+    let mut input = Reader { bytes, position: 0 };
+
+    if input.take(4)? != MAGIC {
+        return Err(invalid("dit is nie 'n .arkc-lêer nie"));
+    }
+    let version = u16::from_le_bytes(input.array()?);
+    if version != FORMAT_VERSION {
+        return Err(invalid(format!(
+            "weergawe {} van die formaat word nie ondersteun nie (verwag {})",
+            version, FORMAT_VERSION
+        ))
+        .with_help("Kompileer die bronlêer weer."));
+    }
+    let source_hash = u64::from_le_bytes(input.array()?);
+
+    let mut files = Vec::new();
+    for _ in 0..input.uint()? {
+        let name = input.string()?;
+        let text = input.string()?;
+        files.push(Rc::new(SourceFile::new(name, text)));
+    }
+
+    let mut globals = Vec::new();
+    for _ in 0..input.uint()? {
+        globals.push(input.string()?);
+    }
+
+    let mut chunks: Vec<Rc<Chunk>> = Vec::new();
+    let mut upvalues_used = Vec::new();
+    for _ in 0..input.uint()? {
+        let file = match input.uint()? {
+            0 => None,
+            index => Some(Rc::clone(files.get(index - 1).ok_or_else(|| invalid("onbekende bronlêer"))?)),
+        };
+        let mut chunk = input.chunk(&chunks, &upvalues_used)?;
+        chunk.file = file;
+        upvalues_used.push(verify(&chunk, globals.len())?);
+        chunks.push(Rc::new(chunk));
+    }
+    let script = chunks.pop().ok_or_else(|| invalid("geen skrip nie"))?;
+
+    let mut functions = Vec::new();
+    for _ in 0..input.uint()? {
+        let index = input.uint()?;
+        functions.push(Rc::clone(chunks.get(index).ok_or_else(|| invalid("onbekende funksie"))?));
+    }
+
+    let mut exports = Vec::new();
+    for _ in 0..input.uint()? {
+        exports.push(input.string()?);
+    }
+
+    if input.position != bytes.len() {
+        return Err(invalid("onverwagte data aan die einde"));
+    }
+
+    Ok(CompiledFile {
+        chunk: Rc::unwrap_or_clone(script),
+        functions,
+        exports,
+        globals,
+        source_hash,
+    })
+}
+
+/// Checks that `chunk` holds whole instructions whose operands stay inside
+/// it, so damaged code is reported here instead of as a panic in the VM.
+/// Returns how many upvalues the code expects its closure to have.
+fn verify(chunk: &Chunk, globals: usize) -> Result<usize, ArkaanError> {
+    let mut starts = vec![false; chunk.code.len() + 1];
+    let mut targets = Vec::new();
+    let mut upvalues = 0;
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        starts[offset] = true;
+        let len = chunk.instruction_len(offset).ok_or_else(|| invalid("onvolledige of onbekende instruksie"))?;
+        let operand = |n: usize| chunk.read_u16(offset + 1 + 2 * n) as usize;
+        let valid = match OpCode::from_byte(chunk.code[offset]) {
+            Some(OpCode::Constant) => operand(0) < chunk.constants.len(),
+            Some(OpCode::GetGlobal | OpCode::SetGlobal | OpCode::DefineGlobal) => operand(0) < globals,
+            Some(OpCode::GetUpvalue | OpCode::SetUpvalue) => {
+                upvalues = upvalues.max(operand(0) + 1);
+                true
+            }
+            Some(OpCode::CheckConstructor | OpCode::GetMember) => operand(0) < chunk.names.len(),
+            Some(OpCode::LoadModule) => operand(0) < chunk.names.len() && operand(1) < chunk.names.len(),
+            Some(OpCode::Jump | OpCode::JumpIfFalse) => {
+                targets.push(chunk.read_u32(offset + 1) as usize);
+                true
+            }
+            Some(OpCode::Closure) => {
+                // Each capture is an is_local byte and an index
+                for capture in (offset + 5..offset + len).step_by(3) {
+                    if chunk.code[capture] == 0 {
+                        upvalues = upvalues.max(chunk.read_u16(capture + 1) as usize + 1);
+                    }
+                }
+                matches!(
+                    chunk.constants.get(operand(0)),
+                    Some(Value::Function(function)) if function.upvalue_count == operand(1)
+                )
+            }
+            _ => true,
+        };
+        if !valid {
+            return Err(invalid("instruksie verwys buite die stuk"));
+        }
+        offset += len;
+    }
+    starts[chunk.code.len()] = true;
+
+    if targets.iter().any(|&target| !starts.get(target).copied().unwrap_or(false)) {
+        return Err(invalid("sprong na die middel van 'n instruksie"));
+    }
+    Ok(upvalues)
+}
+
+fn invalid(message: impl Into<String>) -> ArkaanError {
+    ArkaanError::new(
+        ErrorKind::InvalidBytecode,
+        format!("Ongeldige .arkc-lêer: {}.", message.into()),
+    )
+}
+
+/// Function chunks in the order they are written, children first
+#[derive(Default)]
+struct ChunkOrder {
+    chunks: Vec<Rc<Chunk>>,
+    indices: HashMap<*const Chunk, usize>,
+}
+
+impl ChunkOrder {
+    fn visit(&mut self, chunk: &Rc<Chunk>) {
+        if self.indices.contains_key(&Rc::as_ptr(chunk)) {
+            return;
+        }
+        self.visit_constants(chunk);
+        self.indices.insert(Rc::as_ptr(chunk), self.chunks.len());
+        self.chunks.push(Rc::clone(chunk));
+    }
+
+    fn visit_constants(&mut self, chunk: &Chunk) {
+        for constant in &chunk.constants {
+            if let Value::Function(function) = constant {
+                self.visit(&function.chunk);
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn uint(&mut self, value: usize) {
+        let mut value = value as u64;
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.bytes.push(byte);
+                return;
+            }
+            self.bytes.push(byte | 0x80);
+        }
+    }
+
+    fn string(&mut self, text: &str) {
+        self.uint(text.len());
+        self.bytes.extend_from_slice(text.as_bytes());
+    }
+
+    fn span(&mut self, span: Span) {
+        self.uint(span.start);
+        self.uint(span.end);
+        self.uint(span.line);
+        self.uint(span.column);
+    }
+
+    fn chunk(&mut self, chunk: &Chunk, indices: &HashMap<*const Chunk, usize>) -> Result<(), ArkaanError> {
+        self.uint(chunk.code.len());
+        self.bytes.extend_from_slice(&chunk.code);
+
+        // Every byte of an instruction shares its span, so store them as runs
+        let mut runs: Vec<(usize, Span)> = Vec::new();
+        for &span in &chunk.spans {
+            match runs.last_mut() {
+                Some((count, last)) if *last == span => *count += 1,
+                _ => runs.push((1, span)),
+            }
+        }
+        self.uint(runs.len());
+        for (count, span) in runs {
+            self.uint(count);
+            self.span(span);
+        }
+
+        self.uint(chunk.names.len());
+        for name in &chunk.names {
+            self.string(&name.name());
+        }
+
+        self.uint(chunk.locals.len());
+        for local in &chunk.locals {
+            self.string(&local.name.name());
+            self.uint(local.slot);
+            self.uint(local.start);
+            self.uint(local.end);
+        }
+        self.uint(chunk.upvalue_names.len());
+        for name in &chunk.upvalue_names {
+            self.string(&name.name());
+        }
+
+        self.uint(chunk.constants.len());
+        for constant in &chunk.constants {
+            self.constant(constant, indices)?;
+        }
+        Ok(())
+    }
+
+    fn constant(&mut self, value: &Value, indices: &HashMap<*const Chunk, usize>) -> Result<(), ArkaanError> {
+        match value {
+            Value::Number(n) => {
+                self.bytes.push(0);
+                self.bytes.extend_from_slice(&n.to_bits().to_le_bytes());
+            }
+            Value::Boolean(b) => {
+                self.bytes.push(1);
+                self.bytes.push(u8::from(*b));
+            }
+            Value::String(s) => {
+                self.bytes.push(2);
+                self.string(s);
+            }
+            Value::Nil => self.bytes.push(3),
+            Value::Function(function) => {
+                self.bytes.push(4);
+                self.string(&function.name);
+                self.uint(function.arity);
+                self.uint(function.upvalue_count);
+                self.span(function.span);
+                self.uint(indices[&Rc::as_ptr(&function.chunk)]);
+            }
+            Value::TypeConstructor(constructor) => {
+                self.bytes.push(5);
+                self.string(&constructor.type_name);
+                self.string(&constructor.constructor_name);
+                self.uint(constructor.arity);
+            }
+            // The compiler only puts the kinds above in a constant pool
+            other => {
+                return Err(ArkaanError::new(
+                    ErrorKind::Internal,
+                    format!("Kan nie die konstante '{}' in 'n .arkc-lêer stoor nie.", other),
+                ))
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], ArkaanError> {
+        let end = self.position.checked_add(count).filter(|&end| end <= self.bytes.len());
+        let end = end.ok_or_else(|| invalid("die lêer is afgekap"))?;
+        let taken = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ArkaanError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn byte(&mut self) -> Result<u8, ArkaanError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn uint(&mut self) -> Result<usize, ArkaanError> {
+        let mut value: u64 = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return usize::try_from(value).map_err(|_| invalid("getal te groot"));
+            }
+        }
+        Err(invalid("getal te lank"))
+    }
+
+    fn bool(&mut self) -> Result<bool, ArkaanError> {
+        Ok(self.byte()? != 0)
+    }
+
+    fn string(&mut self) -> Result<String, ArkaanError> {
+        let length = self.uint()?;
+        let bytes = self.take(length)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| invalid("ongeldige UTF-8"))
+    }
+
+    fn span(&mut self) -> Result<Span, ArkaanError> {
+        Ok(Span::new(self.uint()?, self.uint()?, self.uint()?, self.uint()?))
+    }
+
+    /// A chunk whose functions refer to `earlier` chunks, which use
+    /// `upvalues_used` upvalues each
+    fn chunk(&mut self, earlier: &[Rc<Chunk>], upvalues_used: &[usize]) -> Result<Chunk, ArkaanError> {
+        let mut chunk = Chunk::new();
+        let length = self.uint()?;
+        chunk.code = self.take(length)?.to_vec();
+
+        for _ in 0..self.uint()? {
+            let count = self.uint()?;
+            let span = self.span()?;
+            if chunk.spans.len() + count > length {
+                return Err(invalid("meer spans as kode"));
+            }
+            chunk.spans.extend(std::iter::repeat_n(span, count));
+        }
+        if chunk.spans.len() != length {
+            return Err(invalid("minder spans as kode"));
+        }
+
+        for _ in 0..self.uint()? {
+            let name = self.string()?;
+            chunk.names.push(Symbol::intern(&name));
+        }
+
+        for _ in 0..self.uint()? {
+            let name = Symbol::intern(&self.string()?);
+            let (slot, start, end) = (self.uint()?, self.uint()?, self.uint()?);
+            if start > end || end > length {
+                return Err(invalid("lokale veranderlike buite die kode"));
+            }
+            chunk.locals.push(LocalInfo { name, slot, start, end });
+        }
+        for _ in 0..self.uint()? {
+            let name = self.string()?;
+            chunk.upvalue_names.push(Symbol::intern(&name));
+        }
+        for _ in 0..self.uint()? {
+            let constant = self.constant(earlier, upvalues_used)?;
+            chunk.add_constant(constant);
+        }
+        Ok(chunk)
+    }
+
+    fn constant(&mut self, earlier: &[Rc<Chunk>], upvalues_used: &[usize]) -> Result<Value, ArkaanError> {
+        Ok(match self.byte()? {
+            0 => Value::Number(f64::from_bits(u64::from_le_bytes(self.array()?))),
+            1 => Value::Boolean(self.bool()?),
+            2 => Value::String(Rc::new(self.string()?)),
+            3 => Value::Nil,
+            4 => {
+                let name = self.string()?;
+                let arity = self.uint()?;
+                let upvalue_count = self.uint()?;
+                let span = self.span()?;
+                let index = self.uint()?;
+                let chunk = earlier.get(index).ok_or_else(|| invalid("onbekende funksie"))?;
+                if upvalues_used[index] > upvalue_count {
+                    return Err(invalid("funksie gebruik meer upvalues as wat dit het"));
+                }
+                Value::Function(Rc::new(Function {
+                    name,
+                    arity,
+                    chunk: Rc::clone(chunk),
+                    upvalue_count,
+                    span,
+                }))
+            }
+            5 => {
+                let type_name = self.string()?;
+                let constructor_name = self.string()?;
+                // Tags are only meaningful in the process that interned them
+                let tag = Symbol::intern(&constructor_name);
+                Value::TypeConstructor(Rc::new(TypeConstructorDef {
+                    type_name,
+                    constructor_name,
+                    tag,
+                    arity: self.uint()?,
+                }))
+            }
+            tag => return Err(invalid(format!("onbekende konstante-soort {}", tag))),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    use super::{decode, encode, CompiledFile};
+    use crate::engine::Engine;
+    use crate::sandbox::{Limits, ModuleAccess};
+    use crate::span::SourceFile;
+
+    fn compiled_examples() -> Vec<(String, Vec<u8>)> {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/examples");
+        let mut files: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "ark"))
+            .collect();
+        files.sort();
+        files
+            .into_iter()
+            .filter_map(|path| {
+                let source = std::fs::read_to_string(&path).unwrap();
+                let file = Rc::new(SourceFile::new(path.display().to_string(), source));
+                let compiled = Engine::compile(&file).ok()?;
+                Some((path.display().to_string(), encode(&compiled).unwrap()))
+            })
+            .collect()
+    }
+
+    /// Runs a file the way a sandboxed host would, so damaged code that loops cannot hang the test
+    fn run(compiled: CompiledFile) {
+        let mut engine = Engine::new();
+        engine.vm_mut().set_limits(Limits {
+            fuel: Some(100_000),
+            timeout: Some(Duration::from_secs(1)),
+            max_len: Some(10_000),
+            modules: ModuleAccess::None,
+            ..Limits::default()
+        });
+        let _ = engine.execute(compiled);
+    }
+
+    #[test]
+    fn examples_encode_the_same_after_a_round_trip() {
+        let examples = compiled_examples();
+        assert!(examples.len() > 10);
+        for (name, bytes) in examples {
+            let decoded = decode(&bytes).unwrap_or_else(|e| panic!("{}: {}", name, e));
+            assert_eq!(encode(&decoded).unwrap(), bytes, "{} changed", name);
+        }
+    }
+
+    #[test]
+    fn damaged_files_are_refused_or_fail_without_panicking() {
+        // xorshift, so every run damages the same bytes
+        let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut random = move |below: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % below as u64) as usize
+        };
+
+        for (_, bytes) in compiled_examples() {
+            for _ in 0..300 {
+                let mut damaged = bytes.clone();
+                for _ in 0..3 {
+                    let at = random(damaged.len());
+                    damaged[at] = random(256) as u8;
+                }
+                if let Ok(compiled) = decode(&damaged) {
+                    run(compiled);
+                }
+            }
+        }
+    }
+}
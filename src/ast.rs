@@ -1,7 +1,37 @@
+use crate::span::Span;
 use crate::token::Token;
 
+/// An expression together with the source text it was parsed from
 #[derive(Debug, Clone)]
-pub enum Expr {
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Expr { kind, span }
+    }
+}
+
+/// A name as written in the source: a binding, parameter, constructor or member
+#[derive(Debug, Clone)]
+pub struct Ident {
+    pub name: String,
+    pub span: Span,
+}
+
+impl Ident {
+    pub fn new(name: impl Into<String>, span: Span) -> Self {
+        Ident {
+            name: name.into(),
+            span,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ExprKind {
     Binary {
         left: Box<Expr>,
         operator: Token,
@@ -21,7 +51,7 @@ pub enum Expr {
     },
     // Lambda expression: fn(params) expr or fn(params) { stmts }
     Lambda {
-        params: Vec<String>,
+        params: Vec<Ident>,
         body: LambdaBody,
     },
     // List literal: [a, b, c]
@@ -37,7 +67,7 @@ pub enum Expr {
         arms: Vec<MatchArm>,
     },
     // Inline if expression: as(condition) then_expr anders else_expr
-    IfExpr {
+    If {
         condition: Box<Expr>,
         then_branch: Box<Expr>,
         else_branch: Box<Expr>,
@@ -45,15 +75,21 @@ pub enum Expr {
     // Member access for modules: module.member
     MemberAccess {
         object: Box<Expr>,
-        member: String,
+        member: Ident,
+    },
+    // Assignment to a mutable binding: name = value
+    Assign {
+        name: Ident,
+        value: Box<Expr>,
     },
 }
 
 /// Represents a type constructor definition
 #[derive(Debug, Clone)]
 pub struct TypeConstructor {
-    pub name: String,
+    pub name: Ident,
     pub fields: Vec<String>,  // Field names (can be empty for unit constructors)
+    pub span: Span,           // From the name up to and including the ')' after the fields
 }
 
 /// Represents a pattern for pattern matching
@@ -62,12 +98,12 @@ pub enum Pattern {
     /// Wildcard pattern: _
     Wildcard,
     /// Variable binding: x
-    Variable(String),
+    Variable(Ident),
     /// Literal pattern: 42, "hello", waar, vals
     Literal(Literal),
     /// Constructor pattern: Sommige(x), Kons(h, t)
     Constructor {
-        name: String,
+        name: Ident,
         fields: Vec<Pattern>,
     },
 }
@@ -90,16 +126,30 @@ pub enum Literal {
     Number(f64),
     Boolean(bool),
     String(String),
-    Nil,
 }
 
+/// A statement together with the source text it was parsed from
 #[derive(Debug, Clone)]
-pub enum Stmt {
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+impl Stmt {
+    pub fn new(kind: StmtKind, span: Span) -> Self {
+        Stmt { kind, span }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum StmtKind {
     Expression(Expr),
     Print(Expr),
+    // Binding declaration: laat (immutable) or stel (mutable)
     VarDecl {
-        name: String,
+        name: Ident,
         initializer: Expr,
+        mutable: bool,
     },
     Block(Vec<Stmt>),
     If {
@@ -117,17 +167,29 @@ pub enum Stmt {
     },
     // Type declaration (ADT)
     TypeDecl {
-        name: String,
+        name: Ident,
         constructors: Vec<TypeConstructor>,
     },
     // Module import: laai "path" as name
     Import {
         path: String,
-        alias: String,
+        alias: Ident,
     },
     // Exported constant declaration
     ExportVarDecl {
-        name: String,
+        name: Ident,
         initializer: Expr,
     },
+    // Named function declaration: funksie name(params) { stmts }
+    FunctionDecl {
+        name: Ident,
+        params: Vec<Ident>,
+        body: Vec<Stmt>,
+    },
+    // Exported function declaration
+    ExportFunctionDecl {
+        name: Ident,
+        params: Vec<Ident>,
+        body: Vec<Stmt>,
+    },
 }
@@ -1,29 +1,33 @@
-use crate::value::{UpvalueDescriptor, Value};
+use std::cell::Cell;
+use std::rc::Rc;
 
-#[derive(Debug, Clone)]
+use crate::span::{SourceFile, Span};
+use crate::symbol::Symbol;
+use crate::value::Value;
+
+/// An instruction's opcode: the first byte of it in a chunk's code. Operands
+/// follow in little-endian order; `u16` unless noted otherwise.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OpCode {
-    Constant(usize),    // Push constant from pool
+    Constant,           // [constant] Push constant from pool
     Pop,                // Pop top of stack
 
-    // Global variables
-    GetGlobal(String),  // Load global variable onto stack
-    SetGlobal(String),  // Store top of stack in global variable
-    DefineGlobal(String), // Define a new global variable
+    // Global variables, by the slot the compiler gave each name
+    GetGlobal,          // [slot] Load global variable onto stack
+    SetGlobal,          // [slot] Store top of stack in global variable
+    DefineGlobal,       // [slot] Define a new global variable
 
     // Local variables (stack-based)
-    GetLocal(usize),    // Load local at stack offset onto stack
-    SetLocal(usize),    // Store top of stack at stack offset
+    GetLocal,           // [slot] Load local at stack offset onto stack
+    SetLocal,           // [slot] Store top of stack at stack offset
 
     // Upvalues (closure captures)
-    GetUpvalue(usize),  // Load upvalue onto stack
-    SetUpvalue(usize),  // Store top of stack in upvalue
-    Closure(usize, Vec<UpvalueDescriptor>), // Create closure from function constant
+    GetUpvalue,         // [index] Load upvalue onto stack
+    SetUpvalue,         // [index] Store top of stack in upvalue
+    Closure,            // [constant, count, count x (is_local: u8, index)] Create closure from function constant
     CloseUpvalue,       // Close the topmost local variable
 
-    // Legacy aliases (will be removed)
-    GetVar(String),     // Load variable onto stack (deprecated)
-    SetVar(String),     // Store top of stack in variable (deprecated)
-
     // Arithmetic
     Add,                // + (also string concatenation)
     Subtract,           // -
@@ -40,40 +44,98 @@ pub enum OpCode {
     Greater,            // >
     GreaterEqual,       // >=
 
-    // Logical
+    // Logical (&& and || compile to jumps)
     Not,                // !
-    And,                // && (short-circuit)
-    Or,                 // || (short-circuit)
 
     // Control flow
     Print,              // Print top of stack
-    Jump(usize),        // Unconditional jump
-    JumpIfFalse(usize), // Jump if top of stack is falsy
+    Jump,               // [target: u32] Unconditional jump
+    JumpIfFalse,        // [target: u32] Jump if top of stack is falsy
 
     // Functions
-    Call(usize),        // Call function with N arguments
-    TailCall(usize),    // Tail call - reuse current frame
+    Call,               // [count: u8] Call function with N arguments
+    TailCall,           // [count: u8] Tail call - reuse current frame
     Return,             // Return from function (with value on stack)
 
     // Lists
-    MakeList(usize),    // Create list from N values on stack
+    MakeList,           // [count] Create list from N values on stack
     GetIndex,           // Get element at index: stack[list, index] -> value
 
     // Pattern matching
-    CheckConstructor(String, usize),  // Check if TOS is constructor with name and arity
-    GetField(usize),                   // Get field at index from ADT at TOS (doesn't pop)
-    GetFieldPop(usize),                // Get field at index from ADT and pop the ADT
-    Dup,                               // Duplicate top of stack
+    CheckConstructor,   // [name, arity] Check if TOS is constructor with name and arity
+    GetFieldPop,        // [index] Get field at index from ADT and pop the ADT
+    Dup,                // Duplicate top of stack
 
     // Modules
-    LoadModule(String, String),        // Load module: (path, alias) -> pushes Module value
-    GetMember(String),                 // Get member from module on stack
+    LoadModule,         // [path name, alias name] Load module -> pushes Module value
+    GetMember,          // [name] Get member from module on stack
+}
+
+impl OpCode {
+    const ALL: [OpCode; 37] = [
+        OpCode::Constant,
+        OpCode::Pop,
+        OpCode::GetGlobal,
+        OpCode::SetGlobal,
+        OpCode::DefineGlobal,
+        OpCode::GetLocal,
+        OpCode::SetLocal,
+        OpCode::GetUpvalue,
+        OpCode::SetUpvalue,
+        OpCode::Closure,
+        OpCode::CloseUpvalue,
+        OpCode::Add,
+        OpCode::Subtract,
+        OpCode::Multiply,
+        OpCode::Divide,
+        OpCode::Modulo,
+        OpCode::Negate,
+        OpCode::Equal,
+        OpCode::NotEqual,
+        OpCode::Less,
+        OpCode::LessEqual,
+        OpCode::Greater,
+        OpCode::GreaterEqual,
+        OpCode::Not,
+        OpCode::Print,
+        OpCode::Jump,
+        OpCode::JumpIfFalse,
+        OpCode::Call,
+        OpCode::TailCall,
+        OpCode::Return,
+        OpCode::MakeList,
+        OpCode::GetIndex,
+        OpCode::CheckConstructor,
+        OpCode::GetFieldPop,
+        OpCode::Dup,
+        OpCode::LoadModule,
+        OpCode::GetMember,
+    ];
+
+    pub fn from_byte(byte: u8) -> Option<OpCode> {
+        OpCode::ALL.get(byte as usize).copied()
+    }
+}
+
+/// A named local variable of a chunk, for debuggers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalInfo {
+    pub name: Symbol,
+    pub slot: usize,   // Stack slot in the frame
+    pub start: usize,  // Code offsets where it is in scope: start..end
+    pub end: usize,
 }
 
 #[derive(Debug, Clone)]
 pub struct Chunk {
-    pub code: Vec<OpCode>,
+    pub code: Vec<u8>,
     pub constants: Vec<Value>,
+    pub names: Vec<Symbol>,              // Names instructions refer to: constructors, members, module paths
+    pub spans: Vec<Span>,                // Source span of each byte of code
+    pub file: Option<Rc<SourceFile>>,    // Source the spans point into
+    pub module: Cell<usize>,             // Global table the slots index: 0 for the program, set for a loaded module
+    pub locals: Vec<LocalInfo>,          // Named locals, for the debugger
+    pub upvalue_names: Vec<Symbol>,      // Name of each upvalue of the function, for the debugger
 }
 
 impl Chunk {
@@ -81,26 +143,117 @@ impl Chunk {
         Chunk {
             code: Vec::new(),
             constants: Vec::new(),
+            names: Vec::new(),
+            spans: Vec::new(),
+            file: None,
+            module: Cell::new(0),
+            locals: Vec::new(),
+            upvalue_names: Vec::new(),
         }
     }
 
-    pub fn write(&mut self, op: OpCode) -> usize {
-        self.code.push(op);
+    /// Appends an opcode, returning its offset
+    pub fn write(&mut self, op: OpCode, span: Span) -> usize {
+        self.write_byte(op as u8, span);
         self.code.len() - 1
     }
 
+    pub fn write_byte(&mut self, byte: u8, span: Span) {
+        self.code.push(byte);
+        self.spans.push(span);
+    }
+
+    pub fn write_u16(&mut self, value: u16, span: Span) {
+        for byte in value.to_le_bytes() {
+            self.write_byte(byte, span);
+        }
+    }
+
+    pub fn write_u32(&mut self, value: u32, span: Span) {
+        for byte in value.to_le_bytes() {
+            self.write_byte(byte, span);
+        }
+    }
+
+    pub fn read_u16(&self, offset: usize) -> u16 {
+        u16::from_le_bytes([self.code[offset], self.code[offset + 1]])
+    }
+
+    pub fn read_u32(&self, offset: usize) -> u32 {
+        u32::from_le_bytes([
+            self.code[offset],
+            self.code[offset + 1],
+            self.code[offset + 2],
+            self.code[offset + 3],
+        ])
+    }
+
+    /// Source span of the instruction whose code includes `offset`
+    pub fn span_at(&self, offset: usize) -> Span {
+        self.spans.get(offset).copied().unwrap_or_default()
+    }
+
+    /// Source line of the instruction at `offset`, or 0 if it has none
+    pub fn line_at(&self, offset: usize) -> usize {
+        self.span_at(offset).line
+    }
+
     pub fn add_constant(&mut self, value: Value) -> usize {
         self.constants.push(value);
         self.constants.len() - 1
     }
 
+    /// Index of `name` in the name table, adding it the first time
+    pub fn add_name(&mut self, name: Symbol) -> usize {
+        match self.names.iter().position(|&known| known == name) {
+            Some(index) => index,
+            None => {
+                self.names.push(name);
+                self.names.len() - 1
+            }
+        }
+    }
+
+    /// Points the jump instruction at `offset` at `target`
     pub fn patch_jump(&mut self, offset: usize, target: usize) {
-        match &mut self.code[offset] {
-            OpCode::Jump(ref mut addr) => *addr = target,
-            OpCode::JumpIfFalse(ref mut addr) => *addr = target,
+        match OpCode::from_byte(self.code[offset]) {
+            Some(OpCode::Jump | OpCode::JumpIfFalse) => {
+                let bytes = (target as u32).to_le_bytes();
+                self.code[offset + 1..offset + 5].copy_from_slice(&bytes);
+            }
             _ => panic!("Tried to patch non-jump instruction"),
         }
     }
+
+    /// Number of bytes taken by the instruction at `offset`, operands included.
+    /// None if the code there is not a complete instruction.
+    pub fn instruction_len(&self, offset: usize) -> Option<usize> {
+        let op = OpCode::from_byte(*self.code.get(offset)?)?;
+        let len = match op {
+            OpCode::Constant
+            | OpCode::GetGlobal
+            | OpCode::SetGlobal
+            | OpCode::DefineGlobal
+            | OpCode::GetLocal
+            | OpCode::SetLocal
+            | OpCode::GetUpvalue
+            | OpCode::SetUpvalue
+            | OpCode::MakeList
+            | OpCode::GetFieldPop
+            | OpCode::GetMember => 3,
+            OpCode::CheckConstructor | OpCode::LoadModule => 5,
+            OpCode::Jump | OpCode::JumpIfFalse => 5,
+            OpCode::Call | OpCode::TailCall => 2,
+            OpCode::Closure => {
+                if offset + 5 > self.code.len() {
+                    return None;
+                }
+                5 + 3 * self.read_u16(offset + 3) as usize
+            }
+            _ => 1,
+        };
+        (offset + len <= self.code.len()).then_some(len)
+    }
 }
 
 impl Default for Chunk {
@@ -1,25 +1,41 @@
-use crate::ast::{Expr, LambdaBody, Literal, Pattern, Stmt};
-use crate::bytecode::{Chunk, OpCode};
+use crate::ast::{Expr, ExprKind, Ident, LambdaBody, Literal, Pattern, Stmt, StmtKind};
+use crate::bytecode::{Chunk, LocalInfo, OpCode};
+use crate::error::{ArkaanError, ErrorKind};
+use crate::span::{SourceFile, Span};
+use crate::symbol::Symbol;
 use crate::token::TokenType;
 use crate::value::{Function, TypeConstructorDef, UpvalueDescriptor, Value};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 
 // Hidden local variable names used for pattern matching
 const MATCH_SCRUTINEE: &str = "$match";
 const CTOR_HIDDEN_LOCAL: &str = "$ctor";
 
+/// How a local was bound, which decides whether it may be assigned to
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Binding {
+    Constant,   // 'laat', 'funksie', pattern variables and hidden locals
+    Variable,   // 'stel'
+    Parameter,
+}
+
 #[derive(Debug, Clone)]
 struct Local {
     name: String,
     depth: usize,
     is_captured: bool,  // True if this local is captured by a closure
+    binding: Binding,
+    declared: bool,     // False for a function's slot until its declaration is compiled
+    start: usize,       // Code offset from which it is in scope
 }
 
 #[derive(Debug, Clone)]
 struct CompilerUpvalue {
+    name: String,
     index: usize,
     is_local: bool,
+    binding: Binding,   // Mirrors the captured binding
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -62,6 +78,9 @@ impl FunctionCompiler {
                 name: String::new(),
                 depth: 0,
                 is_captured: false,
+                binding: Binding::Constant,
+                declared: true,
+                start: 0,
             });
         }
 
@@ -74,6 +93,11 @@ pub struct Compiler {
     enclosing: Option<Box<Compiler>>,
     functions: Vec<Rc<Chunk>>,
     exported_symbols: HashSet<String>,
+    global_bindings: HashMap<String, Binding>,  // Whether each global declared here may be reassigned
+    globals: Vec<String>,                // Name of each global slot, in slot order
+    global_slots: HashMap<String, usize>,
+    file: Option<Rc<SourceFile>>,        // Source being compiled, recorded in every chunk
+    span: Span,                          // Span of the node being compiled
 }
 
 impl Compiler {
@@ -83,26 +107,82 @@ impl Compiler {
             enclosing: None,
             functions: Vec::new(),
             exported_symbols: HashSet::new(),
+            global_bindings: HashMap::new(),
+            globals: Vec::new(),
+            global_slots: HashMap::new(),
+            file: None,
+            span: Span::default(),
         }
     }
 
+    pub fn set_file(&mut self, file: Rc<SourceFile>) {
+        self.file = Some(file);
+    }
+
     pub fn get_exports(&self) -> &HashSet<String> {
         &self.exported_symbols
     }
 
-    pub fn compile(&mut self, statements: Vec<Stmt>) -> Result<(Chunk, Vec<Rc<Chunk>>), String> {
-        for stmt in statements {
+    /// The global every slot stands for. Slots are only ever added, so the
+    /// table of a REPL session stays valid for code compiled earlier.
+    pub fn global_names(&self) -> &[String] {
+        &self.globals
+    }
+
+    pub fn compile(&mut self, statements: Vec<Stmt>) -> Result<(Chunk, Vec<Rc<Chunk>>), ArkaanError> {
+        self.declare_globals(&statements);
+        for stmt in hoist_functions(statements) {
             self.compile_stmt(stmt)?;
         }
-        let nil_idx = self.add_constant(Value::Nil);
-        self.emit(OpCode::Constant(nil_idx));
+        self.emit_constant(Value::Nil)?;
         self.emit(OpCode::Return);
 
-        let main_chunk = self.current.chunk.clone();
+        let mut main_chunk = self.current.chunk.clone();
+        main_chunk.file = self.file.clone();
         let functions = self.functions.clone();
         Ok((main_chunk, functions))
     }
 
+    /// Compiles one REPL entry against the globals declared by earlier entries.
+    /// If the entry ends with a bare expression, its value is returned from the chunk.
+    pub fn compile_repl(&mut self, statements: Vec<Stmt>) -> Result<(Chunk, Vec<Rc<Chunk>>), ArkaanError> {
+        // Every entry gets a fresh script chunk; global bookkeeping carries over
+        self.current = FunctionCompiler::new(String::from("<repl>"), FunctionType::Script, 0);
+        self.enclosing = None;
+        self.functions.clear();
+
+        // Don't remember declarations from an entry that failed to compile
+        let global_bindings = self.global_bindings.clone();
+        if let Err(e) = self.compile_repl_entry(statements) {
+            self.global_bindings = global_bindings;
+            return Err(e);
+        }
+
+        let mut chunk = self.current.chunk.clone();
+        chunk.file = self.file.clone();
+        Ok((chunk, self.functions.clone()))
+    }
+
+    fn compile_repl_entry(&mut self, mut statements: Vec<Stmt>) -> Result<(), ArkaanError> {
+        let result = match statements.last().map(|stmt| &stmt.kind) {
+            Some(StmtKind::Expression(_)) => statements.pop(),
+            _ => None,
+        };
+
+        self.declare_globals(&statements);
+        for stmt in hoist_functions(statements) {
+            self.compile_stmt(stmt)?;
+        }
+
+        if let Some(Stmt { kind: StmtKind::Expression(expr), .. }) = result {
+            self.compile_expr(expr)?;
+        } else {
+            self.emit_constant(Value::Nil)?;
+        }
+        self.emit(OpCode::Return);
+        Ok(())
+    }
+
     fn begin_scope(&mut self) {
         self.current.scope_depth += 1;
     }
@@ -110,33 +190,67 @@ impl Compiler {
     fn end_scope(&mut self) {
         self.current.scope_depth -= 1;
 
-        // Pop locals that are going out of scope
+        // Pop locals that are going out of scope, closing any captured by closures
         while !self.current.locals.is_empty()
             && self.current.locals.last().unwrap().depth > self.current.scope_depth
         {
-            self.current.locals.pop();
+            let local = self.pop_local();
+            self.emit_local_pop(&local);
+        }
+    }
+
+    /// Takes the innermost local out of scope, recording where it was in
+    /// scope for the debugger
+    fn pop_local(&mut self) -> Local {
+        let local = self.current.locals.pop().unwrap();
+        self.record_local(&local, self.current.locals.len());
+        local
+    }
+
+    fn record_local(&mut self, local: &Local, slot: usize) {
+        // Slot 0 of a function and the locals pattern matching hides have no name to show
+        if local.name.is_empty() || local.name.starts_with('$') {
+            return;
+        }
+        let end = self.current_offset();
+        self.current.chunk.locals.push(LocalInfo {
+            name: Symbol::intern(&local.name),
+            slot,
+            start: local.start,
+            end,
+        });
+    }
+
+    fn emit_local_pop(&mut self, local: &Local) {
+        if local.is_captured {
+            self.emit(OpCode::CloseUpvalue);
+        } else {
             self.emit(OpCode::Pop);
         }
     }
 
-    fn add_local(&mut self, name: String) -> Result<(), String> {
+    fn add_local(&mut self, name: String, binding: Binding) -> Result<(), ArkaanError> {
         // Check for duplicate in current scope
         for local in self.current.locals.iter().rev() {
             if local.depth < self.current.scope_depth {
                 break;
             }
             if local.name == name {
-                return Err(format!(
+                return Err(self.error(ErrorKind::DuplicateDefinition, format!(
                     "Konstante '{}' is reeds in hierdie omvang gedefinieer.",
                     name
-                ));
+                )));
             }
         }
 
+        let start = self.current_offset();
         self.current.locals.push(Local {
             name,
             depth: self.current.scope_depth,
             is_captured: false,
+            binding,
+            declared: true,
+            start,
         });
         Ok(())
     }
@@ -151,28 +265,28 @@ impl Compiler {
     }
 
     fn resolve_upvalue(&mut self, name: &str) -> Option<usize> {
-        // Check if there's an enclosing compiler
-        if self.enclosing.is_none() {
-            return None;
-        }
+        // Only nested functions have an enclosing compiler to capture from
+        let enclosing = self.enclosing.as_mut()?;
 
         // Try to resolve as a local in the enclosing scope
-        let enclosing = self.enclosing.as_mut().unwrap();
         if let Some(local_idx) = enclosing.resolve_local(name) {
             // Mark the local as captured
-            enclosing.current.locals[local_idx].is_captured = true;
-            return Some(self.add_upvalue(local_idx, true));
+            let local = &mut enclosing.current.locals[local_idx];
+            local.is_captured = true;
+            let binding = local.binding;
+            return Some(self.add_upvalue(name, local_idx, true, binding));
         }
 
         // Try to resolve as an upvalue in the enclosing scope (for nested closures)
         if let Some(upvalue_idx) = enclosing.resolve_upvalue(name) {
-            return Some(self.add_upvalue(upvalue_idx, false));
+            let binding = enclosing.current.upvalues[upvalue_idx].binding;
+            return Some(self.add_upvalue(name, upvalue_idx, false, binding));
         }
 
         None
     }
 
-    fn add_upvalue(&mut self, index: usize, is_local: bool) -> usize {
+    fn add_upvalue(&mut self, name: &str, index: usize, is_local: bool, binding: Binding) -> usize {
         // Check if we already have this upvalue
         for (i, upvalue) in self.current.upvalues.iter().enumerate() {
             if upvalue.index == index && upvalue.is_local == is_local {
@@ -181,7 +295,7 @@ impl Compiler {
         }
 
         // Add new upvalue
-        self.current.upvalues.push(CompilerUpvalue { index, is_local });
+        self.current.upvalues.push(CompilerUpvalue { name: name.to_string(), index, is_local, binding });
         self.current.upvalues.len() - 1
     }
 
@@ -200,18 +314,16 @@ impl Compiler {
     fn compile_callable<F>(
         &mut self,
         name: String,
-        params: Vec<String>,
+        params: Vec<Ident>,
         compile_body: F,
-    ) -> Result<(Rc<Chunk>, usize, Vec<UpvalueDescriptor>), String>
+    ) -> Result<(Rc<Function>, Vec<UpvalueDescriptor>), ArkaanError>
     where
-        F: FnOnce(&mut Self) -> Result<(), String>,
+        F: FnOnce(&mut Self) -> Result<(), ArkaanError>,
     {
-        let arity = params.len();
-
         // Save current compiler state
         let old_current = std::mem::replace(
             &mut self.current,
-            FunctionCompiler::new(name, FunctionType::Function, arity),
+            FunctionCompiler::new(name, FunctionType::Function, params.len()),
         );
         let old_enclosing = self.enclosing.take();
 
@@ -221,6 +333,11 @@ impl Compiler {
             enclosing: old_enclosing,
             functions: Vec::new(),
             exported_symbols: HashSet::new(),
+            global_bindings: HashMap::new(),
+            globals: Vec::new(),
+            global_slots: HashMap::new(),
+            file: None,
+            span: Span::default(),
         }));
 
         // Begin function scope
@@ -228,14 +345,23 @@ impl Compiler {
 
         // Bind parameters as locals
         for param in params {
-            self.add_local(param)?;
+            self.add_local(param.name, Binding::Parameter)?;
         }
 
         // Compile body using provided closure
         compile_body(self)?;
 
+        // Parameters and locals of the outermost block stay in scope to the end
+        for (slot, local) in std::mem::take(&mut self.current.locals).iter().enumerate() {
+            self.record_local(local, slot);
+        }
+
         // Get the compiled function chunk and upvalue info
-        let function_chunk = self.current.chunk.clone();
+        let mut function_chunk = self.current.chunk.clone();
+        function_chunk.file = self.file.clone();
+        function_chunk.upvalue_names = self.current.upvalues.iter().map(|upvalue| Symbol::intern(&upvalue.name)).collect();
+        let function_name = self.current.function_name.clone();
+        let arity = self.current.arity;
         let upvalues = self.extract_upvalues();
 
         // Restore compiler state
@@ -244,56 +370,75 @@ impl Compiler {
             self.enclosing = enclosing.enclosing;
         }
 
-        // Store the function chunk and return it
+        // Store the function chunk and return the function
         let chunk = Rc::new(function_chunk);
         self.functions.push(Rc::clone(&chunk));
 
-        Ok((chunk, arity, upvalues))
+        let function = Rc::new(Function {
+            name: function_name,
+            arity,
+            chunk,
+            upvalue_count: upvalues.len(),
+            span: self.span,
+        });
+
+        Ok((function, upvalues))
     }
 
-    fn compile_stmt(&mut self, stmt: Stmt) -> Result<(), String> {
-        match stmt {
-            Stmt::Expression(expr) => {
+    fn compile_stmt(&mut self, stmt: Stmt) -> Result<(), ArkaanError> {
+        // Instructions emitted for this statement point at its span
+        let enclosing_span = std::mem::replace(&mut self.span, stmt.span);
+        let result = self.compile_stmt_kind(stmt.kind);
+        self.span = enclosing_span;
+        result
+    }
+
+    fn compile_stmt_kind(&mut self, kind: StmtKind) -> Result<(), ArkaanError> {
+        match kind {
+            StmtKind::Expression(expr) => {
                 self.compile_expr(expr)?;
                 self.emit(OpCode::Pop);
             }
-            Stmt::Print(expr) => {
+            StmtKind::Print(expr) => {
                 self.compile_expr(expr)?;
                 self.emit(OpCode::Print);
             }
-            Stmt::VarDecl { name, initializer } => {
+            StmtKind::VarDecl {
+                name: Ident { name, .. },
+                initializer,
+                mutable,
+            } => {
                 self.compile_expr(initializer)?;
 
                 if self.current.scope_depth > 0 {
-                    // Local constant
-                    self.add_local(name)?;
+                    // Local binding
+                    let binding = if mutable { Binding::Variable } else { Binding::Constant };
+                    self.add_local(name, binding)?;
                     // Value is already on stack, that's the local
                 } else {
-                    // Global constant
-                    self.emit(OpCode::DefineGlobal(name));
+                    // Global binding
+                    self.define_global(name, mutable)?;
                 }
             }
-            Stmt::Block(statements) => {
+            StmtKind::Block(statements) => {
                 self.begin_scope();
-                for stmt in statements {
-                    self.compile_stmt(stmt)?;
-                }
+                self.compile_block(statements)?;
                 self.end_scope();
             }
-            Stmt::If {
+            StmtKind::If {
                 condition,
                 then_branch,
                 else_branch,
             } => {
                 self.compile_expr(condition)?;
 
-                let jump_to_else = self.emit(OpCode::JumpIfFalse(0));
+                let jump_to_else = self.emit_jump(OpCode::JumpIfFalse);
                 self.emit(OpCode::Pop);
 
                 self.compile_stmt(*then_branch)?;
 
                 if let Some(else_stmt) = else_branch {
-                    let jump_over_else = self.emit(OpCode::Jump(0));
+                    let jump_over_else = self.emit_jump(OpCode::Jump);
 
                     let else_start = self.current_offset();
                     self.current.chunk.patch_jump(jump_to_else, else_start);
@@ -309,101 +454,164 @@ impl Compiler {
                     self.emit(OpCode::Pop);
                 }
             }
-            Stmt::While { condition, body } => {
+            StmtKind::While { condition, body } => {
                 let loop_start = self.current_offset();
 
                 self.compile_expr(condition)?;
 
-                let exit_jump = self.emit(OpCode::JumpIfFalse(0));
+                let exit_jump = self.emit_jump(OpCode::JumpIfFalse);
                 self.emit(OpCode::Pop);
 
                 self.compile_stmt(*body)?;
 
-                self.emit(OpCode::Jump(loop_start));
+                self.emit_jump_to(OpCode::Jump, loop_start);
 
                 let after_loop = self.current_offset();
                 self.current.chunk.patch_jump(exit_jump, after_loop);
                 self.emit(OpCode::Pop);
             }
-            Stmt::Return { value } => {
+            StmtKind::Return { value } => {
                 if self.current.function_type == FunctionType::Script {
-                    return Err("Kan nie buite 'n funksie terugkeer nie.".to_string());
+                    return Err(self.error(ErrorKind::ReturnOutsideFunction, "Kan nie buite 'n funksie terugkeer nie."));
                 }
 
                 if let Some(expr) = value {
                     // Check for tail call optimization
-                    if let Expr::Call { callee, arguments } = expr {
-                        self.compile_tail_call(callee, arguments)?;
+                    if let ExprKind::Call { callee, arguments } = expr.kind {
+                        self.compile_tail_call(*callee, arguments, expr.span)?;
                     } else {
                         self.compile_expr(expr)?;
                         self.emit(OpCode::Return);
                     }
                 } else {
-                    let nil_idx = self.add_constant(Value::Nil);
-                    self.emit(OpCode::Constant(nil_idx));
+                    self.emit_constant(Value::Nil)?;
                     self.emit(OpCode::Return);
                 }
             }
-            Stmt::TypeDecl { name, constructors } => {
+            StmtKind::TypeDecl { name: Ident { name, .. }, constructors } => {
                 // For each constructor, create a TypeConstructor value and define it as a global
                 for constructor in constructors {
                     let constructor_def = TypeConstructorDef {
                         type_name: name.clone(),
-                        constructor_name: constructor.name.clone(),
+                        constructor_name: constructor.name.name.clone(),
                         arity: constructor.fields.len(),
+                        tag: Symbol::intern(&constructor.name.name),
                     };
 
-                    let constructor_value = Value::TypeConstructor(Rc::new(constructor_def));
-                    let const_idx = self.add_constant(constructor_value);
-                    self.emit(OpCode::Constant(const_idx));
-                    self.emit(OpCode::DefineGlobal(constructor.name));
+                    self.emit_constant(Value::TypeConstructor(Rc::new(constructor_def)))?;
+                    self.define_global(constructor.name.name, false)?;
                 }
             }
-            Stmt::Import { path, alias } => {
+            StmtKind::Import { path, alias: Ident { name: alias, .. } } => {
                 // Emit LoadModule instruction which will load and push the module
-                self.emit(OpCode::LoadModule(path, alias.clone()));
-                // Define the module as a global variable
-                self.emit(OpCode::DefineGlobal(alias));
+                let path = self.name(&path);
+                let alias_name = self.name(&alias);
+                self.emit_u16(OpCode::LoadModule, path)?;
+                self.write_u16(alias_name)?;
+                // Define the module as a global constant
+                self.define_global(alias, false)?;
             }
-            Stmt::ExportVarDecl { name, initializer } => {
+            StmtKind::ExportVarDecl { name: Ident { name, .. }, initializer } => {
                 // Track this symbol as exported
                 self.exported_symbols.insert(name.clone());
 
                 // Compile like a regular global constant declaration
                 self.compile_expr(initializer)?;
-                self.emit(OpCode::DefineGlobal(name));
+                self.define_global(name, false)?;
+            }
+            StmtKind::FunctionDecl { name: Ident { name, .. }, params, body } => {
+                self.compile_function(name.clone(), params, body)?;
+
+                if self.current.scope_depth > 0 {
+                    // The slot was reserved when the enclosing block was entered
+                    let slot = self.resolve_local(&name).unwrap();
+                    self.emit_u16(OpCode::SetLocal, slot)?;
+                    self.emit(OpCode::Pop);
+                    self.current.locals[slot].declared = true;
+                } else {
+                    self.define_global(name, false)?;
+                }
+            }
+            StmtKind::ExportFunctionDecl { name: Ident { name, .. }, params, body } => {
+                // Track this symbol as exported
+                self.exported_symbols.insert(name.clone());
+
+                self.compile_function(name.clone(), params, body)?;
+                self.define_global(name, false)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compiles the statements of a block, function or lambda body.
+    ///
+    /// A slot is reserved for each local function declaration before any
+    /// statement runs, so functions in the same block can call themselves and
+    /// each other whatever their order. The function is only made where it is
+    /// declared, after the locals before it that it may capture, so the
+    /// block's own statements may not use it before that.
+    fn compile_block(&mut self, statements: Vec<Stmt>) -> Result<(), ArkaanError> {
+        if self.current.scope_depth > 0 {
+            for stmt in &statements {
+                if let StmtKind::FunctionDecl { name, .. } = &stmt.kind {
+                    self.emit_constant(Value::Nil)?;
+                    self.add_local(name.name.clone(), Binding::Constant)?;
+                    self.current.locals.last_mut().unwrap().declared = false;
+                }
             }
         }
 
+        for stmt in statements {
+            self.compile_stmt(stmt)?;
+        }
         Ok(())
     }
 
-    fn compile_expr(&mut self, expr: Expr) -> Result<(), String> {
-        match expr {
-            Expr::Literal(lit) => {
+    fn compile_expr(&mut self, expr: Expr) -> Result<(), ArkaanError> {
+        // Instructions emitted for this expression point at its span
+        let enclosing_span = std::mem::replace(&mut self.span, expr.span);
+        let result = self.compile_expr_kind(expr.kind);
+        self.span = enclosing_span;
+        result
+    }
+
+    fn compile_expr_kind(&mut self, kind: ExprKind) -> Result<(), ArkaanError> {
+        match kind {
+            ExprKind::Literal(lit) => {
                 let value = self.literal_to_value(&lit);
-                let idx = self.add_constant(value);
-                self.emit(OpCode::Constant(idx));
+                self.emit_constant(value)?;
             }
-            Expr::Variable(name) => {
+            ExprKind::Variable(name) => {
                 match self.resolve_variable(&name) {
-                    VarLocation::Local(slot) => self.emit(OpCode::GetLocal(slot)),
-                    VarLocation::Upvalue(idx) => self.emit(OpCode::GetUpvalue(idx)),
-                    VarLocation::Global => self.emit(OpCode::GetGlobal(name)),
+                    VarLocation::Local(slot) if !self.current.locals[slot].declared => {
+                        return Err(self
+                            .error(
+                                ErrorKind::UseBeforeDeclaration,
+                                format!("Funksie '{}' word gebruik voordat dit verklaar is.", name),
+                            )
+                            .with_help("Verklaar die funksie bo hierdie lyn; net ander funksies in die blok mag dit vroeër roep."));
+                    }
+                    VarLocation::Local(slot) => self.emit_u16(OpCode::GetLocal, slot)?,
+                    VarLocation::Upvalue(idx) => self.emit_u16(OpCode::GetUpvalue, idx)?,
+                    VarLocation::Global => {
+                        let slot = self.global_slot(&name);
+                        self.emit_u16(OpCode::GetGlobal, slot)?
+                    }
                 };
             }
-            Expr::Grouping(inner) => {
+            ExprKind::Grouping(inner) => {
                 self.compile_expr(*inner)?;
             }
-            Expr::Unary { operator, right } => {
+            ExprKind::Unary { operator, right } => {
                 self.compile_expr(*right)?;
                 match operator.token_type {
                     TokenType::Minus => self.emit(OpCode::Negate),
                     TokenType::Bang => self.emit(OpCode::Not),
-                    _ => return Err("Onbekende unêre operator.".to_string()),
+                    _ => return Err(self.error(ErrorKind::Internal, "Onbekende unêre operator.")),
                 };
             }
-            Expr::Binary {
+            ExprKind::Binary {
                 left,
                 operator,
                 right,
@@ -411,7 +619,7 @@ impl Compiler {
                 match operator.token_type {
                     TokenType::And => {
                         self.compile_expr(*left)?;
-                        let jump = self.emit(OpCode::JumpIfFalse(0));
+                        let jump = self.emit_jump(OpCode::JumpIfFalse);
                         self.emit(OpCode::Pop);
                         self.compile_expr(*right)?;
                         let after = self.current_offset();
@@ -419,8 +627,8 @@ impl Compiler {
                     }
                     TokenType::Or => {
                         self.compile_expr(*left)?;
-                        let else_jump = self.emit(OpCode::JumpIfFalse(0));
-                        let end_jump = self.emit(OpCode::Jump(0));
+                        let else_jump = self.emit_jump(OpCode::JumpIfFalse);
+                        let end_jump = self.emit_jump(OpCode::Jump);
 
                         let else_branch = self.current_offset();
                         self.current.chunk.patch_jump(else_jump, else_branch);
@@ -446,63 +654,47 @@ impl Compiler {
                             TokenType::LessEqual => self.emit(OpCode::LessEqual),
                             TokenType::Greater => self.emit(OpCode::Greater),
                             TokenType::GreaterEqual => self.emit(OpCode::GreaterEqual),
-                            _ => return Err("Onbekende binêre operator.".to_string()),
+                            _ => return Err(self.error(ErrorKind::Internal, "Onbekende binêre operator.")),
                         };
                     }
                 }
             }
-            Expr::Call { callee, arguments } => {
+            ExprKind::Call { callee, arguments } => {
                 // Compile the callee (the function to call)
                 self.compile_expr(*callee)?;
 
                 // Compile arguments
                 let arg_count = self.compile_arguments(arguments)?;
-                self.emit(OpCode::Call(arg_count));
+                self.emit_u8(OpCode::Call, arg_count);
             }
-            Expr::Lambda { params, body } => {
+            ExprKind::Lambda { params, body } => {
                 // Compile lambda similar to a function
-                let (chunk, arity, upvalues) = self.compile_lambda(params, body)?;
-
-                // Create function value
-                let function = Value::Function(Rc::new(Function {
-                    name: String::from("<lambda>"),
-                    arity,
-                    chunk,
-                    upvalue_count: upvalues.len(),
-                }));
-
-                let const_idx = self.add_constant(function);
-
-                // Emit Closure opcode if there are upvalues, otherwise just Constant
-                if upvalues.is_empty() {
-                    self.emit(OpCode::Constant(const_idx));
-                } else {
-                    self.emit(OpCode::Closure(const_idx, upvalues));
-                }
+                let (function, upvalues) = self.compile_lambda(params, body)?;
+                self.emit_closure(function, upvalues)?;
             }
-            Expr::List(elements) => {
+            ExprKind::List(elements) => {
                 // Compile each element and push onto stack
                 let count = elements.len();
                 for elem in elements {
                     self.compile_expr(elem)?;
                 }
                 // Create list from stack values
-                self.emit(OpCode::MakeList(count));
+                self.emit_u16(OpCode::MakeList, count)?;
             }
-            Expr::Index { object, index } => {
+            ExprKind::Index { object, index } => {
                 // Compile the object (list) and index
                 self.compile_expr(*object)?;
                 self.compile_expr(*index)?;
                 self.emit(OpCode::GetIndex);
             }
-            Expr::Match { value, arms } => {
+            ExprKind::Match { value, arms } => {
                 // Begin a scope for the entire match expression
                 self.begin_scope();
 
                 // Evaluate the value to match and store as hidden local
                 // This ensures pattern bindings have correct stack indices
                 self.compile_expr(*value)?;
-                self.add_local(String::from(MATCH_SCRUTINEE))?;
+                self.add_local(String::from(MATCH_SCRUTINEE), Binding::Constant)?;
                 let scrutinee_slot = self.resolve_local(MATCH_SCRUTINEE).unwrap();
 
                 // Track jump addresses
@@ -512,7 +704,7 @@ impl Compiler {
                     let is_last = i == arms.len() - 1;
 
                     // Get a copy of the scrutinee onto the stack
-                    self.emit(OpCode::GetLocal(scrutinee_slot));
+                    self.emit_u16(OpCode::GetLocal, scrutinee_slot)?;
 
                     // Begin a new scope for pattern bindings
                     self.begin_scope();
@@ -529,19 +721,19 @@ impl Compiler {
                     // We want: [result]
 
                     // Save result to scrutinee slot (overwrites scrutinee)
-                    self.emit(OpCode::SetLocal(scrutinee_slot));
+                    self.emit_u16(OpCode::SetLocal, scrutinee_slot)?;
                     // Pop the result from top (it's saved in slot 0)
                     self.emit(OpCode::Pop);
                     // Pop each binding manually (can't use end_scope() - need precise stack control)
                     for _ in 0..bindings {
-                        self.emit(OpCode::Pop);
-                        self.current.locals.pop();
+                        let local = self.pop_local();
+                        self.emit_local_pop(&local);
                     }
                     self.current.scope_depth -= 1;
                     // Stack is now [result] in the scrutinee slot position
 
                     // Jump to end after successful match
-                    end_jumps.push(self.emit(OpCode::Jump(0)));
+                    end_jumps.push(self.emit_jump(OpCode::Jump));
 
                     // Patch the fail jump to come here (next arm)
                     // When pattern fails, we need to clean up the stack:
@@ -568,7 +760,7 @@ impl Compiler {
                 self.current.locals.pop();
                 self.current.scope_depth -= 1;
             }
-            Expr::IfExpr {
+            ExprKind::If {
                 condition,
                 then_branch,
                 else_branch,
@@ -577,14 +769,14 @@ impl Compiler {
                 self.compile_expr(*condition)?;
 
                 // Jump to else if false
-                let else_jump = self.emit(OpCode::JumpIfFalse(0));
+                let else_jump = self.emit_jump(OpCode::JumpIfFalse);
                 self.emit(OpCode::Pop); // Pop condition
 
                 // Compile then branch
                 self.compile_expr(*then_branch)?;
 
                 // Jump past else branch
-                let end_jump = self.emit(OpCode::Jump(0));
+                let end_jump = self.emit_jump(OpCode::Jump);
 
                 // Patch else jump
                 let else_offset = self.current_offset();
@@ -598,11 +790,61 @@ impl Compiler {
                 let end_offset = self.current_offset();
                 self.current.chunk.patch_jump(end_jump, end_offset);
             }
-            Expr::MemberAccess { object, member } => {
+            ExprKind::MemberAccess { object, member: Ident { name: member, .. } } => {
                 // Compile the object (module)
                 self.compile_expr(*object)?;
                 // Emit GetMember instruction
-                self.emit(OpCode::GetMember(member));
+                let member = self.name(&member);
+                self.emit_u16(OpCode::GetMember, member)?;
+            }
+            ExprKind::Assign { name: Ident { name, .. }, value } => {
+                let location = self.resolve_variable(&name);
+                let binding = match location {
+                    VarLocation::Local(slot) => self.current.locals[slot].binding,
+                    VarLocation::Upvalue(idx) => self.current.upvalues[idx].binding,
+                    VarLocation::Global => match self.global_bindings.get(&name) {
+                        Some(&binding) => binding,
+                        // Natives and names nothing declared can't be assigned either
+                        None => {
+                            return Err(self
+                                .error(
+                                    ErrorKind::ImmutableAssignment,
+                                    format!("Kan nie aan '{}' toewys nie: dit is nie met 'stel' verklaar nie.", name),
+                                )
+                                .with_help(format!("Verklaar dit eers met 'stel {} = ...'.", name)));
+                        }
+                    },
+                };
+                match binding {
+                    Binding::Variable => {}
+                    Binding::Constant => {
+                        return Err(self
+                            .error(
+                                ErrorKind::ImmutableAssignment,
+                                format!("Kan nie aan '{}' toewys nie: dit is onveranderlik.", name),
+                            )
+                            .with_help(format!("Verklaar dit met 'stel {} = ...' as dit moet verander.", name)));
+                    }
+                    Binding::Parameter => {
+                        return Err(self
+                            .error(
+                                ErrorKind::ImmutableAssignment,
+                                format!("Kan nie aan parameter '{}' toewys nie: parameters is onveranderlik.", name),
+                            )
+                            .with_help(format!("Kopieer dit in 'n veranderlike, soos 'stel nuut = {}', en verander dit.", name)));
+                    }
+                }
+
+                // Assignment is an expression: the value stays on the stack
+                self.compile_expr(*value)?;
+                match location {
+                    VarLocation::Local(slot) => self.emit_u16(OpCode::SetLocal, slot)?,
+                    VarLocation::Upvalue(idx) => self.emit_u16(OpCode::SetUpvalue, idx)?,
+                    VarLocation::Global => {
+                        let slot = self.global_slot(&name);
+                        self.emit_u16(OpCode::SetGlobal, slot)?
+                    }
+                };
             }
         }
 
@@ -635,7 +877,7 @@ impl Compiler {
     /// - For Wildcard: the value is popped
     /// - For Literal: the value is popped
     /// - For Constructor: the constructor is popped, but field bindings remain
-    fn compile_pattern(&mut self, pattern: &Pattern, can_fail: bool) -> Result<Option<usize>, String> {
+    fn compile_pattern(&mut self, pattern: &Pattern, can_fail: bool) -> Result<Option<usize>, ArkaanError> {
         match pattern {
             Pattern::Wildcard => {
                 // Always matches, pop the value
@@ -645,7 +887,7 @@ impl Compiler {
             Pattern::Variable(name) => {
                 // Bind the value to a local constant
                 // The value is on top of stack and becomes the local's storage
-                self.add_local(name.clone())?;
+                self.add_local(name.name.clone(), Binding::Constant)?;
                 Ok(None)
             }
             Pattern::Literal(lit) => {
@@ -653,13 +895,12 @@ impl Compiler {
                 self.emit(OpCode::Dup);
                 // Compare with literal
                 let const_value = self.literal_to_value(lit);
-                let const_idx = self.add_constant(const_value);
-                self.emit(OpCode::Constant(const_idx));
+                self.emit_constant(const_value)?;
                 self.emit(OpCode::Equal);
                 // Stack now has: [..., scrutinee, bool]
 
                 let fail_jump = if can_fail {
-                    Some(self.emit(OpCode::JumpIfFalse(0)))
+                    Some(self.emit_jump(OpCode::JumpIfFalse))
                 } else {
                     None
                 };
@@ -669,10 +910,12 @@ impl Compiler {
             }
             Pattern::Constructor { name, fields } => {
                 // Check if value is this constructor with correct arity
-                self.emit(OpCode::CheckConstructor(name.clone(), fields.len()));
+                let name = self.name(&name.name);
+                self.emit_u16(OpCode::CheckConstructor, name)?;
+                self.write_u16(fields.len())?;
 
                 let fail_jump = if can_fail {
-                    Some(self.emit(OpCode::JumpIfFalse(0)))
+                    Some(self.emit_jump(OpCode::JumpIfFalse))
                 } else {
                     None
                 };
@@ -686,15 +929,15 @@ impl Compiler {
                 } else {
                     // For multi-field constructors, save the ADT as a hidden local
                     // This ensures field extractions use correct stack indexing
-                    self.add_local(String::from(CTOR_HIDDEN_LOCAL))?;
+                    self.add_local(String::from(CTOR_HIDDEN_LOCAL), Binding::Constant)?;
                     let ctor_slot = self.resolve_local(CTOR_HIDDEN_LOCAL).unwrap();
 
                     // Extract each field value and process its pattern
                     for (i, field_pattern) in fields.iter().enumerate() {
                         // Get the ADT from the hidden local
-                        self.emit(OpCode::GetLocal(ctor_slot));
+                        self.emit_u16(OpCode::GetLocal, ctor_slot)?;
                         // Get the field value (leaves ADT copy on stack, pushes field)
-                        self.emit(OpCode::GetFieldPop(i));
+                        self.emit_u16(OpCode::GetFieldPop, i)?;
 
                         // Recursively compile the field pattern
                         self.compile_pattern(field_pattern, false)?;
@@ -723,14 +966,14 @@ impl Compiler {
         }
     }
 
-    fn compile_lambda(&mut self, params: Vec<String>, body: LambdaBody) -> Result<(Rc<Chunk>, usize, Vec<UpvalueDescriptor>), String> {
+    fn compile_lambda(&mut self, params: Vec<Ident>, body: LambdaBody) -> Result<(Rc<Function>, Vec<UpvalueDescriptor>), ArkaanError> {
         self.compile_callable(String::from("<lambda>"), params, |compiler| {
             match body {
                 LambdaBody::Expr(expr) => {
                     // Single expression - implicit return
                     // Check for tail call optimization
-                    if let Expr::Call { callee, arguments } = *expr {
-                        compiler.compile_tail_call(callee, arguments)?;
+                    if let ExprKind::Call { callee, arguments } = expr.kind {
+                        compiler.compile_tail_call(*callee, arguments, expr.span)?;
                     } else {
                         compiler.compile_expr(*expr)?;
                         compiler.emit(OpCode::Return);
@@ -738,12 +981,9 @@ impl Compiler {
                 }
                 LambdaBody::Block(stmts) => {
                     // Block body - like a function
-                    for stmt in stmts {
-                        compiler.compile_stmt(stmt)?;
-                    }
+                    compiler.compile_block(stmts)?;
                     // Implicit nil return
-                    let nil_idx = compiler.add_constant(Value::Nil);
-                    compiler.emit(OpCode::Constant(nil_idx));
+                    compiler.emit_constant(Value::Nil)?;
                     compiler.emit(OpCode::Return);
                 }
             }
@@ -751,8 +991,140 @@ impl Compiler {
         })
     }
 
+    /// Compiles a named function declaration and leaves the function on the stack
+    fn compile_function(&mut self, name: String, params: Vec<Ident>, body: Vec<Stmt>) -> Result<(), ArkaanError> {
+        let (function, upvalues) = self.compile_callable(name, params, |compiler| {
+            compiler.compile_block(body)?;
+            // Implicit nil return
+            compiler.emit_constant(Value::Nil)?;
+            compiler.emit(OpCode::Return);
+            Ok(())
+        })?;
+        self.emit_closure(function, upvalues)
+    }
+
+    fn emit_closure(&mut self, function: Rc<Function>, upvalues: Vec<UpvalueDescriptor>) -> Result<(), ArkaanError> {
+        // Emit Closure opcode if there are upvalues, otherwise just Constant
+        if upvalues.is_empty() {
+            return self.emit_constant(Value::Function(function));
+        }
+
+        let const_idx = self.add_constant(Value::Function(function));
+        self.emit_u16(OpCode::Closure, const_idx)?;
+        self.write_u16(upvalues.len())?;
+        for upvalue in upvalues {
+            self.current.chunk.write_byte(u8::from(upvalue.is_local), self.span);
+            self.write_u16(upvalue.index)?;
+        }
+        Ok(())
+    }
+
+    /// Defines a global binding and records whether it may be reassigned
+    fn define_global(&mut self, name: String, mutable: bool) -> Result<(), ArkaanError> {
+        let slot = self.global_slot(&name);
+        self.declare_global(name, mutable);
+        self.emit_u16(OpCode::DefineGlobal, slot)?;
+        Ok(())
+    }
+
+    fn declare_global(&mut self, name: String, mutable: bool) {
+        let binding = if mutable { Binding::Variable } else { Binding::Constant };
+        self.global_bindings.insert(name, binding);
+    }
+
+    /// Records how the top-level statements bind their names before any of
+    /// them is compiled, so hoisted function bodies can't assign a global
+    /// declared with 'laat' further down
+    fn declare_globals(&mut self, statements: &[Stmt]) {
+        for stmt in statements {
+            match &stmt.kind {
+                StmtKind::VarDecl { name, mutable, .. } => self.declare_global(name.name.clone(), *mutable),
+                StmtKind::TypeDecl { constructors, .. } => {
+                    for constructor in constructors {
+                        self.declare_global(constructor.name.name.clone(), false);
+                    }
+                }
+                StmtKind::Import { alias: name, .. }
+                | StmtKind::ExportVarDecl { name, .. }
+                | StmtKind::FunctionDecl { name, .. }
+                | StmtKind::ExportFunctionDecl { name, .. } => self.declare_global(name.name.clone(), false),
+                _ => {}
+            }
+        }
+    }
+
+    /// The slot of a global the host gives a value. Code may reassign it
+    /// like one bound with 'stel', unless code declared the name first.
+    pub(crate) fn host_global_slot(&mut self, name: &str) -> usize {
+        self.global_bindings.entry(name.to_string()).or_insert(Binding::Variable);
+        self.global_slot(name)
+    }
+
+    /// The slot of a global, given out the first time the name is seen
+    pub(crate) fn global_slot(&mut self, name: &str) -> usize {
+        if let Some(&slot) = self.global_slots.get(name) {
+            return slot;
+        }
+        self.globals.push(name.to_string());
+        self.global_slots.insert(name.to_string(), self.globals.len() - 1);
+        self.globals.len() - 1
+    }
+
+    /// Index of a name in the current chunk's name table
+    fn name(&mut self, name: &str) -> usize {
+        self.current.chunk.add_name(Symbol::intern(name))
+    }
+
     fn emit(&mut self, op: OpCode) -> usize {
-        self.current.chunk.write(op)
+        self.current.chunk.write(op, self.span)
+    }
+
+    fn emit_u8(&mut self, op: OpCode, operand: usize) -> usize {
+        // The parser allows no more than 255 arguments
+        let offset = self.emit(op);
+        self.current.chunk.write_byte(operand as u8, self.span);
+        offset
+    }
+
+    fn emit_u16(&mut self, op: OpCode, operand: usize) -> Result<usize, ArkaanError> {
+        let offset = self.emit(op);
+        self.write_u16(operand)?;
+        Ok(offset)
+    }
+
+    fn write_u16(&mut self, operand: usize) -> Result<(), ArkaanError> {
+        let operand = u16::try_from(operand).map_err(|_| {
+            self.error(
+                ErrorKind::LimitExceeded,
+                format!("Te veel konstantes, veranderlikes of elemente; een funksie kan hoogstens {} hê.", u16::MAX),
+            )
+        })?;
+        self.current.chunk.write_u16(operand, self.span);
+        Ok(())
+    }
+
+    fn emit_constant(&mut self, value: Value) -> Result<(), ArkaanError> {
+        let idx = self.add_constant(value);
+        self.emit_u16(OpCode::Constant, idx)?;
+        Ok(())
+    }
+
+    /// Emits a jump whose target is patched in once it is known
+    fn emit_jump(&mut self, op: OpCode) -> usize {
+        self.emit_jump_to(op, 0)
+    }
+
+    fn emit_jump_to(&mut self, op: OpCode, target: usize) -> usize {
+        let offset = self.emit(op);
+        self.current.chunk.write_u32(target as u32, self.span);
+        offset
+    }
+
+    /// A compile error pointing at the node being compiled
+    fn error(&self, kind: ErrorKind, message: impl Into<String>) -> ArkaanError {
+        let mut error = ArkaanError::at(kind, message, self.span);
+        error.file = self.file.clone();
+        error
     }
 
     fn add_constant(&mut self, value: Value) -> usize {
@@ -768,7 +1140,6 @@ impl Compiler {
             Literal::Number(n) => Value::Number(*n),
             Literal::Boolean(b) => Value::Boolean(*b),
             Literal::String(s) => Value::String(Rc::new(s.clone())),
-            Literal::Nil => Value::Nil,
         }
     }
 
@@ -782,7 +1153,7 @@ impl Compiler {
             .collect()
     }
 
-    fn compile_arguments(&mut self, arguments: Vec<Expr>) -> Result<usize, String> {
+    fn compile_arguments(&mut self, arguments: Vec<Expr>) -> Result<usize, ArkaanError> {
         let arg_count = arguments.len();
         for arg in arguments {
             self.compile_expr(arg)?;
@@ -790,10 +1161,13 @@ impl Compiler {
         Ok(arg_count)
     }
 
-    fn compile_tail_call(&mut self, callee: Box<Expr>, arguments: Vec<Expr>) -> Result<(), String> {
-        self.compile_expr(*callee)?;
+    fn compile_tail_call(&mut self, callee: Expr, arguments: Vec<Expr>, span: Span) -> Result<(), ArkaanError> {
+        self.compile_expr(callee)?;
         let arg_count = self.compile_arguments(arguments)?;
-        self.emit(OpCode::TailCall(arg_count));
+        // The call instruction points at the whole call expression
+        let enclosing_span = std::mem::replace(&mut self.span, span);
+        self.emit_u8(OpCode::TailCall, arg_count);
+        self.span = enclosing_span;
         Ok(())
     }
 }
@@ -803,3 +1177,14 @@ impl Default for Compiler {
         Self::new()
     }
 }
+
+/// Moves top-level function declarations before the other statements, so a
+/// program can call a function above where it is declared. They only read
+/// globals when called, so nothing they use has to exist yet.
+fn hoist_functions(statements: Vec<Stmt>) -> Vec<Stmt> {
+    let (mut functions, rest): (Vec<Stmt>, Vec<Stmt>) = statements.into_iter().partition(|stmt| {
+        matches!(stmt.kind, StmtKind::FunctionDecl { .. } | StmtKind::ExportFunctionDecl { .. })
+    });
+    functions.extend(rest);
+    functions
+}
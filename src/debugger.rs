@@ -0,0 +1,134 @@
+//! Pausing a running program. While a debugger is attached, the VM asks it
+//! before every instruction whether to stop; it stops at the first
+//! instruction of a new line that has a breakpoint or that the last step
+//! command asked for, and hands the VM to a `DebugHandler` to look around.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::bytecode::Chunk;
+use crate::span::SourceFile;
+use crate::vm::VM;
+
+/// How to go on after a pause
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Continue,  // Until the next breakpoint
+    Into,      // Until the next line, also inside a call
+    Over,      // Until the next line of this function or its callers
+    Out,       // Until back in the caller
+}
+
+/// Why the program paused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseReason {
+    Step,
+    Breakpoint,
+}
+
+/// What the debugger's user sees and controls. While it runs, the program
+/// stands still before the instruction it paused at.
+pub trait DebugHandler {
+    fn paused(&mut self, vm: &mut VM, reason: PauseReason) -> Step;
+}
+
+/// An instruction about to run, as the debugger tells one line from the next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Location {
+    pub depth: usize,  // Frames on the call stack
+    pub chunk: *const Chunk,
+    pub line: usize,
+    pub ip: usize,
+}
+
+impl Location {
+    /// Whether this is a different line than `earlier`, or the same one again after a jump back
+    fn moved_from(&self, earlier: &Location) -> bool {
+        self.depth != earlier.depth || self.chunk != earlier.chunk || self.line != earlier.line || self.ip < earlier.ip
+    }
+}
+
+pub struct Debugger {
+    handler: Option<Box<dyn DebugHandler>>,  // Taken while it runs, so code it evaluates is not debugged
+    breakpoints: HashSet<(PathBuf, usize)>,
+    step: Step,
+    paused_at: Option<Location>,
+    last: Option<Location>,  // The instruction before this one
+}
+
+impl Debugger {
+    /// A debugger that pauses at the first line of the program
+    pub(crate) fn new(handler: Box<dyn DebugHandler>) -> Self {
+        Debugger {
+            handler: Some(handler),
+            breakpoints: HashSet::new(),
+            step: Step::Into,
+            paused_at: None,
+            last: None,
+        }
+    }
+
+    /// Pauses at `line` of `file`, the first time each run of the program reaches it
+    pub fn add_breakpoint(&mut self, file: &Path, line: usize) {
+        self.breakpoints.insert((canonical(file), line));
+    }
+
+    /// Whether there was a breakpoint to remove
+    pub fn remove_breakpoint(&mut self, file: &Path, line: usize) -> bool {
+        self.breakpoints.remove(&(canonical(file), line))
+    }
+
+    /// Every breakpoint, by file and line
+    pub fn breakpoints(&self) -> Vec<(PathBuf, usize)> {
+        let mut breakpoints: Vec<_> = self.breakpoints.iter().cloned().collect();
+        breakpoints.sort();
+        breakpoints
+    }
+
+    pub(crate) fn take_handler(&mut self) -> Option<Box<dyn DebugHandler>> {
+        self.handler.take()
+    }
+
+    /// Puts the handler back after a pause, to go on as it asked
+    pub(crate) fn resume(&mut self, handler: Box<dyn DebugHandler>, step: Step, here: Location) {
+        self.handler = Some(handler);
+        self.step = step;
+        self.paused_at = Some(here);
+    }
+
+    /// Whether to pause before the instruction at `here`, in `file`
+    pub(crate) fn should_pause(&mut self, here: Location, file: Option<&SourceFile>) -> Option<PauseReason> {
+        self.handler.as_ref()?;
+        let new_line = self.last.is_none_or(|last| here.moved_from(&last));
+        self.last = Some(here);
+        if !new_line || here.line == 0 {
+            return None;
+        }
+
+        if self.at_breakpoint(file, here.line) {
+            return Some(PauseReason::Breakpoint);
+        }
+        let stop = match (self.step, self.paused_at) {
+            (Step::Continue, _) => false,
+            (_, None) | (Step::Into, _) => true,
+            (Step::Over, Some(paused)) => {
+                here.depth < paused.depth || (here.depth == paused.depth && here.moved_from(&paused))
+            }
+            (Step::Out, Some(paused)) => here.depth < paused.depth,
+        };
+        stop.then_some(PauseReason::Step)
+    }
+
+    fn at_breakpoint(&self, file: Option<&SourceFile>, line: usize) -> bool {
+        // Most lines have none, so only then look up the file
+        if !self.breakpoints.iter().any(|(_, at)| *at == line) {
+            return false;
+        }
+        file.is_some_and(|file| self.breakpoints.contains(&(canonical(Path::new(&file.name)), line)))
+    }
+}
+
+/// The same file however it was named, where it exists
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
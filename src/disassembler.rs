@@ -0,0 +1,110 @@
+//! Human-readable listings of compiled bytecode, for `arkaan disasm` and the
+//! REPL's `disasm` mode.
+
+use std::fmt::Write;
+
+use crate::bytecode::{Chunk, OpCode};
+use crate::value::Value;
+
+/// Lists a chunk's instructions and constants, followed by the listing of
+/// every function in its constant pool. `globals` names the global slots.
+pub fn disassemble(name: &str, chunk: &Chunk, globals: &[String]) -> String {
+    let mut out = String::new();
+    write_chunk(&mut out, name, chunk, globals);
+    out
+}
+
+fn write_chunk(out: &mut String, name: &str, chunk: &Chunk, globals: &[String]) {
+    let _ = writeln!(out, "== {} ==", name);
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        let _ = writeln!(out, "{}", disassemble_instruction(chunk, offset, globals));
+        match chunk.instruction_len(offset) {
+            Some(len) => offset += len,
+            None => break,
+        }
+    }
+
+    if !chunk.constants.is_empty() {
+        let _ = writeln!(out, "-- konstantes --");
+        for (index, constant) in chunk.constants.iter().enumerate() {
+            let _ = writeln!(out, "{:4} {}", index, describe(constant));
+        }
+    }
+
+    // Function bodies are constants of the chunk they are declared in
+    for constant in &chunk.constants {
+        if let Value::Function(function) = constant {
+            let _ = writeln!(out);
+            let params = if function.arity == 1 { "parameter" } else { "parameters" };
+            let name = format!("{} (lyn {}, {} {})", function.name, function.span.line, function.arity, params);
+            write_chunk(out, &name, &function.chunk, globals);
+        }
+    }
+}
+
+/// One instruction: its offset, source line (`|` when the same as the
+/// instruction before), opcode and decoded operands
+pub fn disassemble_instruction(chunk: &Chunk, offset: usize, globals: &[String]) -> String {
+    let line = chunk.line_at(offset);
+    let line = if offset > 0 && line == chunk.line_at(offset - 1) {
+        "   |".to_string()
+    } else if line == 0 {
+        "   ?".to_string()
+    } else {
+        format!("{:4}", line)
+    };
+
+    let Some(len) = chunk.instruction_len(offset) else {
+        return format!("{:04} {} <ongeldig>", offset, line);
+    };
+    let op = OpCode::from_byte(chunk.code[offset]).expect("instruction_len checks the opcode");
+    let operand = |n: usize| chunk.read_u16(offset + 1 + 2 * n) as usize;
+    let constant = |index: usize| {
+        chunk
+            .constants
+            .get(index)
+            .map_or_else(|| "<ongeldig>".to_string(), describe)
+    };
+    let name = |index: usize| chunk.names.get(index).map_or_else(|| "<ongeldig>".to_string(), |name| name.to_string());
+    let global = |slot: usize| {
+        let name = globals.get(slot).map_or("<ongeldig>", String::as_str);
+        format!("{:4} '{}'", slot, name)
+    };
+
+    let operands = match op {
+        OpCode::Constant => format!("{:4} {}", operand(0), constant(operand(0))),
+        OpCode::GetGlobal | OpCode::SetGlobal | OpCode::DefineGlobal => global(operand(0)),
+        OpCode::GetLocal
+        | OpCode::SetLocal
+        | OpCode::GetUpvalue
+        | OpCode::SetUpvalue
+        | OpCode::MakeList
+        | OpCode::GetFieldPop => format!("{:4}", operand(0)),
+        OpCode::Closure => {
+            let mut operands = format!("{:4} {}", operand(0), constant(operand(0)));
+            for capture in (offset + 5..offset + len).step_by(3) {
+                let kind = if chunk.code[capture] != 0 { "lokaal" } else { "opwaarde" };
+                // Continuation lines line up under the operands
+                let _ = write!(operands, "\n{:>9}{:18}{} {}", "|", "", kind, chunk.read_u16(capture + 1));
+            }
+            operands
+        }
+        OpCode::Jump | OpCode::JumpIfFalse => format!("-> {:04}", chunk.read_u32(offset + 1)),
+        OpCode::Call | OpCode::TailCall => format!("{:4}", chunk.code[offset + 1]),
+        OpCode::CheckConstructor => format!("'{}' {}", name(operand(0)), operand(1)),
+        OpCode::LoadModule => format!("\"{}\" as {}", name(operand(0)), name(operand(1))),
+        OpCode::GetMember => format!("'{}'", name(operand(0))),
+        _ => String::new(),
+    };
+
+    format!("{:04} {} {:<16} {}", offset, line, format!("{:?}", op), operands).trim_end().to_string()
+}
+
+/// A constant as it would be written in source, so strings stand out
+fn describe(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("{:?}", s),
+        other => other.to_string(),
+    }
+}
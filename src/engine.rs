@@ -0,0 +1,424 @@
+//! Embedding Arkaan in a Rust program. An `Engine` runs source in one session,
+//! so later code sees what earlier code defined, and lets the host register
+//! Rust closures as natives, read and set globals and call Arkaan functions.
+//! Arguments and results convert through `FromValue` and `IntoValue`.
+
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::arkc::{self, CompiledFile};
+use crate::bytecode::Chunk;
+use crate::compiler::Compiler;
+use crate::error::{ArkaanError, ErrorKind};
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::span::SourceFile;
+use crate::value::{NativeFunction, Value};
+use crate::vm::VM;
+
+pub struct Engine {
+    compiler: Compiler,  // Remembers the session's globals, types and immutable names
+    vm: VM,
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Engine {
+            compiler: Compiler::new(),
+            vm: VM::new(Chunk::new(), Vec::new(), Vec::new()),
+        }
+    }
+
+    pub fn vm(&self) -> &VM {
+        &self.vm
+    }
+
+    /// The VM, for settings such as the call depth limit or module caching
+    pub fn vm_mut(&mut self) -> &mut VM {
+        &mut self.vm
+    }
+
+    /// Lexes, parses and compiles a whole program on its own. Syntax errors
+    /// are all reported at once; a compile error stops at the first.
+    pub fn compile(file: &Rc<SourceFile>) -> Result<CompiledFile, Vec<ArkaanError>> {
+        let tokens = Lexer::new(&file.text).scan_tokens().map_err(|e| vec![e.in_file(file)])?;
+
+        let (statements, errors) = Parser::new(tokens).parse_with_recovery();
+        if !errors.is_empty() {
+            return Err(errors.into_iter().map(|e| e.in_file(file)).collect());
+        }
+
+        let mut compiler = Compiler::new();
+        compiler.set_file(Rc::clone(file));
+        let (chunk, functions) = compiler.compile(statements).map_err(|e| vec![e])?;
+
+        // Kept so the compiled file can also be loaded as a module
+        let mut exports: Vec<String> = compiler.get_exports().iter().cloned().collect();
+        exports.sort();
+
+        Ok(CompiledFile {
+            chunk,
+            functions,
+            exports,
+            globals: compiler.global_names().to_vec(),
+            source_hash: arkc::source_hash(&file.text),
+        })
+    }
+
+    /// Compiles source against what this session has defined so far; the
+    /// value of a final bare expression becomes the result. `name` is the
+    /// file errors point into.
+    pub fn compile_entry(&mut self, name: &str, source: &str) -> Result<CompiledFile, ArkaanError> {
+        let file = Rc::new(SourceFile::new(name, source));
+        let tokens = Lexer::new(&file.text).scan_tokens().map_err(|e| e.in_file(&file))?;
+        let statements = Parser::new(tokens).parse().map_err(|e| e.in_file(&file))?;
+
+        self.compiler.set_file(Rc::clone(&file));
+        let (chunk, functions) = self.compiler.compile_repl(statements)?;
+        Ok(CompiledFile {
+            chunk,
+            functions,
+            exports: Vec::new(),
+            globals: self.compiler.global_names().to_vec(),
+            source_hash: arkc::source_hash(&file.text),
+        })
+    }
+
+    /// Runs code from `compile_entry`, or a whole program from `compile` or an
+    /// `.arkc` file on an engine that has not run anything else
+    pub fn execute(&mut self, compiled: CompiledFile) -> Result<Value, ArkaanError> {
+        self.vm.interpret(compiled.chunk, compiled.functions, &compiled.globals)
+    }
+
+    pub fn eval(&mut self, source: &str) -> Result<Value, ArkaanError> {
+        let compiled = self.compile_entry("<eval>", source)?;
+        self.execute(compiled)
+    }
+
+    /// Runs a source file in this session; modules it loads are found
+    /// relative to it
+    pub fn eval_file(&mut self, path: impl AsRef<Path>) -> Result<Value, ArkaanError> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).map_err(|e| {
+            ArkaanError::new(ErrorKind::FileError, format!("Kon nie {} lees nie: {}", path.display(), e))
+        })?;
+        self.vm.set_current_file(path.canonicalize().unwrap_or_else(|_| path.to_path_buf()));
+        let compiled = self.compile_entry(&path.display().to_string(), &source)?;
+        self.execute(compiled)
+    }
+
+    /// Makes a Rust closure callable from Arkaan as `name`. Its arguments are
+    /// converted with `FromValue`, and a mismatch is reported as a type error.
+    pub fn register_fn<Args, F: HostFunction<Args>>(&mut self, name: &str, function: F) {
+        let arity = function.arity();
+        self.register_native(name, arity, move |_, args| function.call(args));
+    }
+
+    /// Makes a native that works on values directly callable as `name`. It
+    /// gets the VM, so it can call back into Arkaan with `VM::call_value`.
+    pub fn register_native(
+        &mut self,
+        name: &str,
+        arity: usize,
+        function: impl Fn(&mut VM, &[Value]) -> Result<Value, ArkaanError> + 'static,
+    ) {
+        self.vm.define_native(NativeFunction {
+            name: name.to_string(),
+            arity,
+            params: &[],
+            func: Rc::new(function),
+        });
+    }
+
+    /// A global of the session, if it has been given a value
+    pub fn global(&self, name: &str) -> Option<Value> {
+        self.vm.global(name)
+    }
+
+    /// Sets a global of the session, declaring it if code has not yet
+    pub fn set_global(&mut self, name: &str, value: impl IntoValue) {
+        self.compiler.host_global_slot(name);
+        self.vm.link_program_globals(self.compiler.global_names());
+        self.vm
+            .set_global(name, value.into_value())
+            .expect("the global was just declared");
+    }
+
+    /// Calls the Arkaan function in global `name`
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, ArkaanError> {
+        let function = self.global(name).ok_or_else(|| {
+            ArkaanError::new(ErrorKind::UndefinedVariable, format!("Ongedefinieerde veranderlike: '{}'", name))
+        })?;
+        self.vm.call_value(function, args)
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A Rust type that an Arkaan value can be converted to
+pub trait FromValue: Sized {
+    fn from_value(value: &Value) -> Result<Self, ArkaanError>;
+}
+
+/// A Rust type that can be converted to an Arkaan value
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+fn mismatch(expected: &str, value: &Value) -> ArkaanError {
+    ArkaanError::new(ErrorKind::TypeMismatch, format!("Verwag {}, maar het '{}' gekry.", expected, value))
+}
+
+impl FromValue for Value {
+    fn from_value(value: &Value) -> Result<Self, ArkaanError> {
+        Ok(value.clone())
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: &Value) -> Result<Self, ArkaanError> {
+        match value {
+            Value::Number(n) => Ok(*n),
+            other => Err(mismatch("'n nommer", other)),
+        }
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Value) -> Result<Self, ArkaanError> {
+        match value {
+            Value::Boolean(b) => Ok(*b),
+            other => Err(mismatch("waar of vals", other)),
+        }
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value) -> Result<Self, ArkaanError> {
+        match value {
+            Value::String(s) => Ok(s.as_ref().clone()),
+            other => Err(mismatch("'n string", other)),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: &Value) -> Result<Self, ArkaanError> {
+        match value {
+            Value::List(items) => items.iter().map(T::from_value).collect(),
+            other => Err(mismatch("'n lys", other)),
+        }
+    }
+}
+
+/// `nil` is `None`
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: &Value) -> Result<Self, ArkaanError> {
+        match value {
+            Value::Nil => Ok(None),
+            other => T::from_value(other).map(Some),
+        }
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Value {
+        Value::Nil
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Value {
+        Value::Number(self)
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Value {
+        Value::Boolean(self)
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Value {
+        Value::String(Rc::new(self))
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Value {
+        Value::String(Rc::new(self.to_string()))
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Value {
+        Value::List(Rc::new(self.into_iter().map(IntoValue::into_value).collect()))
+    }
+}
+
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Value {
+        self.map_or(Value::Nil, IntoValue::into_value)
+    }
+}
+
+/// What a host function may return: a value, or a `Result` whose error
+/// stops the Arkaan program
+pub trait HostResult {
+    fn into_result(self) -> Result<Value, ArkaanError>;
+}
+
+impl<T: IntoValue> HostResult for T {
+    fn into_result(self) -> Result<Value, ArkaanError> {
+        Ok(self.into_value())
+    }
+}
+
+impl<T: IntoValue> HostResult for Result<T, ArkaanError> {
+    fn into_result(self) -> Result<Value, ArkaanError> {
+        self.map(IntoValue::into_value)
+    }
+}
+
+/// A Rust closure `Engine::register_fn` accepts: up to five arguments that
+/// are `FromValue`, returning a `HostResult`. `Args` only tells the
+/// implementations for each number of arguments apart.
+pub trait HostFunction<Args>: 'static {
+    fn arity(&self) -> usize;
+    fn call(&self, args: &[Value]) -> Result<Value, ArkaanError>;
+}
+
+fn argument<T: FromValue>(args: &[Value], index: usize) -> Result<T, ArkaanError> {
+    T::from_value(&args[index]).map_err(|e| e.with_note(format!("By argument {}.", index + 1)))
+}
+
+macro_rules! host_function {
+    ($arity:expr; $($arg:ident $index:tt),*) => {
+        impl<F, R, $($arg),*> HostFunction<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + 'static,
+            R: HostResult,
+            $($arg: FromValue,)*
+        {
+            fn arity(&self) -> usize {
+                $arity
+            }
+
+            #[allow(unused_variables)]
+            fn call(&self, args: &[Value]) -> Result<Value, ArkaanError> {
+                self($(argument::<$arg>(args, $index)?),*).into_result()
+            }
+        }
+    };
+}
+
+host_function!(0;);
+host_function!(1; A 0);
+host_function!(2; A 0, B 1);
+host_function!(3; A 0, B 1, C 2);
+host_function!(4; A 0, B 1, C 2, D 3);
+host_function!(5; A 0, B 1, C 2, D 3, E 4);
+
+#[cfg(test)]
+mod tests {
+    use super::{Engine, IntoValue};
+    use crate::error::{ArkaanError, ErrorKind};
+    use crate::value::Value;
+
+    #[test]
+    fn host_functions_convert_their_arguments() {
+        let mut engine = Engine::new();
+        engine.register_fn("verdubbel", |x: f64| x * 2.0);
+        engine.register_fn("beskryf", |naam: String, lys: Vec<f64>, vlag: Option<bool>| {
+            format!("{}:{}:{:?}", naam, lys.len(), vlag)
+        });
+
+        assert_eq!(engine.eval("verdubbel(4)").unwrap().to_string(), "8");
+        assert_eq!(engine.eval("beskryf(\"a\", [1, 2], waar)").unwrap().to_string(), "a:2:Some(true)");
+
+        let error = engine.eval("verdubbel(\"vier\")").unwrap_err();
+        assert_eq!(error.code(), "E0401");
+        assert_eq!(engine.eval("beskryf(\"a\", [1, \"b\"], waar)").unwrap_err().code(), "E0401");
+        assert_eq!(engine.eval("verdubbel(1, 2)").unwrap_err().code(), "E0403");
+    }
+
+    #[test]
+    fn host_functions_may_fail() {
+        let mut engine = Engine::new();
+        engine.register_fn("deel", |a: f64, b: f64| -> Result<f64, ArkaanError> {
+            if b == 0.0 {
+                return Err(ArkaanError::new(ErrorKind::DivisionByZero, "Deling deur nul."));
+            }
+            Ok(a / b)
+        });
+
+        assert_eq!(engine.eval("deel(6, 3)").unwrap().to_string(), "2");
+        let error = engine.eval("laat x = deel(1, 0)").unwrap_err();
+        assert_eq!(error.code(), "E0405");
+        assert_eq!(error.message, "Deling deur nul.");
+    }
+
+    #[test]
+    fn globals_round_trip() {
+        let mut engine = Engine::new();
+        engine.set_global("limiet", 10.0);
+        assert_eq!(engine.global("limiet"), Some(Value::Number(10.0)));
+        assert_eq!(engine.eval("limiet * 2").unwrap().to_string(), "20");
+
+        engine.eval("limiet = limiet + 1\nstel naam = \"Arkaan\"").unwrap();
+        assert_eq!(engine.global("limiet"), Some(Value::Number(11.0)));
+        assert_eq!(engine.global("naam").unwrap().to_string(), "Arkaan");
+        assert_eq!(engine.global("onbekend"), None);
+
+        engine.set_global("naam", vec!["a", "b"]);
+        assert_eq!(engine.eval("naam").unwrap().to_string(), "[a, b]");
+    }
+
+    #[test]
+    fn calling_needs_a_defined_function() {
+        let mut engine = Engine::new();
+        engine.eval("funksie som(a, b) {\n    gee a + b\n}\nlaat getal = 1").unwrap();
+
+        let result = engine.call("som", vec![2.0.into_value(), 3.0.into_value()]).unwrap();
+        assert_eq!(result, Value::Number(5.0));
+        assert_eq!(engine.call("onbekend", Vec::new()).unwrap_err().code(), "E0402");
+        assert_eq!(engine.call("getal", Vec::new()).unwrap_err().code(), "E0407");
+        assert_eq!(engine.call("som", vec![Value::Nil]).unwrap_err().code(), "E0403");
+    }
+
+    #[test]
+    fn natives_call_back_into_arkaan() {
+        let mut engine = Engine::new();
+        engine.register_native("twee_keer", 2, |vm, args| {
+            let once = vm.call_value(args[0].clone(), vec![args[1].clone()])?;
+            vm.call_value(args[0].clone(), vec![once])
+        });
+
+        assert_eq!(engine.eval("twee_keer(fn(x) x * 3, 2)").unwrap().to_string(), "18");
+        let error = engine.eval("twee_keer(fn(x) x + \"a\" - 1, 2)").unwrap_err();
+        assert_eq!(error.code(), "E0401");
+        // The session is still usable after an error in a callback
+        assert_eq!(engine.eval("twee_keer(fn(x) x + 1, 0)").unwrap().to_string(), "2");
+    }
+
+    #[test]
+    fn a_failed_eval_leaves_closures_intact() {
+        let mut engine = Engine::new();
+        engine.eval("stel hou = 0").unwrap();
+        engine
+            .eval("{\n    stel a = 1\n    stel b = 2\n    laat c = \"bewaar\"\n    hou = fn() c\n    laat boom = a - \"b\"\n}")
+            .unwrap_err();
+        assert_eq!(engine.call("hou", Vec::new()).unwrap().to_string(), "bewaar");
+    }
+}
@@ -0,0 +1,296 @@
+use std::fmt;
+use std::rc::Rc;
+
+use crate::span::{SourceFile, Span};
+
+/// The stage of the interpreter an error came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Lex,
+    Parse,
+    Compile,
+    Runtime,
+}
+
+/// What went wrong. Every kind has a stable code that tools can match on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    // Lexing
+    UnexpectedCharacter,
+    UnterminatedString,
+    InvalidEscape,
+
+    // Parsing
+    UnexpectedToken,
+    InvalidAssignmentTarget,
+    TooManyArguments,
+    EmptyMatch,
+    EmptyType,
+    InvalidPipeTarget,
+
+    // Compiling
+    ImmutableAssignment,
+    DuplicateDefinition,
+    ReturnOutsideFunction,
+    LimitExceeded,    // More constants, locals or list elements than an instruction can address
+    UseBeforeDeclaration,  // A local function used by its block before it is declared
+
+    // Runtime
+    TypeMismatch,
+    UndefinedVariable,
+    ArityMismatch,
+    IndexOutOfBounds,
+    DivisionByZero,
+    EmptyList,
+    NotCallable,
+    ModuleError,
+    InvalidBytecode,  // An .arkc file that is damaged or from another version, or code compiled for another VM
+    StackOverflow,    // Calls nested deeper than the VM allows
+    FileError,        // A file the host asked to run could not be read
+    FuelExhausted,    // More instructions than the sandbox allows
+    DeadlineExceeded, // Ran longer than the sandbox allows
+    ValueTooLarge,    // A list or string longer than the sandbox allows
+    ModuleDenied,     // A module the sandbox does not allow loading
+    Internal,  // An interpreter invariant was broken; always a bug in Arkaan itself
+}
+
+impl ErrorKind {
+    pub fn stage(self) -> Stage {
+        match self {
+            ErrorKind::UnexpectedCharacter
+            | ErrorKind::UnterminatedString
+            | ErrorKind::InvalidEscape => Stage::Lex,
+            ErrorKind::UnexpectedToken
+            | ErrorKind::InvalidAssignmentTarget
+            | ErrorKind::TooManyArguments
+            | ErrorKind::EmptyMatch
+            | ErrorKind::EmptyType
+            | ErrorKind::InvalidPipeTarget => Stage::Parse,
+            ErrorKind::ImmutableAssignment
+            | ErrorKind::DuplicateDefinition
+            | ErrorKind::ReturnOutsideFunction
+            | ErrorKind::LimitExceeded
+            | ErrorKind::UseBeforeDeclaration => Stage::Compile,
+            ErrorKind::TypeMismatch
+            | ErrorKind::UndefinedVariable
+            | ErrorKind::ArityMismatch
+            | ErrorKind::IndexOutOfBounds
+            | ErrorKind::DivisionByZero
+            | ErrorKind::EmptyList
+            | ErrorKind::NotCallable
+            | ErrorKind::ModuleError
+            | ErrorKind::InvalidBytecode
+            | ErrorKind::StackOverflow
+            | ErrorKind::FileError
+            | ErrorKind::FuelExhausted
+            | ErrorKind::DeadlineExceeded
+            | ErrorKind::ValueTooLarge
+            | ErrorKind::ModuleDenied
+            | ErrorKind::Internal => Stage::Runtime,
+        }
+    }
+
+    /// Stable identifier for this kind; never renumber existing codes
+    pub fn code(self) -> &'static str {
+        match self {
+            ErrorKind::UnexpectedCharacter => "E0101",
+            ErrorKind::UnterminatedString => "E0102",
+            ErrorKind::InvalidEscape => "E0103",
+            ErrorKind::UnexpectedToken => "E0201",
+            ErrorKind::InvalidAssignmentTarget => "E0202",
+            ErrorKind::TooManyArguments => "E0203",
+            ErrorKind::EmptyMatch => "E0204",
+            ErrorKind::EmptyType => "E0205",
+            ErrorKind::InvalidPipeTarget => "E0206",
+            ErrorKind::ImmutableAssignment => "E0301",
+            ErrorKind::DuplicateDefinition => "E0302",
+            ErrorKind::ReturnOutsideFunction => "E0303",
+            ErrorKind::LimitExceeded => "E0304",
+            ErrorKind::UseBeforeDeclaration => "E0305",
+            ErrorKind::TypeMismatch => "E0401",
+            ErrorKind::UndefinedVariable => "E0402",
+            ErrorKind::ArityMismatch => "E0403",
+            ErrorKind::IndexOutOfBounds => "E0404",
+            ErrorKind::DivisionByZero => "E0405",
+            ErrorKind::EmptyList => "E0406",
+            ErrorKind::NotCallable => "E0407",
+            ErrorKind::ModuleError => "E0408",
+            ErrorKind::InvalidBytecode => "E0409",
+            ErrorKind::StackOverflow => "E0410",
+            ErrorKind::FileError => "E0411",
+            ErrorKind::FuelExhausted => "E0412",
+            ErrorKind::DeadlineExceeded => "E0413",
+            ErrorKind::ValueTooLarge => "E0414",
+            ErrorKind::ModuleDenied => "E0415",
+            ErrorKind::Internal => "E0999",
+        }
+    }
+}
+
+/// An error from any stage of the interpreter, with its location if known
+#[derive(Debug, Clone)]
+pub struct ArkaanError {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub span: Option<Span>,
+    pub file: Option<Rc<SourceFile>>,
+    details: Option<Box<Details>>,  // Most errors have none, so they stay small
+}
+
+/// What only some errors carry besides their message and location
+#[derive(Debug, Clone, Default)]
+struct Details {
+    notes: Vec<String>,
+    help: Option<String>,
+    trace: Vec<TraceFrame>,  // Runtime call stack, innermost call first
+}
+
+/// One call on the stack at the time a runtime error was raised
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceFrame {
+    pub function: String,
+    pub file: Option<String>,  // None for natives
+    pub line: usize,
+}
+
+impl ArkaanError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        ArkaanError {
+            kind,
+            message: message.into(),
+            span: None,
+            file: None,
+            details: None,
+        }
+    }
+
+    pub fn at(kind: ErrorKind, message: impl Into<String>, span: Span) -> Self {
+        ArkaanError {
+            span: Some(span),
+            ..ArkaanError::new(kind, message)
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        self.kind.code()
+    }
+
+    pub fn stage(&self) -> Stage {
+        self.kind.stage()
+    }
+
+    pub fn notes(&self) -> &[String] {
+        self.details.as_ref().map_or(&[], |details| &details.notes)
+    }
+
+    pub fn help(&self) -> Option<&str> {
+        self.details.as_ref()?.help.as_deref()
+    }
+
+    /// The calls a runtime error unwound through, innermost first
+    pub fn trace(&self) -> &[TraceFrame] {
+        self.details.as_ref().map_or(&[], |details| &details.trace)
+    }
+
+    fn details_mut(&mut self) -> &mut Details {
+        self.details.get_or_insert_with(Box::default)
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.details_mut().notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.details_mut().help = Some(help.into());
+        self
+    }
+
+    /// Attaches a location, unless a more precise one was already recorded
+    pub fn located(mut self, span: Span, file: Option<&Rc<SourceFile>>) -> Self {
+        if self.span.is_none() {
+            self.span = Some(span);
+            self.file = file.cloned();
+        }
+        self
+    }
+
+    /// Attaches the file the span points into, if none was recorded yet
+    pub fn in_file(mut self, file: &Rc<SourceFile>) -> Self {
+        if self.file.is_none() {
+            self.file = Some(Rc::clone(file));
+        }
+        self
+    }
+
+    /// Records a call the error unwound through
+    pub fn with_frame(mut self, frame: TraceFrame) -> Self {
+        self.details_mut().trace.push(frame);
+        self
+    }
+}
+
+impl fmt::Display for ArkaanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+
+        match (self.span, &self.file) {
+            (Some(span), Some(file)) => write!(f, "\n{}", render_snippet(file, span))?,
+            (Some(span), None) => write!(f, " (lyn {}, kolom {})", span.line, span.column)?,
+            (None, _) => {}
+        }
+
+        for note in self.notes() {
+            write!(f, "\n  = nota: {}", note)?;
+        }
+        if let Some(help) = self.help() {
+            write!(f, "\n  = hulp: {}", help)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for TraceFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "by {} ({}:{})", self.function, file, self.line),
+            None => write!(f, "by {} (ingeboude funksie)", self.function),
+        }
+    }
+}
+
+/// Renders the location line and the source line with the span underlined:
+///
+/// ```text
+///   --> voorbeeld.ark:3:6
+///    |
+///  3 | druk(1 - "a")
+///    |      ^^^^^^^
+/// ```
+fn render_snippet(file: &SourceFile, span: Span) -> String {
+    let line_text = file.text.lines().nth(span.line - 1).unwrap_or("");
+    let gutter = " ".repeat(span.line.to_string().len());
+
+    // Keep tabs in the padding so the carets line up with the source
+    let padding: String = line_text
+        .chars()
+        .take(span.column - 1)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+
+    // Underline up to the end of the span or the end of the line, whichever is first
+    let line_start = file.text[..span.start.min(file.text.len())]
+        .rfind('\n')
+        .map_or(0, |i| i + 1);
+    let line_end = line_start + line_text.len();
+    let underlined = file
+        .text
+        .get(span.start.min(line_end)..span.end.min(line_end))
+        .map_or(0, |s| s.chars().count());
+    let carets = "^".repeat(underlined.max(1));
+
+    format!(
+        "{gutter} --> {}:{}:{}\n{gutter} |\n{} | {}\n{gutter} | {}{}",
+        file.name, span.line, span.column, span.line, line_text, padding, carets
+    )
+}
@@ -0,0 +1,539 @@
+//! Rewrites Arkaan source in one canonical style.
+//!
+//! The parser drops comments, so the printer walks the syntax tree alongside
+//! the comments the lexer collected and writes each one back before the item
+//! it precedes, or after the item it shares a line with.
+
+use std::ops::Range;
+
+use crate::ast::{Expr, ExprKind, Ident, LambdaBody, Literal, MatchArm, Pattern, Stmt, StmtKind, TypeConstructor};
+use crate::error::ArkaanError;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::token::Comment;
+
+const INDENT: &str = "    ";
+
+/// Formats a whole file. Source with syntax errors is left alone and the
+/// errors are returned instead.
+pub fn format_source(source: &str) -> Result<String, Vec<ArkaanError>> {
+    Ok(format_items(source)?.text)
+}
+
+/// Formats the top-level statements that overlap `range`. Returns the part of
+/// the source to replace and its replacement, or `None` if nothing overlaps.
+pub fn format_range(source: &str, range: Range<usize>) -> Result<Option<(Range<usize>, String)>, Vec<ArkaanError>> {
+    let formatted = format_items(source)?;
+    // A selection of whole lines ends at the start of the next one, which it leaves out
+    let end = range.end.max(range.start + 1);
+    let touched: Vec<&Item> = formatted
+        .items
+        .iter()
+        .filter(|item| item.source.start < end && range.start <= item.source.end)
+        .collect();
+
+    Ok(match (touched.first(), touched.last()) {
+        (Some(first), Some(last)) => Some((
+            first.source.start..last.source.end,
+            formatted.text[first.output.start..last.output.end].to_string(),
+        )),
+        _ => None,
+    })
+}
+
+/// A top-level statement with its comments: where it was and where it went
+struct Item {
+    source: Range<usize>,
+    output: Range<usize>,
+}
+
+struct Formatted {
+    text: String,
+    items: Vec<Item>,
+}
+
+fn format_items(source: &str) -> Result<Formatted, Vec<ArkaanError>> {
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.scan_tokens().map_err(|e| vec![e])?;
+    let (statements, errors) = Parser::new(tokens).parse_with_recovery();
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut printer = Printer {
+        source,
+        comments: lexer.comments().to_vec(),
+        next_comment: 0,
+        out: String::new(),
+        indent: 0,
+    };
+    let items = printer.lines(&statements, source.len(), stmt_span, Printer::stmt);
+    if !printer.out.is_empty() {
+        printer.out.push('\n');
+    }
+
+    Ok(Formatted {
+        text: printer.out,
+        items,
+    })
+}
+
+fn stmt_span(stmt: &Stmt) -> (usize, usize) {
+    (stmt.span.start, stmt.span.end)
+}
+
+fn arm_span(arm: &MatchArm) -> (usize, usize) {
+    // Patterns carry no span; the arm is on one line, so its body stands in
+    (arm.body.span.start, arm.body.span.end)
+}
+
+fn constructor_span(constructor: &TypeConstructor) -> (usize, usize) {
+    (constructor.span.start, constructor.span.end)
+}
+
+struct Printer<'a> {
+    source: &'a str,
+    comments: Vec<Comment>,
+    next_comment: usize,  // First comment not yet written
+    out: String,
+    indent: usize,
+}
+
+impl<'a> Printer<'a> {
+    /// Writes items one per line at the current indent, each with its
+    /// comments, then any comments left before `end`. A blank line in the
+    /// source between two items is kept as one.
+    fn lines<T>(
+        &mut self,
+        items: &[T],
+        end: usize,
+        span: fn(&T) -> (usize, usize),
+        print: fn(&mut Self, &T),
+    ) -> Vec<Item> {
+        let mut written = Vec::new();
+        let mut previous: Option<usize> = None;  // Where the last line ended in the source
+
+        for item in items {
+            let (start, stop) = span(item);
+            let mut source_start = start;
+            let mut output_start = None;
+
+            while let Some(comment) = self.comment_before(start) {
+                self.new_line(previous, comment.span.start);
+                source_start = source_start.min(comment.span.start);
+                output_start.get_or_insert(self.out.len());
+                self.write_indent();
+                self.out.push_str(&comment.text);
+                previous = Some(comment.span.end);
+            }
+
+            self.new_line(previous, start);
+            let output_start = *output_start.get_or_insert(self.out.len());
+            self.write_indent();
+            print(self, item);
+
+            let mut source_end = stop;
+            if let Some(comment) = self.trailing_comment(stop) {
+                self.out.push_str("  ");
+                self.out.push_str(&comment.text);
+                source_end = comment.span.end;
+            }
+            previous = Some(source_end);
+
+            written.push(Item {
+                source: self.line_start(source_start)..self.line_end(source_end),
+                output: output_start..self.out.len(),
+            });
+        }
+
+        while let Some(comment) = self.comment_before(end) {
+            self.new_line(previous, comment.span.start);
+            self.write_indent();
+            self.out.push_str(&comment.text);
+            previous = Some(comment.span.end);
+        }
+
+        written
+    }
+
+    /// Ends the previous line, if there is one, keeping a blank line after it
+    /// when the source had one
+    fn new_line(&mut self, previous: Option<usize>, next: usize) {
+        let Some(previous) = previous else {
+            return;
+        };
+        self.out.push('\n');
+        let gap = self.source.get(previous..next).unwrap_or_default();
+        if gap.matches('\n').count() > 1 {
+            self.out.push('\n');
+        }
+    }
+
+    /// The next comment, taken if it starts before `offset`
+    fn comment_before(&mut self, offset: usize) -> Option<Comment> {
+        let comment = self.comments.get(self.next_comment)?;
+        if comment.span.start >= offset {
+            return None;
+        }
+        self.next_comment += 1;
+        Some(comment.clone())
+    }
+
+    /// The next comment, taken if only spaces separate it from `offset`. One
+    /// after more code on the line belongs to whatever holds that code.
+    fn trailing_comment(&mut self, offset: usize) -> Option<Comment> {
+        let comment = self.comments.get(self.next_comment)?;
+        let between = self.source.get(offset..comment.span.start)?;
+        if !between.chars().all(|c| c == ' ' || c == '\t') {
+            return None;
+        }
+        self.next_comment += 1;
+        Some(comment.clone())
+    }
+
+    fn has_comment_before(&self, offset: usize) -> bool {
+        matches!(self.comments.get(self.next_comment), Some(comment) if comment.span.start < offset)
+    }
+
+    fn line_start(&self, offset: usize) -> usize {
+        self.source[..offset].rfind('\n').map_or(0, |i| i + 1)
+    }
+
+    fn line_end(&self, offset: usize) -> usize {
+        self.source[offset..].find('\n').map_or(self.source.len(), |i| offset + i)
+    }
+
+    fn write_indent(&mut self) {
+        for _ in 0..self.indent {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    /// Writes `{`, the lines between, and `}` at the current indent
+    fn braced<T>(&mut self, items: &[T], end: usize, span: fn(&T) -> (usize, usize), print: fn(&mut Self, &T)) {
+        self.out.push('{');
+        if items.is_empty() && !self.has_comment_before(end) {
+            self.out.push('}');
+            return;
+        }
+
+        self.indent += 1;
+        self.out.push('\n');
+        self.lines(items, end, span, print);
+        self.indent -= 1;
+
+        self.out.push('\n');
+        self.write_indent();
+        self.out.push('}');
+    }
+
+    fn block(&mut self, statements: &[Stmt], end: usize) {
+        self.braced(statements, end, stmt_span, Printer::stmt);
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Expression(expr) => self.expr(expr),
+            StmtKind::Print(expr) => {
+                self.out.push_str("druk(");
+                self.expr(expr);
+                self.out.push(')');
+            }
+            StmtKind::VarDecl {
+                name,
+                initializer,
+                mutable,
+            } => {
+                self.out.push_str(if *mutable { "stel " } else { "laat " });
+                self.out.push_str(&name.name);
+                self.out.push_str(" = ");
+                self.expr(initializer);
+            }
+            StmtKind::ExportVarDecl { name, initializer } => {
+                self.out.push_str("verskaf laat ");
+                self.out.push_str(&name.name);
+                self.out.push_str(" = ");
+                self.expr(initializer);
+            }
+            StmtKind::Block(statements) => self.block(statements, stmt.span.end),
+            StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.out.push_str("as ");
+                self.expr(condition);
+                self.out.push(' ');
+                self.stmt(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.out.push_str(" anders ");
+                    self.stmt(else_branch);
+                }
+            }
+            StmtKind::While { condition, body } => {
+                self.out.push_str("terwyl (");
+                self.expr(condition);
+                self.out.push_str(") ");
+                self.stmt(body);
+            }
+            StmtKind::Return { value } => {
+                self.out.push_str("gee");
+                if let Some(value) = value {
+                    self.out.push(' ');
+                    self.expr(value);
+                }
+            }
+            StmtKind::TypeDecl { name, constructors } => {
+                self.out.push_str("tipe ");
+                self.out.push_str(&name.name);
+                self.out.push(' ');
+                self.braced(constructors, stmt.span.end, constructor_span, Printer::constructor);
+            }
+            StmtKind::Import { path, alias } => {
+                self.out.push_str("laai \"");
+                self.out.push_str(&escape(path));
+                self.out.push_str("\" as ");
+                self.out.push_str(&alias.name);
+            }
+            StmtKind::FunctionDecl { name, params, body } => {
+                self.function("funksie ", &name.name, params, body, stmt.span.end);
+            }
+            StmtKind::ExportFunctionDecl { name, params, body } => {
+                self.function("verskaf funksie ", &name.name, params, body, stmt.span.end);
+            }
+        }
+    }
+
+    fn function(&mut self, keyword: &str, name: &str, params: &[Ident], body: &[Stmt], end: usize) {
+        self.out.push_str(keyword);
+        self.out.push_str(name);
+        self.params(params);
+        self.out.push(' ');
+        self.block(body, end);
+    }
+
+    fn params(&mut self, params: &[Ident]) {
+        let names: Vec<&str> = params.iter().map(|param| param.name.as_str()).collect();
+        self.out.push('(');
+        self.out.push_str(&names.join(", "));
+        self.out.push(')');
+    }
+
+    fn constructor(&mut self, constructor: &TypeConstructor) {
+        self.out.push_str(&constructor.name.name);
+        if !constructor.fields.is_empty() {
+            self.out.push('(');
+            self.out.push_str(&constructor.fields.join(", "));
+            self.out.push(')');
+        }
+    }
+
+    fn arm(&mut self, arm: &MatchArm) {
+        self.out.push_str("geval ");
+        self.pattern(&arm.pattern);
+        self.out.push_str(" => ");
+        self.expr(&arm.body);
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            // Written as in the source, so `1.50` and escapes survive
+            ExprKind::Literal(_) => {
+                let source = self.source;
+                self.out.push_str(&source[expr.span.start..expr.span.end]);
+            }
+            ExprKind::Variable(name) => self.out.push_str(name),
+            ExprKind::Binary { left, operator, right } => {
+                self.expr(left);
+                self.out.push(' ');
+                self.out.push_str(&operator.lexeme);
+                self.out.push(' ');
+                self.expr(right);
+            }
+            ExprKind::Unary { operator, right } => {
+                self.out.push_str(&operator.lexeme);
+                self.expr(right);
+            }
+            ExprKind::Grouping(inner) => {
+                self.out.push('(');
+                self.expr(inner);
+                self.out.push(')');
+            }
+            ExprKind::Call { callee, arguments } => {
+                // `x -> f(a)` is parsed as `f(x, a)`; the piped value comes first in the source
+                match arguments.split_first() {
+                    Some((piped, rest)) if piped.span.start < callee.span.start => {
+                        self.expr(piped);
+                        self.out.push_str(" -> ");
+                        self.expr(callee);
+                        // `x -> f` has no parentheses of its own
+                        if expr.span.end > callee.span.end {
+                            self.arguments(rest);
+                        }
+                    }
+                    _ => {
+                        self.expr(callee);
+                        self.arguments(arguments);
+                    }
+                }
+            }
+            ExprKind::Lambda { params, body } => {
+                self.out.push_str("fn");
+                self.params(params);
+                self.out.push(' ');
+                match body {
+                    LambdaBody::Expr(body) => self.expr(body),
+                    LambdaBody::Block(statements) => self.block(statements, expr.span.end),
+                }
+            }
+            ExprKind::List(elements) => {
+                self.out.push('[');
+                self.comma_separated(elements);
+                self.out.push(']');
+            }
+            ExprKind::Index { object, index } => {
+                self.expr(object);
+                self.out.push('[');
+                self.expr(index);
+                self.out.push(']');
+            }
+            ExprKind::Match { value, arms } => {
+                self.out.push_str("pas(");
+                self.expr(value);
+                self.out.push_str(") ");
+                self.braced(arms, expr.span.end, arm_span, Printer::arm);
+            }
+            ExprKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.out.push_str("as ");
+                self.expr(condition);
+                self.out.push(' ');
+                self.expr(then_branch);
+                self.out.push_str(" anders ");
+                self.expr(else_branch);
+            }
+            ExprKind::MemberAccess { object, member } => {
+                self.expr(object);
+                self.out.push('.');
+                self.out.push_str(&member.name);
+            }
+            ExprKind::Assign { name, value } => {
+                self.out.push_str(&name.name);
+                self.out.push_str(" = ");
+                self.expr(value);
+            }
+        }
+    }
+
+    fn arguments(&mut self, arguments: &[Expr]) {
+        self.out.push('(');
+        self.comma_separated(arguments);
+        self.out.push(')');
+    }
+
+    fn comma_separated(&mut self, exprs: &[Expr]) {
+        for (i, expr) in exprs.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.expr(expr);
+        }
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Wildcard => self.out.push('_'),
+            Pattern::Variable(name) => self.out.push_str(&name.name),
+            Pattern::Literal(Literal::Number(n)) => self.out.push_str(&n.to_string()),
+            Pattern::Literal(Literal::Boolean(b)) => self.out.push_str(if *b { "waar" } else { "vals" }),
+            Pattern::Literal(Literal::String(s)) => {
+                self.out.push('"');
+                self.out.push_str(&escape(s));
+                self.out.push('"');
+            }
+            Pattern::Constructor { name, fields } => {
+                self.out.push_str(&name.name);
+                // A lowercase `niks()` would read as a binding without its parentheses
+                let binding_like = !name.name.starts_with(char::is_uppercase);
+                if !fields.is_empty() || binding_like {
+                    self.out.push('(');
+                    for (i, field) in fields.iter().enumerate() {
+                        if i > 0 {
+                            self.out.push_str(", ");
+                        }
+                        self.pattern(field);
+                    }
+                    self.out.push(')');
+                }
+            }
+        }
+    }
+}
+
+/// The string literal text that the lexer reads back as `text`
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_source;
+
+    fn format(source: &str) -> String {
+        format_source(source).unwrap_or_else(|errors| panic!("{:?}", errors))
+    }
+
+    fn comments(text: &str) -> Vec<&str> {
+        text.lines().filter_map(|line| line.find("//").map(|i| line[i..].trim_end())).collect()
+    }
+
+    #[test]
+    fn examples_format_the_same_twice_and_keep_their_comments() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/examples");
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|ext| ext != "ark") {
+                continue;
+            }
+            let source = std::fs::read_to_string(&path).unwrap();
+            let once = format(&source);
+            assert_eq!(format(&once), once, "{} is not stable", path.display());
+            assert_eq!(comments(&once), comments(&source), "{} lost or reordered comments", path.display());
+        }
+    }
+
+    #[test]
+    fn comment_after_a_closing_brace_stays_with_the_statement() {
+        let source = "laat a = 1\nas a == 1 { druk(1) } anders { druk(3) } // slot\n";
+        assert_eq!(
+            format(source),
+            "laat a = 1\nas a == 1 {\n    druk(1)\n} anders {\n    druk(3)\n}  // slot\n",
+        );
+
+        let source = "laat f = fn(x) { gee x } // identiteit\n";
+        assert_eq!(format(source), "laat f = fn(x) {\n    gee x\n}  // identiteit\n");
+    }
+
+    #[test]
+    fn trailing_comments_stay_on_their_line() {
+        let source = "tipe Vorm {\n    Sirkel(r) // rond\n    Punt // klein\n}\nfunksie f(x) {\n    gee x // terug\n}\n";
+        assert_eq!(
+            format(source),
+            "tipe Vorm {\n    Sirkel(r)  // rond\n    Punt  // klein\n}\nfunksie f(x) {\n    gee x  // terug\n}\n",
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! Reclaims reference cycles. Values are reference counted, which frees
+//! everything except cycles, such as a closure kept in a variable it captures.
+//! Lists, ADT instances and modules cannot change once they are made, so every
+//! cycle runs through an upvalue: the collector marks what the VM can still
+//! reach and empties the closed upvalues it did not, after which reference
+//! counting frees the rest.
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::{Rc, Weak};
+
+use crate::value::{Closure, Upvalue, UpvalueLocation, Value};
+
+/// Fewest closures and upvalues made between two collections
+const MIN_THRESHOLD: usize = 10_000;
+
+/// What the collector has done so far
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GcStats {
+    pub collections: usize,  // Times the collector ran
+    pub allocated: usize,    // Closures and upvalues made
+    pub freed: usize,        // Of those, freed by breaking a cycle
+    pub live: usize,         // Closures and upvalues alive after the last collection
+}
+
+/// Every closure and upvalue the VM made that may still be alive
+pub(crate) struct Heap {
+    closures: Vec<Weak<Closure>>,
+    upvalues: Vec<Weak<RefCell<Upvalue>>>,
+    since_collection: usize,
+    threshold: usize,
+    stress: bool,  // Collect at every opportunity, to find missing roots
+    stats: GcStats,
+}
+
+impl Heap {
+    pub fn new() -> Self {
+        Heap {
+            closures: Vec::new(),
+            upvalues: Vec::new(),
+            since_collection: 0,
+            threshold: MIN_THRESHOLD,
+            stress: false,
+            stats: GcStats::default(),
+        }
+    }
+
+    pub fn set_stress(&mut self, stress: bool) {
+        self.stress = stress;
+    }
+
+    pub fn stats(&self) -> GcStats {
+        self.stats
+    }
+
+    pub fn track_closure(&mut self, closure: &Rc<Closure>) {
+        self.closures.push(Rc::downgrade(closure));
+        self.allocated();
+    }
+
+    pub fn track_upvalue(&mut self, upvalue: &Rc<RefCell<Upvalue>>) {
+        self.upvalues.push(Rc::downgrade(upvalue));
+        self.allocated();
+    }
+
+    fn allocated(&mut self) {
+        self.since_collection += 1;
+        self.stats.allocated += 1;
+    }
+
+    /// Whether enough was made since the last collection to be worth another
+    pub fn should_collect(&self) -> bool {
+        self.stress || self.since_collection >= self.threshold
+    }
+
+    /// Empties every closed upvalue that the roots given to `marker` do not
+    /// reach, freeing the cycles it was part of
+    pub fn sweep(&mut self, mut marker: Marker) {
+        marker.trace();
+
+        // Whatever reference counting freed since the last collection
+        self.closures.retain(|closure| closure.strong_count() > 0);
+        self.upvalues.retain(|upvalue| upvalue.strong_count() > 0);
+        let before = self.closures.len() + self.upvalues.len();
+
+        for upvalue in self.upvalues.iter().filter_map(Weak::upgrade) {
+            if !marker.upvalues.contains(&Rc::as_ptr(&upvalue)) {
+                let value = std::mem::replace(&mut upvalue.borrow_mut().location, UpvalueLocation::Closed(Value::Nil));
+                drop(value);
+            }
+        }
+
+        self.closures.retain(|closure| closure.strong_count() > 0);
+        self.upvalues.retain(|upvalue| upvalue.strong_count() > 0);
+        let live = self.closures.len() + self.upvalues.len();
+
+        self.stats.collections += 1;
+        self.stats.freed += before - live;
+        self.stats.live = live;
+        self.since_collection = 0;
+        // Marking costs about as much as there is to mark, so wait at least that long
+        self.threshold = (marker.seen.len() + marker.upvalues.len()).max(MIN_THRESHOLD);
+    }
+}
+
+/// Finds everything reachable from the roots it is given
+#[derive(Default)]
+pub(crate) struct Marker {
+    seen: HashSet<*const ()>,
+    upvalues: HashSet<*const RefCell<Upvalue>>,
+    pending: Vec<Value>,  // Seen, but not yet looked inside
+}
+
+impl Marker {
+    pub fn value(&mut self, value: &Value) {
+        let address = match value {
+            Value::List(list) => Rc::as_ptr(list) as *const (),
+            Value::Closure(closure) => Rc::as_ptr(closure) as *const (),
+            Value::Adt(adt) => Rc::as_ptr(adt) as *const (),
+            Value::Module(module) => Rc::as_ptr(module) as *const (),
+            // Nothing else can lead to an upvalue
+            _ => return,
+        };
+        if self.seen.insert(address) {
+            self.pending.push(value.clone());
+        }
+    }
+
+    pub fn upvalue(&mut self, upvalue: &Rc<RefCell<Upvalue>>) {
+        if self.upvalues.insert(Rc::as_ptr(upvalue)) {
+            if let UpvalueLocation::Closed(value) = &upvalue.borrow().location {
+                self.value(value);
+            }
+        }
+    }
+
+    /// Follows everything seen so far; iterative, since lists and ADTs can nest deeply
+    fn trace(&mut self) {
+        while let Some(value) = self.pending.pop() {
+            match &value {
+                Value::List(list) => list.iter().for_each(|item| self.value(item)),
+                Value::Closure(closure) => closure.upvalues.iter().for_each(|upvalue| self.upvalue(upvalue)),
+                Value::Adt(adt) => adt.fields.iter().for_each(|field| self.value(field)),
+                Value::Module(module) => module.exports.values().for_each(|export| self.value(export)),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::Engine;
+
+    #[test]
+    fn a_dropped_recursive_closure_is_freed() {
+        let mut engine = Engine::new();
+        let result = engine.eval(
+            "{\n    stel aftel = 0\n    aftel = fn(n) {\n        as n == 0 {\n            gee 0\n        }\n        gee aftel(n - 1)\n    }\n    aftel(3)\n}",
+        );
+        assert!(result.is_ok());
+
+        // The closure and the upvalue it is kept in only hold each other now
+        engine.vm_mut().collect_garbage();
+        let stats = engine.vm().gc_stats();
+        assert!(stats.freed >= 2, "{:?}", stats);
+        assert_eq!(stats.live, 0);
+    }
+
+    #[test]
+    fn reachable_values_survive_collecting_before_every_closure() {
+        let mut engine = Engine::new();
+        engine.vm_mut().set_gc_stress(true);
+        let source = "\
+funksie maak_teller() {
+    stel n = 0
+    gee fn() {
+        n = n + 1
+        gee n
+    }
+}
+laat teller = maak_teller()
+
+funksie maak() {
+    laat eie = maak_teller()
+    stel n = 0
+    laat eerste = (fn() n)()
+    laat volgende = fn() {
+        n = n + 1
+        gee n
+    }
+    laat lys = [fn() n, fn() n * 10]
+    volgende()
+    laat nog = [fn() n + 1, fn() n + 2, fn() n + 3]
+    gee [eerste, volgende(), lys[0](), lys[1](), nog[2](), teller(), eie()]
+}
+
+maak()";
+        // While `maak` runs, `eie` is only on the stack, `teller` only in a
+        // global, and the upvalue for `n` only open until `volgende` reuses it
+        assert_eq!(engine.eval(source).unwrap().to_string(), "[0, 2, 2, 20, 5, 1, 1]");
+        assert!(engine.vm().gc_stats().collections >= 6);
+        assert_eq!(engine.eval("teller()").unwrap().to_string(), "2");
+    }
+}
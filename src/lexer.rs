@@ -1,11 +1,20 @@
-use crate::token::{Token, TokenType};
+use crate::error::{ArkaanError, ErrorKind};
+use crate::span::Span;
+use crate::token::{Comment, Token, TokenType};
 
 pub struct Lexer {
     source: Vec<char>,
     tokens: Vec<Token>,
+    comments: Vec<Comment>,
     start: usize,
     current: usize,
     line: usize,
+    column: usize,
+    // Position of the token being scanned
+    start_byte: usize,
+    current_byte: usize,
+    start_line: usize,
+    start_column: usize,
 }
 
 impl Lexer {
@@ -13,23 +22,38 @@ impl Lexer {
         Lexer {
             source: source.chars().collect(),
             tokens: Vec::new(),
+            comments: Vec::new(),
             start: 0,
             current: 0,
             line: 1,
+            column: 1,
+            start_byte: 0,
+            current_byte: 0,
+            start_line: 1,
+            start_column: 1,
         }
     }
 
-    pub fn scan_tokens(&mut self) -> Result<Vec<Token>, String> {
+    pub fn scan_tokens(&mut self) -> Result<Vec<Token>, ArkaanError> {
         while !self.is_at_end() {
             self.start = self.current;
+            self.start_byte = self.current_byte;
+            self.start_line = self.line;
+            self.start_column = self.column;
             self.scan_token()?;
         }
 
-        self.tokens.push(Token::new(TokenType::Eof, String::new(), self.line));
+        let eof = Span::new(self.current_byte, self.current_byte, self.line, self.column);
+        self.tokens.push(Token::new(TokenType::Eof, String::new(), eof));
         Ok(self.tokens.clone())
     }
 
-    fn scan_token(&mut self) -> Result<(), String> {
+    /// The comments skipped by `scan_tokens`, in source order
+    pub fn comments(&self) -> &[Comment] {
+        &self.comments
+    }
+
+    fn scan_token(&mut self) -> Result<(), ArkaanError> {
         let c = self.advance();
 
         match c {
@@ -57,6 +81,11 @@ impl Lexer {
                     while self.peek() != '\n' && !self.is_at_end() {
                         self.advance();
                     }
+                    let text: String = self.source[self.start..self.current].iter().collect();
+                    self.comments.push(Comment {
+                        text: text.trim_end().to_string(),
+                        span: self.span(),
+                    });
                 } else {
                     self.add_token(TokenType::Slash);
                 }
@@ -100,20 +129,17 @@ impl Lexer {
                 if self.match_char('&') {
                     self.add_token(TokenType::And);
                 } else {
-                    return Err(format!("Onverwagte karakter '&' op lyn {}", self.line));
+                    return Err(self.error(ErrorKind::UnexpectedCharacter, "Onverwagte karakter '&'"));
                 }
             }
             '|' => {
                 if self.match_char('|') {
                     self.add_token(TokenType::Or);
                 } else {
-                    return Err(format!("Onverwagte karakter '|' op lyn {}", self.line));
+                    return Err(self.error(ErrorKind::UnexpectedCharacter, "Onverwagte karakter '|'"));
                 }
             }
-            '\n' => {
-                self.add_token(TokenType::Newline);
-                self.line += 1;
-            }
+            '\n' => self.add_token(TokenType::Newline),
             ' ' | '\r' | '\t' => {}
             _ => {
                 if c.is_ascii_digit() {
@@ -121,7 +147,7 @@ impl Lexer {
                 } else if c.is_alphabetic() || c == '_' {
                     self.identifier();
                 } else {
-                    return Err(format!("Onverwagte karakter '{}' op lyn {}", c, self.line));
+                    return Err(self.error(ErrorKind::UnexpectedCharacter, format!("Onverwagte karakter '{}'", c)));
                 }
             }
         }
@@ -166,6 +192,7 @@ impl Lexer {
             "fn" => TokenType::Fn,
             "gee" => TokenType::Gee,
             "laat" => TokenType::Laat,
+            "stel" => TokenType::Stel,
             "pas" => TokenType::Pas,
             "geval" => TokenType::Geval,
             "tipe" => TokenType::Tipe,
@@ -181,13 +208,8 @@ impl Lexer {
         self.add_token(token_type);
     }
 
-    fn string(&mut self) -> Result<(), String> {
-        let start_line = self.line;
-
+    fn string(&mut self) -> Result<(), ArkaanError> {
         while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
-            }
             if self.peek() == '\\' && !self.is_at_end() {
                 self.advance(); // consume the backslash
                 if !self.is_at_end() {
@@ -199,7 +221,7 @@ impl Lexer {
         }
 
         if self.is_at_end() {
-            return Err(format!("Onbeëindigde string op lyn {}", start_line));
+            return Err(self.error(ErrorKind::UnterminatedString, "Onbeëindigde string"));
         }
 
         // Consume the closing "
@@ -211,7 +233,7 @@ impl Lexer {
             .collect();
 
         // Process escape sequences
-        let processed = self.process_escapes(&value)?;
+        let processed = self.process_escapes(&value).map_err(|e| self.error(ErrorKind::InvalidEscape, e))?;
         self.add_token(TokenType::Str(processed));
         Ok(())
     }
@@ -250,6 +272,13 @@ impl Lexer {
     fn advance(&mut self) -> char {
         let c = self.source[self.current];
         self.current += 1;
+        self.current_byte += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
         c
     }
 
@@ -273,13 +302,22 @@ impl Lexer {
         if self.is_at_end() || self.source[self.current] != expected {
             false
         } else {
-            self.current += 1;
+            self.advance();
             true
         }
     }
 
+    /// Span of the token scanned so far
+    fn span(&self) -> Span {
+        Span::new(self.start_byte, self.current_byte, self.start_line, self.start_column)
+    }
+
+    fn error(&self, kind: ErrorKind, message: impl Into<String>) -> ArkaanError {
+        ArkaanError::at(kind, message, self.span())
+    }
+
     fn add_token(&mut self, token_type: TokenType) {
         let lexeme: String = self.source[self.start..self.current].iter().collect();
-        self.tokens.push(Token::new(token_type, lexeme, self.line));
+        self.tokens.push(Token::new(token_type, lexeme, self.span()));
     }
 }
@@ -0,0 +1,26 @@
+//! The Arkaan interpreter: lexer, parser, bytecode compiler and VM.
+//! Shared by the `arkaan` command line tool and the `arkaan-lsp` server; other
+//! Rust programs embed Arkaan through `Engine`.
+
+pub mod arkc;
+pub mod ast;
+pub mod bytecode;
+pub mod compiler;
+pub mod debugger;
+pub mod disassembler;
+pub mod engine;
+pub mod error;
+pub mod formatter;
+pub mod gc;
+pub mod lexer;
+pub mod parser;
+pub mod sandbox;
+pub mod span;
+pub mod symbol;
+pub mod token;
+pub mod value;
+pub mod vm;
+
+pub use engine::{Engine, FromValue, HostFunction, HostResult, IntoValue};
+pub use error::{ArkaanError, ErrorKind};
+pub use value::Value;
@@ -1,755 +1,101 @@
-use tower_lsp::lsp_types::*;
-
-// We need to duplicate some core logic here since we can't easily share
-// between binaries. In a real project, you'd use a library crate.
-
-#[derive(Debug, Clone, PartialEq)]
-enum TokenType {
-    // Original keywords
-    As, Anders, Terwyl, Druk, Waar, Vals,
-    // Functional keywords
-    Funksie, Fn, Gee, Laat,
-    // Pattern matching
-    Pas, Geval, Tipe,
-    // Literals and identifiers
-    Number(f64), Str(String), Identifier(String),
-    // Operators
-    Plus, Minus, Star, Slash, Percent,
-    Equal, EqualEqual, Bang, BangEqual,
-    Less, LessEqual, Greater, GreaterEqual,
-    And, Or, Arrow,
-    // Delimiters
-    LeftParen, RightParen, LeftBrace, RightBrace,
-    LeftBracket, RightBracket, Comma, Underscore,
-    Newline, Eof,
-}
-
-#[derive(Debug, Clone)]
-struct Token {
-    token_type: TokenType,
-    lexeme: String,
-    line: u32,
-    start_col: u32,
-    end_col: u32,
-}
-
-struct Lexer {
-    source: Vec<char>,
-    tokens: Vec<Token>,
-    start: usize,
-    current: usize,
-    line: u32,
-    col: u32,
-    start_col: u32,
-}
-
-impl Lexer {
-    fn new(source: &str) -> Self {
-        Lexer {
-            source: source.chars().collect(),
-            tokens: Vec::new(),
-            start: 0,
-            current: 0,
-            line: 0,
-            col: 0,
-            start_col: 0,
-        }
-    }
-
-    fn scan_tokens(&mut self) -> (Vec<Token>, Vec<Diagnostic>) {
-        let mut diagnostics = Vec::new();
-
-        while !self.is_at_end() {
-            self.start = self.current;
-            self.start_col = self.col;
-            if let Err(e) = self.scan_token() {
-                diagnostics.push(e);
-            }
-        }
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::sync::Arc;
 
-        self.tokens.push(Token {
-            token_type: TokenType::Eof,
-            lexeme: String::new(),
-            line: self.line,
-            start_col: self.col,
-            end_col: self.col,
-        });
-
-        (self.tokens.clone(), diagnostics)
-    }
-
-    fn scan_token(&mut self) -> std::result::Result<(), Diagnostic> {
-        let c = self.advance();
-
-        match c {
-            '(' => self.add_token(TokenType::LeftParen),
-            ')' => self.add_token(TokenType::RightParen),
-            '{' => self.add_token(TokenType::LeftBrace),
-            '}' => self.add_token(TokenType::RightBrace),
-            '[' => self.add_token(TokenType::LeftBracket),
-            ']' => self.add_token(TokenType::RightBracket),
-            ',' => self.add_token(TokenType::Comma),
-            '+' => self.add_token(TokenType::Plus),
-            '-' => self.add_token(TokenType::Minus),
-            '*' => self.add_token(TokenType::Star),
-            '%' => self.add_token(TokenType::Percent),
-            '/' => {
-                if self.match_char('/') {
-                    while self.peek() != '\n' && !self.is_at_end() {
-                        self.advance();
-                    }
-                } else {
-                    self.add_token(TokenType::Slash);
-                }
-            }
-            '=' => {
-                let token = if self.match_char('=') {
-                    TokenType::EqualEqual
-                } else if self.match_char('>') {
-                    TokenType::Arrow
-                } else {
-                    TokenType::Equal
-                };
-                self.add_token(token);
-            }
-            '!' => {
-                let token = if self.match_char('=') {
-                    TokenType::BangEqual
-                } else {
-                    TokenType::Bang
-                };
-                self.add_token(token);
-            }
-            '<' => {
-                let token = if self.match_char('=') {
-                    TokenType::LessEqual
-                } else {
-                    TokenType::Less
-                };
-                self.add_token(token);
-            }
-            '>' => {
-                let token = if self.match_char('=') {
-                    TokenType::GreaterEqual
-                } else {
-                    TokenType::Greater
-                };
-                self.add_token(token);
-            }
-            '&' => {
-                if self.match_char('&') {
-                    self.add_token(TokenType::And);
-                } else {
-                    return Err(self.make_diagnostic("Onverwagte karakter '&'. Bedoel jy '&&'?"));
-                }
-            }
-            '|' => {
-                if self.match_char('|') {
-                    self.add_token(TokenType::Or);
-                } else {
-                    return Err(self.make_diagnostic("Onverwagte karakter '|'. Bedoel jy '||'?"));
-                }
-            }
-            '"' => {
-                self.string()?;
-            }
-            '\n' => {
-                self.add_token(TokenType::Newline);
-                self.line += 1;
-                self.col = 0;
-            }
-            ' ' | '\r' | '\t' => {}
-            _ => {
-                if c.is_ascii_digit() {
-                    self.number();
-                } else if c.is_alphabetic() || c == '_' {
-                    self.identifier();
-                } else {
-                    return Err(self.make_diagnostic(&format!("Onverwagte karakter '{}'", c)));
-                }
-            }
-        }
-
-        Ok(())
-    }
-
-    fn number(&mut self) {
-        while self.peek().is_ascii_digit() {
-            self.advance();
-        }
-        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
-            self.advance();
-            while self.peek().is_ascii_digit() {
-                self.advance();
-            }
-        }
-        let lexeme: String = self.source[self.start..self.current].iter().collect();
-        let value: f64 = lexeme.parse().unwrap_or(0.0);
-        self.add_token(TokenType::Number(value));
-    }
-
-    fn identifier(&mut self) {
-        while self.peek().is_alphanumeric() || self.peek() == '_' {
-            self.advance();
-        }
-        let lexeme: String = self.source[self.start..self.current].iter().collect();
-        let token_type = match lexeme.as_str() {
-            // Original keywords
-            "as" => TokenType::As,
-            "anders" => TokenType::Anders,
-            "terwyl" => TokenType::Terwyl,
-            "druk" => TokenType::Druk,
-            "waar" => TokenType::Waar,
-            "vals" => TokenType::Vals,
-            // Functional keywords
-            "funksie" => TokenType::Funksie,
-            "fn" => TokenType::Fn,
-            "gee" => TokenType::Gee,
-            "laat" => TokenType::Laat,
-            // Pattern matching
-            "pas" => TokenType::Pas,
-            "geval" => TokenType::Geval,
-            "tipe" => TokenType::Tipe,
-            // Wildcard
-            "_" => TokenType::Underscore,
-            _ => TokenType::Identifier(lexeme.clone()),
-        };
-        self.add_token(token_type);
-    }
-
-    fn string(&mut self) -> std::result::Result<(), Diagnostic> {
-        let start_line = self.line;
-        let start_col = self.start_col;
-
-        while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
-                self.col = 0;
-            }
-            if self.peek() == '\\' && !self.is_at_end() {
-                self.advance(); // consume backslash
-                if !self.is_at_end() {
-                    self.advance(); // consume escaped char
-                }
-            } else {
-                self.advance();
-            }
-        }
-
-        if self.is_at_end() {
-            return Err(Diagnostic {
-                range: Range {
-                    start: Position { line: start_line, character: start_col },
-                    end: Position { line: self.line, character: self.col },
-                },
-                severity: Some(DiagnosticSeverity::ERROR),
-                source: Some("arkaan".to_string()),
-                message: "Onbeeindigde string - verwag '\"'".to_string(),
-                ..Default::default()
-            });
-        }
-
-        self.advance(); // consume closing "
-
-        let value: String = self.source[self.start + 1..self.current - 1].iter().collect();
-        self.add_token(TokenType::Str(value));
-        Ok(())
-    }
-
-    fn is_at_end(&self) -> bool {
-        self.current >= self.source.len()
-    }
+use tower_lsp::lsp_types::*;
 
-    fn advance(&mut self) -> char {
-        let c = self.source[self.current];
-        self.current += 1;
-        self.col += 1;
-        c
+use arkaan_lang::ast::{Stmt, StmtKind, TypeConstructor};
+use arkaan_lang::engine::Engine;
+use arkaan_lang::error::ArkaanError;
+use arkaan_lang::lexer::Lexer;
+use arkaan_lang::span::{SourceFile, Span};
+use arkaan_lang::token::{Token, TokenType};
+
+use crate::document::Analysis;
+use crate::resolver::{Resolution, SymbolId, SymbolKind, Target};
+use crate::visit::{walk, Node};
+
+/// Runs the interpreter's own lexer, parser and compiler over the document,
+/// so the diagnostics match what `arkaan` reports for the file
+pub fn diagnostics(analysis: &Analysis) -> Vec<Diagnostic> {
+    if !analysis.syntax_errors.is_empty() {
+        return analysis.syntax_errors.clone();
     }
 
-    fn peek(&self) -> char {
-        if self.is_at_end() { '\0' } else { self.source[self.current] }
+    let file = Rc::new(SourceFile::new("<dokument>", analysis.text.as_str()));
+    match Engine::compile(&file) {
+        Ok(_) => Vec::new(),
+        Err(errors) => errors.iter().map(|error| to_diagnostic(&analysis.text, error)).collect(),
     }
+}
 
-    fn peek_next(&self) -> char {
-        if self.current + 1 >= self.source.len() { '\0' } else { self.source[self.current + 1] }
-    }
+pub fn to_diagnostic(text: &str, error: &ArkaanError) -> Diagnostic {
+    let range = error.span.map_or_else(Range::default, |span| span_range(text, span));
 
-    fn match_char(&mut self, expected: char) -> bool {
-        if self.is_at_end() || self.source[self.current] != expected {
-            false
-        } else {
-            self.current += 1;
-            self.col += 1;
-            true
-        }
+    let mut message = error.message.clone();
+    for note in error.notes() {
+        message.push_str(&format!("\nnota: {}", note));
     }
-
-    fn add_token(&mut self, token_type: TokenType) {
-        let lexeme: String = self.source[self.start..self.current].iter().collect();
-        self.tokens.push(Token {
-            token_type,
-            lexeme,
-            line: self.line,
-            start_col: self.start_col,
-            end_col: self.col,
-        });
+    if let Some(help) = error.help() {
+        message.push_str(&format!("\nhulp: {}", help));
     }
 
-    fn make_diagnostic(&self, message: &str) -> Diagnostic {
-        Diagnostic {
-            range: Range {
-                start: Position { line: self.line, character: self.start_col },
-                end: Position { line: self.line, character: self.col },
-            },
-            severity: Some(DiagnosticSeverity::ERROR),
-            source: Some("arkaan".to_string()),
-            message: message.to_string(),
-            ..Default::default()
-        }
+    Diagnostic {
+        range,
+        severity: Some(DiagnosticSeverity::ERROR),
+        code: Some(NumberOrString::String(error.code().to_string())),
+        source: Some("arkaan".to_string()),
+        message,
+        ..Default::default()
     }
 }
 
-// Simple parser for diagnostics
-fn parse_for_diagnostics(tokens: &[Token]) -> Vec<Diagnostic> {
-    let mut diagnostics = Vec::new();
-    let mut i = 0;
-    let mut paren_stack: Vec<&Token> = Vec::new();
-    let mut brace_stack: Vec<&Token> = Vec::new();
-    let mut bracket_stack: Vec<&Token> = Vec::new();
-
-    // Track declared variables for undefined variable detection
-    let mut declared_vars: std::collections::HashSet<String> = std::collections::HashSet::new();
-    let mut declared_funcs: std::collections::HashSet<String> = std::collections::HashSet::new();
-
-    // Built-in functions that are always valid
-    let builtin_funcs: std::collections::HashSet<&str> = [
-        // Output
-        "druk",
-        // Higher-order functions
-        "kaart", "filter", "vou", "vir_elk",
-        // List functions
-        "lengte", "kop", "stert", "leeg", "voeg_by", "heg_aan", "ketting", "omgekeer",
-    ].iter().cloned().collect();
-
-    // First pass: collect all declared constants
-    let mut j = 0;
-    while j < tokens.len() {
-        // Track 'laat' declarations
-        if matches!(tokens[j].token_type, TokenType::Laat) {
-            if j + 1 < tokens.len() {
-                if let TokenType::Identifier(name) = &tokens[j + 1].token_type {
-                    declared_vars.insert(name.clone());
-                }
-            }
-        }
-        j += 1;
+pub fn span_range(text: &str, span: Span) -> Range {
+    Range {
+        start: position_at(text, span.start),
+        end: position_at(text, span.end),
     }
+}
 
-    // Second pass: collect function parameters
-    j = 0;
-    while j < tokens.len() {
-        // For 'fn(' lambda - collect parameters
-        if matches!(tokens[j].token_type, TokenType::Fn) {
-            if j + 1 < tokens.len() && matches!(tokens[j + 1].token_type, TokenType::LeftParen) {
-                let mut k = j + 2;
-                while k < tokens.len() && !matches!(tokens[k].token_type, TokenType::RightParen) {
-                    if let TokenType::Identifier(name) = &tokens[k].token_type {
-                        declared_vars.insert(name.clone());
-                    }
-                    k += 1;
-                }
-            }
-        }
-        // For 'geval' pattern matching - collect pattern bindings (identifiers between geval and =>)
-        if matches!(tokens[j].token_type, TokenType::Geval) {
-            let mut k = j + 1;
-            while k < tokens.len() && !matches!(tokens[k].token_type, TokenType::Arrow) {
-                if let TokenType::Identifier(name) = &tokens[k].token_type {
-                    // Only add lowercase identifiers (not constructors which start uppercase)
-                    if name.chars().next().map(|c| c.is_lowercase()).unwrap_or(false) {
-                        declared_vars.insert(name.clone());
-                    }
-                }
-                k += 1;
-            }
+/// Byte offset of an LSP position, clamped to the text
+pub fn offset_at(text: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(i) => line_start += i + 1,
+            None => return text.len(),
         }
-        j += 1;
     }
 
-    // Third pass: collect type constructors from 'tipe' definitions
-    j = 0;
-    while j < tokens.len() {
-        if matches!(tokens[j].token_type, TokenType::Tipe) {
-            // Skip type name and opening brace
-            if j + 2 < tokens.len() && matches!(tokens[j + 2].token_type, TokenType::LeftBrace) {
-                let mut k = j + 3;
-                // Collect constructors until closing brace
-                while k < tokens.len() && !matches!(tokens[k].token_type, TokenType::RightBrace) {
-                    if let TokenType::Identifier(name) = &tokens[k].token_type {
-                        // Constructor names start with uppercase
-                        if name.chars().next().map(|c| c.is_uppercase()).unwrap_or(false) {
-                            declared_funcs.insert(name.clone());
-                        }
-                    }
-                    k += 1;
-                }
-            }
+    let mut units = 0;
+    for (i, c) in text[line_start..].char_indices() {
+        if units >= position.character as usize || c == '\n' {
+            return line_start + i;
         }
-        j += 1;
+        units += c.len_utf16();
     }
-
-    // Track if we're inside a type definition block
-    let mut in_type_def = false;
-    let mut type_def_brace_depth = 0;
-
-    // Track if we're inside a pattern (between 'geval' and '=>')
-    let mut in_pattern = false;
-
-    while i < tokens.len() {
-        let token = &tokens[i];
-
-        // Track entering/exiting type definitions
-        if matches!(token.token_type, TokenType::Tipe) {
-            in_type_def = true;
-        }
-        if in_type_def && matches!(token.token_type, TokenType::LeftBrace) {
-            type_def_brace_depth += 1;
-        }
-        if type_def_brace_depth > 0 && matches!(token.token_type, TokenType::RightBrace) {
-            type_def_brace_depth -= 1;
-            if type_def_brace_depth == 0 {
-                in_type_def = false;
-            }
-        }
-
-        // Track entering/exiting patterns (between 'geval' and '=>')
-        if matches!(token.token_type, TokenType::Geval) {
-            in_pattern = true;
-        }
-        if matches!(token.token_type, TokenType::Arrow) {
-            in_pattern = false;
-        }
-
-        match &token.token_type {
-            TokenType::LeftParen => paren_stack.push(token),
-            TokenType::RightParen => {
-                if paren_stack.pop().is_none() {
-                    diagnostics.push(Diagnostic {
-                        range: Range {
-                            start: Position { line: token.line, character: token.start_col },
-                            end: Position { line: token.line, character: token.end_col },
-                        },
-                        severity: Some(DiagnosticSeverity::ERROR),
-                        source: Some("arkaan".to_string()),
-                        message: "Ongepaarde ')' - geen ooreenstemmende '(' gevind".to_string(),
-                        ..Default::default()
-                    });
-                }
-            }
-            TokenType::LeftBrace => brace_stack.push(token),
-            TokenType::RightBrace => {
-                if brace_stack.pop().is_none() {
-                    diagnostics.push(Diagnostic {
-                        range: Range {
-                            start: Position { line: token.line, character: token.start_col },
-                            end: Position { line: token.line, character: token.end_col },
-                        },
-                        severity: Some(DiagnosticSeverity::ERROR),
-                        source: Some("arkaan".to_string()),
-                        message: "Ongepaarde '}' - geen ooreenstemmende '{' gevind".to_string(),
-                        ..Default::default()
-                    });
-                }
-            }
-            TokenType::LeftBracket => bracket_stack.push(token),
-            TokenType::RightBracket => {
-                if bracket_stack.pop().is_none() {
-                    diagnostics.push(Diagnostic {
-                        range: Range {
-                            start: Position { line: token.line, character: token.start_col },
-                            end: Position { line: token.line, character: token.end_col },
-                        },
-                        severity: Some(DiagnosticSeverity::ERROR),
-                        source: Some("arkaan".to_string()),
-                        message: "Ongepaarde ']' - geen ooreenstemmende '[' gevind".to_string(),
-                        ..Default::default()
-                    });
-                }
-            }
-            TokenType::Laat => {
-                // Check for: laat <identifier> = <expr>
-                if i + 1 < tokens.len() {
-                    if !matches!(tokens[i + 1].token_type, TokenType::Identifier(_)) {
-                        diagnostics.push(Diagnostic {
-                            range: Range {
-                                start: Position { line: token.line, character: token.start_col },
-                                end: Position { line: token.line, character: token.end_col },
-                            },
-                            severity: Some(DiagnosticSeverity::ERROR),
-                            source: Some("arkaan".to_string()),
-                            message: "Verwag konstante naam na 'laat'".to_string(),
-                            ..Default::default()
-                        });
-                    } else if i + 2 < tokens.len()
-                        && !matches!(tokens[i + 2].token_type, TokenType::Equal)
-                    {
-                        diagnostics.push(Diagnostic {
-                            range: Range {
-                                start: Position { line: tokens[i + 1].line, character: tokens[i + 1].end_col },
-                                end: Position { line: tokens[i + 1].line, character: tokens[i + 1].end_col + 1 },
-                            },
-                            severity: Some(DiagnosticSeverity::ERROR),
-                            source: Some("arkaan".to_string()),
-                            message: "Verwag '=' na konstante naam".to_string(),
-                            ..Default::default()
-                        });
-                    }
-                }
-            }
-            TokenType::Funksie => {
-                // 'funksie' keyword is deprecated - use fn() expressions instead
-                diagnostics.push(Diagnostic {
-                    range: Range {
-                        start: Position { line: token.line, character: token.start_col },
-                        end: Position { line: token.line, character: token.end_col },
-                    },
-                    severity: Some(DiagnosticSeverity::ERROR),
-                    source: Some("arkaan".to_string()),
-                    message: "'funksie' is nie meer ondersteun nie. Gebruik 'laat naam = fn(params) ...' in plaas daarvan.".to_string(),
-                    ..Default::default()
-                });
-            }
-            TokenType::Fn => {
-                // Check for: fn(<params>) <expr> or fn(<params>) { ... }
-                if i + 1 < tokens.len()
-                    && !matches!(tokens[i + 1].token_type, TokenType::LeftParen)
-                {
-                    diagnostics.push(Diagnostic {
-                        range: Range {
-                            start: Position { line: token.line, character: token.end_col },
-                            end: Position { line: token.line, character: token.end_col + 1 },
-                        },
-                        severity: Some(DiagnosticSeverity::ERROR),
-                        source: Some("arkaan".to_string()),
-                        message: "Verwag '(' na 'fn'".to_string(),
-                        ..Default::default()
-                    });
-                }
-            }
-            TokenType::Pas => {
-                // Check for: pas(<expr>) {
-                if i + 1 < tokens.len()
-                    && !matches!(tokens[i + 1].token_type, TokenType::LeftParen)
-                {
-                    diagnostics.push(Diagnostic {
-                        range: Range {
-                            start: Position { line: token.line, character: token.end_col },
-                            end: Position { line: token.line, character: token.end_col + 1 },
-                        },
-                        severity: Some(DiagnosticSeverity::ERROR),
-                        source: Some("arkaan".to_string()),
-                        message: "Verwag '(' na 'pas'".to_string(),
-                        ..Default::default()
-                    });
-                }
-            }
-            TokenType::Tipe => {
-                // Check for: tipe <Name> {
-                if i + 1 < tokens.len() {
-                    if !matches!(tokens[i + 1].token_type, TokenType::Identifier(_)) {
-                        diagnostics.push(Diagnostic {
-                            range: Range {
-                                start: Position { line: token.line, character: token.end_col },
-                                end: Position { line: token.line, character: token.end_col + 1 },
-                            },
-                            severity: Some(DiagnosticSeverity::ERROR),
-                            source: Some("arkaan".to_string()),
-                            message: "Verwag tipe naam na 'tipe'".to_string(),
-                            ..Default::default()
-                        });
-                    } else if i + 2 < tokens.len()
-                        && !matches!(tokens[i + 2].token_type, TokenType::LeftBrace)
-                    {
-                        diagnostics.push(Diagnostic {
-                            range: Range {
-                                start: Position { line: tokens[i + 1].line, character: tokens[i + 1].end_col },
-                                end: Position { line: tokens[i + 1].line, character: tokens[i + 1].end_col + 1 },
-                            },
-                            severity: Some(DiagnosticSeverity::ERROR),
-                            source: Some("arkaan".to_string()),
-                            message: "Verwag '{' na tipe naam".to_string(),
-                            ..Default::default()
-                        });
-                    }
-                }
-            }
-            TokenType::As => {
-                // Check that 'as' is NOT followed by '(' - parentheses are forbidden
-                if i + 1 < tokens.len() && matches!(tokens[i + 1].token_type, TokenType::LeftParen) {
-                    diagnostics.push(Diagnostic {
-                        range: Range {
-                            start: Position { line: tokens[i + 1].line, character: tokens[i + 1].start_col },
-                            end: Position { line: tokens[i + 1].line, character: tokens[i + 1].end_col },
-                        },
-                        severity: Some(DiagnosticSeverity::ERROR),
-                        source: Some("arkaan".to_string()),
-                        message: "Moenie hakies gebruik na 'as' nie. Skryf: as voorwaarde { ... }".to_string(),
-                        ..Default::default()
-                    });
-                }
-            }
-            TokenType::Terwyl => {
-                // Check for: terwyl (condition) {
-                if i + 1 < tokens.len() && !matches!(tokens[i + 1].token_type, TokenType::LeftParen) {
-                    diagnostics.push(Diagnostic {
-                        range: Range {
-                            start: Position { line: token.line, character: token.end_col },
-                            end: Position { line: token.line, character: token.end_col + 1 },
-                        },
-                        severity: Some(DiagnosticSeverity::ERROR),
-                        source: Some("arkaan".to_string()),
-                        message: "Verwag '(' na 'terwyl'".to_string(),
-                        ..Default::default()
-                    });
-                }
-            }
-            TokenType::Druk => {
-                // Check for: druk(expr)
-                if i + 1 < tokens.len() && !matches!(tokens[i + 1].token_type, TokenType::LeftParen) {
-                    diagnostics.push(Diagnostic {
-                        range: Range {
-                            start: Position { line: token.line, character: token.end_col },
-                            end: Position { line: token.line, character: token.end_col + 1 },
-                        },
-                        severity: Some(DiagnosticSeverity::ERROR),
-                        source: Some("arkaan".to_string()),
-                        message: "Verwag '(' na 'druk'".to_string(),
-                        ..Default::default()
-                    });
-                }
-            }
-            TokenType::Identifier(name) => {
-                // Skip identifier checks inside type definitions (field names are not variables)
-                if type_def_brace_depth > 0 {
-                    i += 1;
-                    continue;
-                }
-
-                // Check if this identifier is used as a constant (not being declared)
-                let is_declaration = i > 0 && matches!(
-                    tokens[i - 1].token_type,
-                    TokenType::Laat
-                );
-
-                // Check for tipe declaration context
-                let is_type_name = i > 0 && matches!(tokens[i - 1].token_type, TokenType::Tipe);
-
-                // Check if this is a function call (followed by '(')
-                let is_function_call = i + 1 < tokens.len()
-                    && matches!(tokens[i + 1].token_type, TokenType::LeftParen);
-
-                if is_function_call && !is_type_name {
-                    // Valid if: built-in, ADT constructor, or variable holding function
-                    let is_valid = builtin_funcs.contains(name.as_str())
-                        || declared_funcs.contains(name)
-                        || declared_vars.contains(name); // Variable could hold a function
-
-                    if !is_valid {
-                        diagnostics.push(Diagnostic {
-                            range: Range {
-                                start: Position { line: token.line, character: token.start_col },
-                                end: Position { line: token.line, character: token.end_col },
-                            },
-                            severity: Some(DiagnosticSeverity::WARNING), // Warning, not error
-                            source: Some("arkaan".to_string()),
-                            message: format!("Moontlike onbekende funksie: '{}'. Is dit gedefinieer?", name),
-                            ..Default::default()
-                        });
-                    }
-                } else if !is_declaration && !is_type_name && !in_pattern {
-                    if !declared_vars.contains(name) && !declared_funcs.contains(name) {
-                        diagnostics.push(Diagnostic {
-                            range: Range {
-                                start: Position { line: token.line, character: token.start_col },
-                                end: Position { line: token.line, character: token.end_col },
-                            },
-                            severity: Some(DiagnosticSeverity::ERROR),
-                            source: Some("arkaan".to_string()),
-                            message: format!("Ongedefinieerde veranderlike: '{}'", name),
-                            ..Default::default()
-                        });
-                    }
-                }
-            }
-            _ => {}
-        }
-
-        i += 1;
-    }
-
-    // Report unclosed brackets
-    for token in paren_stack {
-        diagnostics.push(Diagnostic {
-            range: Range {
-                start: Position { line: token.line, character: token.start_col },
-                end: Position { line: token.line, character: token.end_col },
-            },
-            severity: Some(DiagnosticSeverity::ERROR),
-            source: Some("arkaan".to_string()),
-            message: "Ongeslote '(' - verwag ')'".to_string(),
-            ..Default::default()
-        });
-    }
-
-    for token in brace_stack {
-        diagnostics.push(Diagnostic {
-            range: Range {
-                start: Position { line: token.line, character: token.start_col },
-                end: Position { line: token.line, character: token.end_col },
-            },
-            severity: Some(DiagnosticSeverity::ERROR),
-            source: Some("arkaan".to_string()),
-            message: "Ongeslote '{' - verwag '}'".to_string(),
-            ..Default::default()
-        });
-    }
-
-    for token in bracket_stack {
-        diagnostics.push(Diagnostic {
-            range: Range {
-                start: Position { line: token.line, character: token.start_col },
-                end: Position { line: token.line, character: token.end_col },
-            },
-            severity: Some(DiagnosticSeverity::ERROR),
-            source: Some("arkaan".to_string()),
-            message: "Ongeslote '[' - verwag ']'".to_string(),
-            ..Default::default()
-        });
-    }
-
-    diagnostics
+    text.len()
 }
 
-pub fn analyze_document(text: &str) -> Vec<Diagnostic> {
-    let mut lexer = Lexer::new(text);
-    let (tokens, mut diagnostics) = lexer.scan_tokens();
-
-    let parse_diagnostics = parse_for_diagnostics(&tokens);
-    diagnostics.extend(parse_diagnostics);
-
-    diagnostics
+/// LSP position of a byte offset: 0-based line and UTF-16 column
+pub fn position_at(text: &str, offset: usize) -> Position {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position {
+        line: before.matches('\n').count() as u32,
+        character: before[line_start..].encode_utf16().count() as u32,
+    }
 }
 
-pub fn get_hover_info(text: &str, position: Position) -> Option<Hover> {
-    let mut lexer = Lexer::new(text);
-    let (tokens, _) = lexer.scan_tokens();
+pub fn get_hover_info(analysis: &Analysis, position: Position) -> Option<Hover> {
+    let text = &analysis.text;
 
     // Find the token at the position
-    for token in tokens {
-        if token.line == position.line
-            && position.character >= token.start_col
-            && position.character < token.end_col
+    for token in &analysis.tokens {
+        let range = span_range(text, token.span);
+        if range.start.line == position.line
+            && position.character >= range.start.character
+            && position.character < range.end.character
         {
             let info = match &token.token_type {
                 TokenType::As => Some((
@@ -777,8 +123,8 @@ pub fn get_hover_info(text: &str, position: Position) -> Option<Hover> {
                     "Boolean false"
                 )),
                 TokenType::Funksie => Some((
-                    "**funksie** (verouderd)\n\n⚠️ Hierdie sleutelwoord is nie meer ondersteun nie.\n\nGebruik lambda uitdrukkings in plaas daarvan:\n\n```arkaan\nlaat groet = fn(naam) {\n    druk(\"Hallo \" + naam)\n}\n```",
-                    "Deprecated - use fn() expressions instead"
+                    "**funksie** (sleutelwoord)\n\nVerklaar 'n benoemde funksie. Benoemde funksies kan hulself en mekaar rekursief roep.\n\n```arkaan\nfunksie fakulteit(n) {\n    as n <= 1 { gee 1 }\n    gee n * fakulteit(n - 1)\n}\n```",
+                    "Named function declaration"
                 )),
                 TokenType::Fn => Some((
                     "**fn** (sleutelwoord)\n\nSkep 'n funksie uitdrukking.\n\n```arkaan\nlaat dubbel = fn(x) x * 2\n\nlaat groet = fn(naam) {\n    druk(\"Hallo \" + naam)\n}\n```",
@@ -792,6 +138,10 @@ pub fn get_hover_info(text: &str, position: Position) -> Option<Hover> {
                     "**laat** (sleutelwoord)\n\nVerklaar 'n konstante.\n\n```arkaan\nlaat x = 42\n```",
                     "Declare constant"
                 )),
+                TokenType::Stel => Some((
+                    "**stel** (sleutelwoord)\n\nVerklaar 'n veranderlike wat hertoegewys kan word.\n\n```arkaan\nstel teller = 0\nteller = teller + 1\n```",
+                    "Declare mutable variable"
+                )),
                 TokenType::Pas => Some((
                     "**pas** (sleutelwoord)\n\nPatroon-passing uitdrukking.\n\n```arkaan\npas(waarde) {\n    geval Sommige(x) => x\n    geval Niks => 0\n}\n```",
                     "Pattern matching expression"
@@ -866,10 +216,7 @@ pub fn get_hover_info(text: &str, position: Position) -> Option<Hover> {
                         kind: MarkupKind::Markdown,
                         value: format!("{}\n\n---\n*{}*", afrikaans, english),
                     }),
-                    range: Some(Range {
-                        start: Position { line: token.line, character: token.start_col },
-                        end: Position { line: token.line, character: token.end_col },
-                    }),
+                    range: Some(range),
                 });
             }
         }
@@ -878,8 +225,44 @@ pub fn get_hover_info(text: &str, position: Position) -> Option<Hover> {
     None
 }
 
-pub fn get_completions(text: &str, position: Position) -> Vec<CompletionItem> {
-    let mut completions = vec![
+/// Completions at the cursor: the exports of a module after `alias.`, the
+/// constructors of the matched type in a `pas` pattern, and otherwise the
+/// keywords, builtins and every name in scope
+pub fn get_completions(uri: &Url, analysis: &Analysis, position: Position, load: Loader) -> Vec<CompletionItem> {
+    let offset = offset_at(&analysis.text, position);
+
+    // The tokens before the cursor, leaving out a name that is being typed
+    let mut before: Vec<&Token> = analysis
+        .tokens
+        .iter()
+        .filter(|token| token.span.end <= offset && token.token_type != TokenType::Eof)
+        .collect();
+    if matches!(before.last(), Some(token) if matches!(token.token_type, TokenType::Identifier(_)) && token.span.end == offset) {
+        before.pop();
+    }
+
+    match before.as_slice() {
+        [.., object, dot] if dot.token_type == TokenType::Dot => {
+            if let TokenType::Identifier(alias) = &object.token_type {
+                return module_completions(uri, analysis, alias, load);
+            }
+            return Vec::new();
+        }
+        [.., geval] if geval.token_type == TokenType::Geval => {
+            if let Some(completions) = pattern_completions(analysis, before.len() - 1, offset) {
+                return completions;
+            }
+        }
+        _ => {}
+    }
+
+    let mut completions = keyword_completions();
+    completions.extend(scope_completions(analysis, offset));
+    completions
+}
+
+fn keyword_completions() -> Vec<CompletionItem> {
+    vec![
         // Constant declarations
         CompletionItem {
             label: "laat".to_string(),
@@ -889,7 +272,23 @@ pub fn get_completions(text: &str, position: Position) -> Vec<CompletionItem> {
             insert_text_format: Some(InsertTextFormat::SNIPPET),
             ..Default::default()
         },
+        CompletionItem {
+            label: "stel".to_string(),
+            kind: Some(CompletionItemKind::KEYWORD),
+            detail: Some("Verklaar veranderlike".to_string()),
+            insert_text: Some("stel ${1:naam} = ${0:waarde}".to_string()),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..Default::default()
+        },
         // Functions
+        CompletionItem {
+            label: "funksie".to_string(),
+            kind: Some(CompletionItemKind::KEYWORD),
+            detail: Some("Verklaar funksie".to_string()),
+            insert_text: Some("funksie ${1:naam}(${2:params}) {\n\t${0}\n}".to_string()),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..Default::default()
+        },
         CompletionItem {
             label: "fn".to_string(),
             kind: Some(CompletionItemKind::KEYWORD),
@@ -1076,65 +475,314 @@ pub fn get_completions(text: &str, position: Position) -> Vec<CompletionItem> {
             detail: Some("Boolean vals (false)".to_string()),
             ..Default::default()
         },
-    ];
+    ]
+}
+
+/// The names visible at an offset, innermost first, each listed once
+fn scope_completions(analysis: &Analysis, offset: usize) -> Vec<CompletionItem> {
+    let resolution = &analysis.resolution;
+    let fields = constructor_fields(analysis);
+    let mut seen = HashSet::new();
+
+    resolution
+        .visible_at(offset)
+        .into_iter()
+        .map(|id| &resolution.symbols[id])
+        .filter(|symbol| symbol.kind != SymbolKind::Type && seen.insert(symbol.name.clone()))
+        .map(|symbol| {
+            let (kind, detail) = match symbol.kind {
+                SymbolKind::Constant => (CompletionItemKind::CONSTANT, "Konstante"),
+                SymbolKind::Variable => (CompletionItemKind::VARIABLE, "Veranderlike"),
+                SymbolKind::Function => (CompletionItemKind::FUNCTION, "Funksie"),
+                SymbolKind::Parameter => (CompletionItemKind::VARIABLE, "Parameter"),
+                SymbolKind::PatternBinding => (CompletionItemKind::VARIABLE, "Patroonbinding"),
+                SymbolKind::Module => (CompletionItemKind::MODULE, "Module"),
+                SymbolKind::Constructor | SymbolKind::Type => {
+                    return constructor_completion(&symbol.name, fields.get(&symbol.name), "Tipe konstruktor".to_string());
+                }
+            };
+            CompletionItem {
+                label: symbol.name.clone(),
+                kind: Some(kind),
+                detail: Some(detail.to_string()),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
 
-    // Extract constant names from the document
-    let mut lexer = Lexer::new(text);
-    let (tokens, _) = lexer.scan_tokens();
+/// What a module loaded as `alias` makes available with 'verskaf'
+fn module_completions(uri: &Url, analysis: &Analysis, alias: &str, load: Loader) -> Vec<CompletionItem> {
+    let resolution = &analysis.resolution;
+    let Some(module) = resolution
+        .symbols
+        .iter()
+        .position(|symbol| symbol.kind == SymbolKind::Module && symbol.name == alias)
+    else {
+        return Vec::new();
+    };
+    let Some(module_analysis) = module_uri(uri, resolution, module).and_then(|uri| load(&uri)) else {
+        return Vec::new();
+    };
+    let path = resolution.symbols[module].module_path.as_deref().unwrap_or_default();
+
+    module_analysis
+        .resolution
+        .symbols
+        .iter()
+        .filter(|symbol| symbol.exported)
+        .map(|symbol| {
+            let (kind, detail) = match symbol.kind {
+                SymbolKind::Function => (CompletionItemKind::FUNCTION, "Funksie"),
+                _ => (CompletionItemKind::CONSTANT, "Konstante"),
+            };
+            CompletionItem {
+                label: symbol.name.clone(),
+                kind: Some(kind),
+                detail: Some(format!("{} uit \"{}\"", detail, path)),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
 
-    let mut seen_vars = std::collections::HashSet::new();
-    let mut i = 0;
-    while i < tokens.len() {
-        // Constant declarations (laat)
-        if matches!(tokens[i].token_type, TokenType::Laat) {
-            if i + 1 < tokens.len() {
-                if let TokenType::Identifier(name) = &tokens[i + 1].token_type {
-                    if !seen_vars.contains(name) {
-                        seen_vars.insert(name.clone());
-                        completions.push(CompletionItem {
-                            label: name.clone(),
-                            kind: Some(CompletionItemKind::CONSTANT),
-                            detail: Some("Konstante".to_string()),
-                            ..Default::default()
-                        });
-                    }
+/// Constructors for the pattern after `geval`. Once an arm names a
+/// constructor, only the rest of that constructor's type is offered.
+fn pattern_completions(analysis: &Analysis, geval: usize, offset: usize) -> Option<Vec<CompletionItem>> {
+    let tokens = &analysis.tokens;
+    let body = enclosing_match_body(tokens, geval)?;
+
+    // Constructors the other arms of this 'pas' already match
+    let mut used = HashSet::new();
+    let mut depth = 0;
+    for (token, next) in tokens[body + 1..].iter().zip(&tokens[body + 2..]) {
+        match &token.token_type {
+            TokenType::LeftBrace => depth += 1,
+            TokenType::RightBrace if depth == 0 => break,
+            TokenType::RightBrace => depth -= 1,
+            TokenType::Geval if depth == 0 => {
+                let typing = next.span.start < offset && offset <= next.span.end;
+                if let (TokenType::Identifier(name), false) = (&next.token_type, typing) {
+                    used.insert(name.clone());
                 }
             }
+            _ => {}
         }
-        i += 1;
     }
 
-    // Extract type constructors from document
-    let mut seen_constructors = std::collections::HashSet::new();
-    i = 0;
-    while i < tokens.len() {
-        if matches!(tokens[i].token_type, TokenType::Tipe) {
-            // Skip type name and opening brace
-            if i + 2 < tokens.len() && matches!(tokens[i + 2].token_type, TokenType::LeftBrace) {
-                let mut k = i + 3;
-                while k < tokens.len() && !matches!(tokens[k].token_type, TokenType::RightBrace) {
-                    if let TokenType::Identifier(name) = &tokens[k].token_type {
-                        // Constructor names typically start uppercase
-                        if name.chars().next().map(|c| c.is_uppercase()).unwrap_or(false)
-                            && !seen_constructors.contains(name)
-                        {
-                            seen_constructors.insert(name.clone());
-                            completions.push(CompletionItem {
-                                label: name.clone(),
-                                kind: Some(CompletionItemKind::CONSTRUCTOR),
-                                detail: Some("Tipe konstruktor".to_string()),
-                                insert_text: Some(format!("{}(${{0}})", name)),
-                                insert_text_format: Some(InsertTextFormat::SNIPPET),
-                                ..Default::default()
-                            });
-                        }
-                    }
-                    k += 1;
+    let types = type_declarations(analysis);
+    let matched: Vec<_> = types
+        .iter()
+        .filter(|(_, constructors)| constructors.iter().any(|constructor| used.contains(&constructor.name.name)))
+        .collect();
+    let candidates = if matched.is_empty() { types.iter().collect() } else { matched };
+
+    Some(
+        candidates
+            .into_iter()
+            .flat_map(|(type_name, constructors)| {
+                constructors
+                    .iter()
+                    .filter(|constructor| !used.contains(&constructor.name.name))
+                    .map(move |constructor| {
+                        let detail = format!("Konstruktor van {}", type_name);
+                        constructor_completion(&constructor.name.name, Some(&constructor.fields), detail)
+                    })
+            })
+            .collect(),
+    )
+}
+
+/// Index of the `{` that opens the `pas` body around a token, if any
+fn enclosing_match_body(tokens: &[Token], at: usize) -> Option<usize> {
+    let open = matching_open(tokens, at, &TokenType::LeftBrace, &TokenType::RightBrace)?;
+
+    // The body follows `pas(waarde)`
+    if open == 0 || tokens[open - 1].token_type != TokenType::RightParen {
+        return None;
+    }
+    let paren = matching_open(tokens, open - 1, &TokenType::LeftParen, &TokenType::RightParen)?;
+    (paren > 0 && tokens[paren - 1].token_type == TokenType::Pas).then_some(open)
+}
+
+/// Index of the unclosed `open` bracket nearest before a token
+fn matching_open(tokens: &[Token], at: usize, open: &TokenType, close: &TokenType) -> Option<usize> {
+    let mut depth = 0;
+    for i in (0..at).rev() {
+        if tokens[i].token_type == *close {
+            depth += 1;
+        } else if tokens[i].token_type == *open {
+            if depth == 0 {
+                return Some(i);
+            }
+            depth -= 1;
+        }
+    }
+    None
+}
+
+fn constructor_completion(name: &str, fields: Option<&Vec<String>>, detail: String) -> CompletionItem {
+    let insert_text = match fields {
+        Some(fields) if !fields.is_empty() => {
+            let placeholders: Vec<String> = fields
+                .iter()
+                .enumerate()
+                .map(|(i, field)| format!("${{{}:{}}}", i + 1, field))
+                .collect();
+            format!("{}({})", name, placeholders.join(", "))
+        }
+        _ => name.to_string(),
+    };
+    CompletionItem {
+        label: name.to_string(),
+        kind: Some(CompletionItemKind::CONSTRUCTOR),
+        detail: Some(detail),
+        insert_text: Some(insert_text),
+        insert_text_format: Some(InsertTextFormat::SNIPPET),
+        ..Default::default()
+    }
+}
+
+/// Every `tipe` in the document with its constructors
+fn type_declarations(analysis: &Analysis) -> Vec<(String, Vec<TypeConstructor>)> {
+    let mut types = Vec::new();
+    walk(&analysis.statements, &mut |node| {
+        if let Node::Stmt(Stmt {
+            kind: StmtKind::TypeDecl { name, constructors },
+            ..
+        }) = node
+        {
+            types.push((name.name.clone(), constructors.clone()));
+        }
+    });
+    types
+}
+
+fn constructor_fields(analysis: &Analysis) -> HashMap<String, Vec<String>> {
+    type_declarations(analysis)
+        .into_iter()
+        .flat_map(|(_, constructors)| constructors)
+        .map(|constructor| (constructor.name.name, constructor.fields))
+        .collect()
+}
+
+/// Loads the analysis of another document, e.g. a module brought in with 'laai'
+pub type Loader<'a> = &'a dyn Fn(&Url) -> Option<Arc<Analysis>>;
+
+/// A module's exported symbol, resolved in the module's own file
+struct ModuleSymbol {
+    uri: Url,
+    analysis: Arc<Analysis>,
+    id: SymbolId,
+}
+
+impl ModuleSymbol {
+    fn location(&self) -> Location {
+        let span = self.analysis.resolution.symbols[self.id].span;
+        Location::new(self.uri.clone(), span_range(&self.analysis.text, span))
+    }
+}
+
+/// The file that `laai "pad" as m` loads
+fn module_uri(uri: &Url, resolution: &Resolution, module: SymbolId) -> Option<Url> {
+    // Module paths are relative to the importing file, as in the VM
+    let path = resolution.symbols[module].module_path.as_ref()?;
+    let file = uri.to_file_path().ok()?;
+    Url::from_file_path(file.parent()?.join(path)).ok()
+}
+
+/// Follows `m.naam` into the module's file
+fn module_symbol(uri: &Url, resolution: &Resolution, module: SymbolId, member: &str, load: Loader) -> Option<ModuleSymbol> {
+    let module_uri = module_uri(uri, resolution, module)?;
+    let analysis = load(&module_uri)?;
+    let id = analysis.resolution.exported(member)?;
+    Some(ModuleSymbol {
+        uri: module_uri,
+        analysis,
+        id,
+    })
+}
+
+pub fn goto_definition(uri: &Url, analysis: &Analysis, position: Position, load: Loader) -> Option<Location> {
+    let (text, resolution) = (&analysis.text, &analysis.resolution);
+
+    match resolution.target_at(offset_at(text, position))? {
+        Target::Symbol(id) => Some(Location::new(uri.clone(), span_range(text, resolution.symbols[id].span))),
+        Target::ModuleMember { module, member } => {
+            Some(module_symbol(uri, resolution, module, &member, load)?.location())
+        }
+    }
+}
+
+pub fn find_references(uri: &Url, analysis: &Analysis, position: Position, include_declaration: bool, load: Loader) -> Vec<Location> {
+    let (text, resolution) = (&analysis.text, &analysis.resolution);
+    let Some(target) = resolution.target_at(offset_at(text, position)) else {
+        return Vec::new();
+    };
+
+    let mut locations = Vec::new();
+    if include_declaration {
+        match &target {
+            Target::Symbol(id) => {
+                locations.push(Location::new(uri.clone(), span_range(text, resolution.symbols[*id].span)));
+            }
+            Target::ModuleMember { module, member } => {
+                if let Some(found) = module_symbol(uri, resolution, *module, member, load) {
+                    locations.push(found.location());
                 }
             }
         }
-        i += 1;
     }
+    locations.extend(
+        resolution
+            .references_to(&target)
+            .map(|span| Location::new(uri.clone(), span_range(text, span))),
+    );
+    locations
+}
 
-    completions
+/// Renames the symbol under the cursor. Renaming a module member also
+/// renames it inside the module's file.
+pub fn rename(uri: &Url, analysis: &Analysis, position: Position, new_name: &str, load: Loader) -> Result<Option<WorkspaceEdit>, String> {
+    if !is_identifier(new_name) {
+        return Err(format!("'{}' is nie 'n geldige naam nie.", new_name));
+    }
+    let (text, resolution) = (&analysis.text, &analysis.resolution);
+    let Some(target) = resolution.target_at(offset_at(text, position)) else {
+        return Ok(None);
+    };
+
+    let edit = |text: &str, span: Span| TextEdit::new(span_range(text, span), new_name.to_string());
+    let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+
+    let mut edits: Vec<TextEdit> = resolution.references_to(&target).map(|span| edit(text, span)).collect();
+    match &target {
+        Target::Symbol(id) => edits.push(edit(text, resolution.symbols[*id].span)),
+        Target::ModuleMember { module, member } => {
+            if let Some(found) = module_symbol(uri, resolution, *module, member, load) {
+                let module = &found.analysis;
+                let declaration = Target::Symbol(found.id);
+                let mut module_edits: Vec<TextEdit> = module
+                    .resolution
+                    .references_to(&declaration)
+                    .map(|span| edit(&module.text, span))
+                    .collect();
+                module_edits.push(edit(&module.text, module.resolution.symbols[found.id].span));
+                changes.insert(found.uri, module_edits);
+            }
+        }
+    }
+    changes.entry(uri.clone()).or_default().extend(edits);
+
+    Ok(Some(WorkspaceEdit {
+        changes: Some(changes),
+        ..Default::default()
+    }))
+}
+
+fn is_identifier(name: &str) -> bool {
+    match Lexer::new(name).scan_tokens() {
+        Ok(tokens) => tokens.len() == 2 && tokens[0].token_type == TokenType::Identifier(name.to_string()),
+        Err(_) => false,
+    }
 }
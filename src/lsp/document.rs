@@ -0,0 +1,76 @@
+use std::sync::{Arc, OnceLock};
+
+use tower_lsp::lsp_types::{Diagnostic, TextDocumentContentChangeEvent};
+
+use arkaan_lang::ast::Stmt;
+use arkaan_lang::lexer::Lexer;
+use arkaan_lang::parser::Parser;
+use arkaan_lang::token::Token;
+
+use crate::analysis::{offset_at, to_diagnostic};
+use crate::resolver::{resolve, Resolution};
+
+/// An open document and, once asked for, the analysis of its current text
+pub struct Document {
+    pub text: String,
+    pub version: i32,
+    analysis: OnceLock<Arc<Analysis>>,
+}
+
+impl Document {
+    pub fn new(text: String, version: i32) -> Self {
+        Document {
+            text,
+            version,
+            analysis: OnceLock::new(),
+        }
+    }
+
+    /// Applies an edit from the client, either to a range or to the whole text
+    pub fn apply_change(&mut self, change: TextDocumentContentChangeEvent) {
+        match change.range {
+            Some(range) => {
+                let start = offset_at(&self.text, range.start);
+                let end = offset_at(&self.text, range.end).max(start);
+                self.text.replace_range(start..end, &change.text);
+            }
+            None => self.text = change.text,
+        }
+        self.analysis = OnceLock::new();
+    }
+
+    pub fn analysis(&self) -> Arc<Analysis> {
+        Arc::clone(self.analysis.get_or_init(|| Arc::new(Analysis::new(&self.text))))
+    }
+}
+
+/// Tokens, syntax tree and names of one version of a document
+pub struct Analysis {
+    pub text: String,
+    pub tokens: Vec<Token>,                  // Empty while the text does not lex
+    pub statements: Vec<Stmt>,               // Statements that parsed
+    pub syntax_errors: Vec<Diagnostic>,      // Lex or parse errors
+    pub resolution: Resolution,
+}
+
+impl Analysis {
+    pub fn new(text: &str) -> Self {
+        let (tokens, statements, syntax_errors) = match Lexer::new(text).scan_tokens() {
+            Ok(tokens) => {
+                let (statements, errors) = Parser::new(tokens.clone()).parse_with_recovery();
+                let errors = errors.iter().map(|error| to_diagnostic(text, error)).collect();
+                (tokens, statements, errors)
+            }
+            Err(error) => (Vec::new(), Vec::new(), vec![to_diagnostic(text, &error)]),
+        };
+        let resolution = resolve(&statements);
+
+        Analysis {
+            text: text.to_string(),
+            tokens,
+            statements,
+            syntax_errors,
+            resolution,
+        }
+    }
+}
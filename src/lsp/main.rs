@@ -1,48 +1,153 @@
 use std::collections::HashMap;
-use std::sync::RwLock;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer, LspService, Server};
 
+use arkaan_lang::formatter::{format_range, format_source};
+
 mod analysis;
+mod document;
+mod resolver;
+mod semantic;
+mod signature;
+mod symbols;
+mod visit;
+
+use analysis::{diagnostics, find_references, get_completions, get_hover_info, goto_definition, offset_at, position_at, rename};
+use document::{Analysis, Document};
+use semantic::semantic_tokens;
+use signature::{inlay_hints, signature_help};
+use symbols::{ark_files, document_symbols, folding_ranges, workspace_symbols};
 
-use analysis::{analyze_document, get_completions, get_hover_info};
+// How long typing has to pause before a changed document is checked again
+const DIAGNOSTICS_DELAY: Duration = Duration::from_millis(300);
 
 struct ArkaanLanguageServer {
     client: Client,
-    documents: RwLock<HashMap<Url, String>>,
+    documents: Arc<RwLock<HashMap<Url, Document>>>,
+    workspace_roots: RwLock<Vec<PathBuf>>,
 }
 
 impl ArkaanLanguageServer {
     fn new(client: Client) -> Self {
         ArkaanLanguageServer {
             client,
-            documents: RwLock::new(HashMap::new()),
+            documents: Arc::new(RwLock::new(HashMap::new())),
+            workspace_roots: RwLock::new(Vec::new()),
         }
     }
 
-    async fn update_diagnostics(&self, uri: Url, text: &str) {
-        let diagnostics = analyze_document(text);
-        self.client
-            .publish_diagnostics(uri, diagnostics, None)
-            .await;
+    /// Analysis of a document: cached for open documents, else read from disk
+    fn analysis(&self, uri: &Url) -> Option<Arc<Analysis>> {
+        if let Some(document) = self.documents.read().unwrap().get(uri) {
+            return Some(document.analysis());
+        }
+        let text = fs::read_to_string(uri.to_file_path().ok()?).ok()?;
+        Some(Arc::new(Analysis::new(&text)))
+    }
+
+    /// Every open document plus the Arkaan files in the workspace folders
+    fn workspace_documents(&self) -> Vec<(Url, Arc<Analysis>)> {
+        let mut documents: Vec<(Url, Arc<Analysis>)> = self
+            .documents
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(uri, document)| (uri.clone(), document.analysis()))
+            .collect();
+
+        for root in self.workspace_roots.read().unwrap().iter() {
+            for path in ark_files(root) {
+                let Ok(uri) = Url::from_file_path(&path) else {
+                    continue;
+                };
+                if documents.iter().any(|(open, _)| *open == uri) {
+                    continue;
+                }
+                if let Ok(text) = fs::read_to_string(&path) {
+                    documents.push((uri, Arc::new(Analysis::new(&text))));
+                }
+            }
+        }
+
+        documents
+    }
+
+    /// Publishes diagnostics once the document has stayed at this version for
+    /// a moment, so a burst of edits is only checked once
+    fn schedule_diagnostics(&self, uri: Url, version: i32) {
+        let client = self.client.clone();
+        let documents = Arc::clone(&self.documents);
+
+        tokio::spawn(async move {
+            tokio::time::sleep(DIAGNOSTICS_DELAY).await;
+            let analysis = match documents.read().unwrap().get(&uri) {
+                Some(document) if document.version == version => document.analysis(),
+                _ => return,  // Edited again or closed in the meantime
+            };
+            client
+                .publish_diagnostics(uri, diagnostics(&analysis), Some(version))
+                .await;
+        });
     }
 }
 
 #[tower_lsp::async_trait]
 impl LanguageServer for ArkaanLanguageServer {
-    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        let mut roots: Vec<Url> = params
+            .workspace_folders
+            .unwrap_or_default()
+            .into_iter()
+            .map(|folder| folder.uri)
+            .collect();
+        // Older clients only send `root_uri`
+        #[allow(deprecated)]
+        if roots.is_empty() {
+            roots.extend(params.root_uri);
+        }
+        *self.workspace_roots.write().unwrap() = roots
+            .iter()
+            .filter_map(|uri| uri.to_file_path().ok())
+            .collect();
+
         Ok(InitializeResult {
             capabilities: ServerCapabilities {
                 text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
+                    TextDocumentSyncKind::INCREMENTAL,
                 )),
                 completion_provider: Some(CompletionOptions {
                     trigger_characters: Some(vec![".".to_string()]),
                     ..Default::default()
                 }),
                 hover_provider: Some(HoverProviderCapability::Simple(true)),
+                definition_provider: Some(OneOf::Left(true)),
+                references_provider: Some(OneOf::Left(true)),
+                rename_provider: Some(OneOf::Left(true)),
+                document_symbol_provider: Some(OneOf::Left(true)),
+                workspace_symbol_provider: Some(OneOf::Left(true)),
+                folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+                signature_help_provider: Some(SignatureHelpOptions {
+                    trigger_characters: Some(vec!["(".to_string(), ",".to_string()]),
+                    retrigger_characters: None,
+                    work_done_progress_options: Default::default(),
+                }),
+                inlay_hint_provider: Some(OneOf::Left(true)),
+                document_formatting_provider: Some(OneOf::Left(true)),
+                document_range_formatting_provider: Some(OneOf::Left(true)),
+                semantic_tokens_provider: Some(SemanticTokensServerCapabilities::SemanticTokensOptions(
+                    SemanticTokensOptions {
+                        legend: semantic::legend(),
+                        full: Some(SemanticTokensFullOptions::Bool(true)),
+                        range: Some(true),
+                        ..Default::default()
+                    },
+                )),
                 ..Default::default()
             },
             server_info: Some(ServerInfo {
@@ -64,25 +169,32 @@ impl LanguageServer for ArkaanLanguageServer {
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
         let uri = params.text_document.uri;
-        let text = params.text_document.text;
+        let version = params.text_document.version;
+        let document = Document::new(params.text_document.text, version);
 
-        self.documents
-            .write()
-            .unwrap()
-            .insert(uri.clone(), text.clone());
-        self.update_diagnostics(uri, &text).await;
+        let analysis = document.analysis();
+        self.documents.write().unwrap().insert(uri.clone(), document);
+        self.client
+            .publish_diagnostics(uri, diagnostics(&analysis), Some(version))
+            .await;
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
         let uri = params.text_document.uri;
-        if let Some(change) = params.content_changes.into_iter().next() {
-            let text = change.text;
-            self.documents
-                .write()
-                .unwrap()
-                .insert(uri.clone(), text.clone());
-            self.update_diagnostics(uri, &text).await;
+        let version = params.text_document.version;
+
+        {
+            let mut documents = self.documents.write().unwrap();
+            let Some(document) = documents.get_mut(&uri) else {
+                return;
+            };
+            // Changes are applied in order, each to the result of the last
+            for change in params.content_changes {
+                document.apply_change(change);
+            }
+            document.version = version;
         }
+        self.schedule_diagnostics(uri, version);
     }
 
     async fn did_close(&self, params: DidCloseTextDocumentParams) {
@@ -96,24 +208,153 @@ impl LanguageServer for ArkaanLanguageServer {
         let uri = params.text_document_position_params.text_document.uri;
         let position = params.text_document_position_params.position;
 
-        let documents = self.documents.read().unwrap();
-        if let Some(text) = documents.get(&uri) {
-            Ok(get_hover_info(text, position))
-        } else {
-            Ok(None)
-        }
+        Ok(self
+            .analysis(&uri)
+            .and_then(|analysis| get_hover_info(&analysis, position)))
     }
 
     async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
         let uri = params.text_document_position.text_document.uri;
         let position = params.text_document_position.position;
 
-        let documents = self.documents.read().unwrap();
-        let text = documents.get(&uri).map(|s| s.as_str()).unwrap_or("");
-
-        let completions = get_completions(text, position);
+        let Some(analysis) = self.analysis(&uri) else {
+            return Ok(None);
+        };
+        let load = |uri: &Url| self.analysis(uri);
+        let completions = get_completions(&uri, &analysis, position, &load);
         Ok(Some(CompletionResponse::Array(completions)))
     }
+
+    async fn goto_definition(&self, params: GotoDefinitionParams) -> Result<Option<GotoDefinitionResponse>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+
+        let Some(analysis) = self.analysis(&uri) else {
+            return Ok(None);
+        };
+        let load = |uri: &Url| self.analysis(uri);
+        Ok(goto_definition(&uri, &analysis, position, &load).map(GotoDefinitionResponse::Scalar))
+    }
+
+    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        let uri = params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+
+        let Some(analysis) = self.analysis(&uri) else {
+            return Ok(None);
+        };
+        let load = |uri: &Url| self.analysis(uri);
+        let include_declaration = params.context.include_declaration;
+        Ok(Some(find_references(&uri, &analysis, position, include_declaration, &load)))
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let uri = params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+
+        let Some(analysis) = self.analysis(&uri) else {
+            return Ok(None);
+        };
+        let load = |uri: &Url| self.analysis(uri);
+        rename(&uri, &analysis, position, &params.new_name, &load).map_err(tower_lsp::jsonrpc::Error::invalid_params)
+    }
+
+    async fn document_symbol(&self, params: DocumentSymbolParams) -> Result<Option<DocumentSymbolResponse>> {
+        let Some(analysis) = self.analysis(&params.text_document.uri) else {
+            return Ok(None);
+        };
+        Ok(Some(DocumentSymbolResponse::Nested(document_symbols(&analysis))))
+    }
+
+    async fn symbol(&self, params: WorkspaceSymbolParams) -> Result<Option<Vec<SymbolInformation>>> {
+        let documents = self.workspace_documents();
+        Ok(Some(workspace_symbols(&params.query, &documents)))
+    }
+
+    async fn folding_range(&self, params: FoldingRangeParams) -> Result<Option<Vec<FoldingRange>>> {
+        let Some(analysis) = self.analysis(&params.text_document.uri) else {
+            return Ok(None);
+        };
+        Ok(Some(folding_ranges(&analysis)))
+    }
+
+    async fn semantic_tokens_full(&self, params: SemanticTokensParams) -> Result<Option<SemanticTokensResult>> {
+        let Some(analysis) = self.analysis(&params.text_document.uri) else {
+            return Ok(None);
+        };
+        Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
+            result_id: None,
+            data: semantic_tokens(&analysis, None),
+        })))
+    }
+
+    async fn semantic_tokens_range(&self, params: SemanticTokensRangeParams) -> Result<Option<SemanticTokensRangeResult>> {
+        let Some(analysis) = self.analysis(&params.text_document.uri) else {
+            return Ok(None);
+        };
+        let text = &analysis.text;
+        let within = (offset_at(text, params.range.start), offset_at(text, params.range.end));
+        Ok(Some(SemanticTokensRangeResult::Tokens(SemanticTokens {
+            result_id: None,
+            data: semantic_tokens(&analysis, Some(within)),
+        })))
+    }
+
+    async fn signature_help(&self, params: SignatureHelpParams) -> Result<Option<SignatureHelp>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+
+        let Some(analysis) = self.analysis(&uri) else {
+            return Ok(None);
+        };
+        Ok(signature_help(&analysis, position))
+    }
+
+    async fn inlay_hint(&self, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
+        let Some(analysis) = self.analysis(&params.text_document.uri) else {
+            return Ok(None);
+        };
+        Ok(Some(inlay_hints(&analysis, params.range)))
+    }
+
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        let Some(analysis) = self.analysis(&params.text_document.uri) else {
+            return Ok(None);
+        };
+        let text = &analysis.text;
+        // Source that does not parse is left as it is
+        let Ok(formatted) = format_source(text) else {
+            return Ok(None);
+        };
+        if formatted == *text {
+            return Ok(Some(Vec::new()));
+        }
+        Ok(Some(vec![TextEdit {
+            range: Range::new(Position::new(0, 0), position_at(text, text.len())),
+            new_text: formatted,
+        }]))
+    }
+
+    async fn range_formatting(&self, params: DocumentRangeFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        let Some(analysis) = self.analysis(&params.text_document.uri) else {
+            return Ok(None);
+        };
+        let text = &analysis.text;
+        let range = offset_at(text, params.range.start)..offset_at(text, params.range.end);
+        let Ok(formatted) = format_range(text, range) else {
+            return Ok(None);
+        };
+        Ok(Some(
+            formatted
+                .filter(|(replaced, new_text)| text[replaced.clone()] != *new_text)
+                .map(|(replaced, new_text)| TextEdit {
+                    range: Range::new(position_at(text, replaced.start), position_at(text, replaced.end)),
+                    new_text,
+                })
+                .into_iter()
+                .collect(),
+        ))
+    }
 }
 
 #[tokio::main]
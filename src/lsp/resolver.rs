@@ -0,0 +1,465 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use arkaan_lang::ast::{Expr, ExprKind, Ident, LambdaBody, MatchArm, Pattern, Stmt, StmtKind};
+use arkaan_lang::span::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Constant,
+    Variable,
+    Function,
+    Parameter,
+    Type,
+    Constructor,
+    PatternBinding,
+    Module,
+}
+
+/// A declared name
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub span: Span,                   // The name in its declaration
+    pub module_path: Option<String>,  // Path given to 'laai', for modules
+    pub exported: bool,
+    pub visible: Option<Range<usize>>,  // Where a local can be used; globals are visible everywhere
+}
+
+pub type SymbolId = usize;
+
+/// What a name in the source refers to
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+    Symbol(SymbolId),
+    // `m.naam` where `m` is an imported module
+    ModuleMember { module: SymbolId, member: String },
+}
+
+/// A use of a name (declarations are recorded on their symbol instead)
+#[derive(Debug, Clone)]
+pub struct Reference {
+    pub span: Span,
+    pub target: Target,
+}
+
+/// Every declaration in a document and what each name in it refers to
+#[derive(Debug, Default)]
+pub struct Resolution {
+    pub symbols: Vec<Symbol>,
+    pub references: Vec<Reference>,
+}
+
+impl Resolution {
+    /// The target of the declaration or reference under a byte offset
+    pub fn target_at(&self, offset: usize) -> Option<Target> {
+        let contains = |span: &Span| span.start <= offset && offset <= span.end;
+
+        if let Some(reference) = self.references.iter().find(|r| contains(&r.span)) {
+            return Some(reference.target.clone());
+        }
+        self.symbols
+            .iter()
+            .position(|symbol| contains(&symbol.span))
+            .map(Target::Symbol)
+    }
+
+    /// Spans of every reference to a target
+    pub fn references_to<'a>(&'a self, target: &'a Target) -> impl Iterator<Item = Span> + 'a {
+        self.references
+            .iter()
+            .filter(move |r| &r.target == target)
+            .map(|r| r.span)
+    }
+
+    /// Symbols that can be named at a byte offset, innermost first
+    pub fn visible_at(&self, offset: usize) -> Vec<SymbolId> {
+        let mut locals: Vec<SymbolId> = (0..self.symbols.len())
+            .filter(|&id| {
+                let visible = self.symbols[id].visible.as_ref();
+                visible.is_some_and(|range| range.contains(&offset))
+            })
+            .collect();
+        // Scopes nest, so the latest declaration is the innermost
+        locals.sort_by_key(|&id| std::cmp::Reverse(self.symbols[id].span.start));
+
+        let globals = (0..self.symbols.len()).filter(|&id| self.symbols[id].visible.is_none());
+        locals.into_iter().chain(globals).collect()
+    }
+
+    /// A top-level symbol the document exports with 'verskaf'
+    pub fn exported(&self, name: &str) -> Option<SymbolId> {
+        self.symbols
+            .iter()
+            .position(|symbol| symbol.exported && symbol.name == name)
+    }
+}
+
+/// Resolves names the way the compiler does: block-scoped locals that are
+/// visible after their declaration, hoisted local functions, and late-bound
+/// globals. Constructors and module aliases are always global.
+pub fn resolve(statements: &[Stmt]) -> Resolution {
+    let mut resolver = Resolver {
+        resolution: Resolution::default(),
+        globals: HashMap::new(),
+        scopes: Vec::new(),
+        scope_ends: Vec::new(),
+        initializing: None,
+    };
+
+    // Globals are looked up when the code runs, so functions may use globals
+    // declared further down
+    for stmt in statements {
+        resolver.declare_global_stmt(stmt);
+    }
+    for stmt in statements {
+        resolver.stmt(stmt);
+    }
+
+    resolver.resolution
+}
+
+struct Resolver {
+    resolution: Resolution,
+    globals: HashMap<String, Vec<SymbolId>>,
+    scopes: Vec<HashMap<String, SymbolId>>,  // Local scopes, innermost last
+    scope_ends: Vec<usize>,                  // Where each scope's source ends
+    initializing: Option<SymbolId>,          // Global whose initializer is being resolved
+}
+
+impl Resolver {
+    fn add_symbol(&mut self, ident: &Ident, kind: SymbolKind, exported: bool) -> SymbolId {
+        self.resolution.symbols.push(Symbol {
+            name: ident.name.clone(),
+            kind,
+            span: ident.span,
+            module_path: None,
+            exported,
+            visible: None,
+        });
+        self.resolution.symbols.len() - 1
+    }
+
+    fn declare_global(&mut self, ident: &Ident, kind: SymbolKind, exported: bool) -> SymbolId {
+        let id = self.add_symbol(ident, kind, exported);
+        self.globals.entry(ident.name.clone()).or_default().push(id);
+        id
+    }
+
+    fn declare_local(&mut self, ident: &Ident, kind: SymbolKind) -> SymbolId {
+        self.declare_local_from(ident, kind, ident.span.start)
+    }
+
+    /// Declares a local that can only be used from `start` on
+    fn declare_local_from(&mut self, ident: &Ident, kind: SymbolKind, start: usize) -> SymbolId {
+        let id = self.add_symbol(ident, kind, false);
+        if let (Some(scope), Some(&end)) = (self.scopes.last_mut(), self.scope_ends.last()) {
+            scope.insert(ident.name.clone(), id);
+            self.resolution.symbols[id].visible = Some(start..end);
+        }
+        id
+    }
+
+    fn enter_scope(&mut self, span: Span) {
+        self.scopes.push(HashMap::new());
+        self.scope_ends.push(span.end);
+    }
+
+    fn exit_scope(&mut self) {
+        self.scopes.pop();
+        self.scope_ends.pop();
+    }
+
+    /// Declares the names a top-level statement binds
+    fn declare_global_stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::VarDecl { name, mutable, .. } => {
+                let kind = if *mutable { SymbolKind::Variable } else { SymbolKind::Constant };
+                self.declare_global(name, kind, false);
+            }
+            StmtKind::ExportVarDecl { name, .. } => {
+                self.declare_global(name, SymbolKind::Constant, true);
+            }
+            StmtKind::FunctionDecl { name, .. } => {
+                self.declare_global(name, SymbolKind::Function, false);
+            }
+            StmtKind::ExportFunctionDecl { name, .. } => {
+                self.declare_global(name, SymbolKind::Function, true);
+            }
+            StmtKind::TypeDecl { .. } | StmtKind::Import { .. } => self.declare_always_global(stmt),
+            _ => {}
+        }
+    }
+
+    /// Type constructors and module aliases become globals wherever they appear
+    fn declare_always_global(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::TypeDecl { name, constructors } => {
+                self.declare_global(name, SymbolKind::Type, false);
+                for constructor in constructors {
+                    self.declare_global(&constructor.name, SymbolKind::Constructor, false);
+                }
+            }
+            StmtKind::Import { path, alias } => {
+                let id = self.declare_global(alias, SymbolKind::Module, false);
+                self.resolution.symbols[id].module_path = Some(path.clone());
+            }
+            _ => {}
+        }
+    }
+
+    fn lookup(&self, name: &str, at: Span) -> Option<SymbolId> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(&id) = scope.get(name) {
+                return Some(id);
+            }
+        }
+
+        // With several global declarations, use the latest one above the use
+        let candidates: Vec<SymbolId> = self
+            .globals
+            .get(name)?
+            .iter()
+            .copied()
+            .filter(|&id| Some(id) != self.initializing)
+            .collect();
+        candidates
+            .iter()
+            .rev()
+            .find(|&&id| self.resolution.symbols[id].span.start <= at.start)
+            .or_else(|| candidates.first())
+            .copied()
+    }
+
+    fn reference(&mut self, name: &str, span: Span) {
+        // Natives and misspelled names resolve to nothing
+        if let Some(id) = self.lookup(name, span) {
+            self.resolution.references.push(Reference {
+                span,
+                target: Target::Symbol(id),
+            });
+        }
+    }
+
+    fn global_initializer(&mut self, name: &Ident, initializer: &Expr) {
+        self.initializing = self
+            .globals
+            .get(&name.name)
+            .and_then(|ids| ids.iter().copied().find(|&id| self.resolution.symbols[id].span == name.span));
+        self.expr(initializer);
+        self.initializing = None;
+    }
+
+    fn block(&mut self, statements: &[Stmt], span: Span) {
+        self.enter_scope(span);
+
+        // Local functions are hoisted to the top of their block
+        for stmt in statements {
+            if let StmtKind::FunctionDecl { name, .. } = &stmt.kind {
+                let id = self.declare_local(name, SymbolKind::Function);
+                self.resolution.symbols[id].visible = Some(span.start..span.end);
+            }
+        }
+        for stmt in statements {
+            self.stmt(stmt);
+        }
+
+        self.exit_scope();
+    }
+
+    fn function(&mut self, params: &[Ident], body: &[Stmt], span: Span) {
+        self.enter_scope(span);
+        for param in params {
+            self.declare_local(param, SymbolKind::Parameter);
+        }
+        self.block(body, span);
+        self.exit_scope();
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        let global = self.scopes.is_empty();
+
+        match &stmt.kind {
+            StmtKind::Expression(expr) | StmtKind::Print(expr) => self.expr(expr),
+            StmtKind::VarDecl {
+                name,
+                initializer,
+                mutable,
+            } => {
+                // The initializer cannot see the binding it defines
+                if global {
+                    self.global_initializer(name, initializer);
+                } else {
+                    self.expr(initializer);
+                    // A cursor right after the initializer is still writing it
+                    let kind = if *mutable { SymbolKind::Variable } else { SymbolKind::Constant };
+                    self.declare_local_from(name, kind, stmt.span.end + 1);
+                }
+            }
+            StmtKind::ExportVarDecl { name, initializer } => self.global_initializer(name, initializer),
+            StmtKind::Block(statements) => self.block(statements, stmt.span),
+            StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expr(condition);
+                self.stmt(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.stmt(else_branch);
+                }
+            }
+            StmtKind::While { condition, body } => {
+                self.expr(condition);
+                self.stmt(body);
+            }
+            StmtKind::Return { value } => {
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+            }
+            StmtKind::TypeDecl { .. } | StmtKind::Import { .. } => {
+                if !global {
+                    self.declare_always_global(stmt);
+                }
+            }
+            StmtKind::FunctionDecl { params, body, .. }
+            | StmtKind::ExportFunctionDecl { params, body, .. } => self.function(params, body, stmt.span),
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Variable(name) => self.reference(name, expr.span),
+            ExprKind::Assign { name, value } => {
+                self.expr(value);
+                self.reference(&name.name, name.span);
+            }
+            ExprKind::Binary { left, right, .. } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            ExprKind::Unary { right, .. } => self.expr(right),
+            ExprKind::Literal(_) => {}
+            ExprKind::Grouping(inner) => self.expr(inner),
+            ExprKind::Call { callee, arguments } => {
+                self.expr(callee);
+                for argument in arguments {
+                    self.expr(argument);
+                }
+            }
+            ExprKind::Lambda { params, body } => match body {
+                LambdaBody::Expr(body) => {
+                    self.enter_scope(expr.span);
+                    for param in params {
+                        self.declare_local(param, SymbolKind::Parameter);
+                    }
+                    self.expr(body);
+                    self.exit_scope();
+                }
+                LambdaBody::Block(body) => self.function(params, body, expr.span),
+            },
+            ExprKind::List(items) => {
+                for item in items {
+                    self.expr(item);
+                }
+            }
+            ExprKind::Index { object, index } => {
+                self.expr(object);
+                self.expr(index);
+            }
+            ExprKind::Match { value, arms } => {
+                self.expr(value);
+                for arm in arms {
+                    self.match_arm(arm);
+                }
+            }
+            ExprKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expr(condition);
+                self.expr(then_branch);
+                self.expr(else_branch);
+            }
+            ExprKind::MemberAccess { object, member } => {
+                self.expr(object);
+
+                // Members of an imported module live in the module's file
+                if let ExprKind::Variable(name) = &object.kind {
+                    if let Some(id) = self.lookup(name, object.span) {
+                        if self.resolution.symbols[id].kind == SymbolKind::Module {
+                            self.resolution.references.push(Reference {
+                                span: member.span,
+                                target: Target::ModuleMember {
+                                    module: id,
+                                    member: member.name.clone(),
+                                },
+                            });
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn match_arm(&mut self, arm: &MatchArm) {
+        self.enter_scope(arm.body.span);
+        self.pattern(&arm.pattern);
+        self.expr(&arm.body);
+        self.exit_scope();
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Wildcard | Pattern::Literal(_) => {}
+            Pattern::Variable(name) => {
+                self.declare_local(name, SymbolKind::PatternBinding);
+            }
+            Pattern::Constructor { name, fields } => {
+                self.reference(&name.name, name.span);
+                for field in fields {
+                    self.pattern(field);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arkaan_lang::lexer::Lexer;
+    use arkaan_lang::parser::Parser;
+
+    use super::{resolve, Resolution, Target};
+
+    fn resolution(source: &str) -> Resolution {
+        let tokens = Lexer::new(source).scan_tokens().unwrap();
+        resolve(&Parser::new(tokens).parse().unwrap())
+    }
+
+    #[test]
+    fn a_binding_is_not_visible_in_its_own_initializer() {
+        let source = "laat x = 1\n{\n    laat x = x + 1\n    druk(x)\n}\n";
+        let resolution = resolution(source);
+        let inner = resolution.symbols.iter().rposition(|symbol| symbol.name == "x").unwrap();
+
+        let in_initializer = source.find("x + 1").unwrap();
+        assert_eq!(resolution.target_at(in_initializer), Some(Target::Symbol(0)));
+        assert!(!resolution.visible_at(in_initializer + 1).contains(&inner));
+        assert!(!resolution.visible_at(source.find(" + 1\n").unwrap() + 4).contains(&inner));
+
+        let after = source.find("x)").unwrap();
+        assert_eq!(resolution.target_at(after), Some(Target::Symbol(inner)));
+        assert!(resolution.visible_at(after).contains(&inner));
+    }
+
+    #[test]
+    fn a_global_is_not_its_own_initializer() {
+        let source = "laat x = x + 1\n";
+        let resolution = resolution(source);
+        assert_eq!(resolution.target_at(source.find("x +").unwrap()), None);
+    }
+}
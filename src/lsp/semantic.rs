@@ -0,0 +1,146 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+use tower_lsp::lsp_types::*;
+
+use arkaan_lang::token::TokenType;
+use arkaan_lang::vm::VM;
+
+use crate::analysis::position_at;
+use crate::document::Analysis;
+use crate::resolver::{Resolution, SymbolKind, Target};
+
+// Indices into these follow the order of the legend sent to the client
+const TOKEN_TYPES: &[SemanticTokenType] = &[
+    SemanticTokenType::KEYWORD,
+    SemanticTokenType::FUNCTION,
+    SemanticTokenType::TYPE,
+    SemanticTokenType::ENUM_MEMBER,
+    SemanticTokenType::NAMESPACE,
+    SemanticTokenType::PARAMETER,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::PROPERTY,
+];
+
+const TOKEN_MODIFIERS: &[SemanticTokenModifier] = &[
+    SemanticTokenModifier::DECLARATION,
+    SemanticTokenModifier::READONLY,
+    SemanticTokenModifier::DEFAULT_LIBRARY,
+];
+
+const KEYWORD: u32 = 0;
+const FUNCTION: u32 = 1;
+const TYPE: u32 = 2;
+const ENUM_MEMBER: u32 = 3;
+const NAMESPACE: u32 = 4;
+const PARAMETER: u32 = 5;
+const VARIABLE: u32 = 6;
+const PROPERTY: u32 = 7;
+
+const DECLARATION: u32 = 1 << 0;
+const READONLY: u32 = 1 << 1;
+const DEFAULT_LIBRARY: u32 = 1 << 2;
+
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TOKEN_TYPES.to_vec(),
+        token_modifiers: TOKEN_MODIFIERS.to_vec(),
+    }
+}
+
+fn natives() -> &'static HashSet<String> {
+    static NATIVES: OnceLock<HashSet<String>> = OnceLock::new();
+    NATIVES.get_or_init(|| VM::natives().iter().map(|native| native.name.clone()).collect())
+}
+
+/// Semantic tokens for the keywords and names of a document, limited to the
+/// byte range `within` when one is given
+pub fn semantic_tokens(analysis: &Analysis, within: Option<(usize, usize)>) -> Vec<SemanticToken> {
+    let text = &analysis.text;
+    let names = classify_names(&analysis.resolution);
+
+    let mut encoded = Vec::new();
+    let mut previous = Position::default();
+
+    for token in &analysis.tokens {
+        if let Some((start, end)) = within {
+            if token.span.end < start || token.span.start > end {
+                continue;
+            }
+        }
+
+        let classified = match &token.token_type {
+            TokenType::As
+            | TokenType::Anders
+            | TokenType::Terwyl
+            | TokenType::Druk
+            | TokenType::Waar
+            | TokenType::Vals
+            | TokenType::Funksie
+            | TokenType::Fn
+            | TokenType::Gee
+            | TokenType::Laat
+            | TokenType::Stel
+            | TokenType::Pas
+            | TokenType::Geval
+            | TokenType::Tipe
+            | TokenType::Of
+            | TokenType::Laai
+            | TokenType::Verskaf => Some((KEYWORD, 0)),
+            TokenType::Identifier(name) => names
+                .get(&token.span.start)
+                .copied()
+                // A name that resolves to nothing in the document is a builtin or a typo
+                .or_else(|| natives().contains(name).then_some((FUNCTION, DEFAULT_LIBRARY))),
+            _ => None,
+        };
+        let Some((token_type, modifiers)) = classified else {
+            continue;
+        };
+
+        let start = position_at(text, token.span.start);
+        let end = position_at(text, token.span.end);
+        let delta_start = if start.line == previous.line {
+            start.character - previous.character
+        } else {
+            start.character
+        };
+        encoded.push(SemanticToken {
+            delta_line: start.line - previous.line,
+            delta_start,
+            length: end.character - start.character,
+            token_type,
+            token_modifiers_bitset: modifiers,
+        });
+        previous = start;
+    }
+
+    encoded
+}
+
+/// Token type and modifiers for every resolved name, keyed by its start offset
+fn classify_names(resolution: &Resolution) -> HashMap<usize, (u32, u32)> {
+    let classify = |kind: SymbolKind| match kind {
+        SymbolKind::Constant | SymbolKind::PatternBinding => (VARIABLE, READONLY),
+        SymbolKind::Variable => (VARIABLE, 0),
+        SymbolKind::Function => (FUNCTION, 0),
+        SymbolKind::Parameter => (PARAMETER, 0),
+        SymbolKind::Type => (TYPE, 0),
+        SymbolKind::Constructor => (ENUM_MEMBER, 0),
+        SymbolKind::Module => (NAMESPACE, 0),
+    };
+
+    let mut names = HashMap::new();
+    for symbol in &resolution.symbols {
+        let (token_type, modifiers) = classify(symbol.kind);
+        names.insert(symbol.span.start, (token_type, modifiers | DECLARATION));
+    }
+    for reference in &resolution.references {
+        let classified = match &reference.target {
+            Target::Symbol(id) => classify(resolution.symbols[*id].kind),
+            Target::ModuleMember { .. } => (PROPERTY, 0),
+        };
+        names.insert(reference.span.start, classified);
+    }
+    names
+}
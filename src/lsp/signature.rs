@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+
+use tower_lsp::lsp_types::*;
+
+use arkaan_lang::ast::{Expr, ExprKind, Ident, Stmt, StmtKind};
+use arkaan_lang::token::{Token, TokenType};
+use arkaan_lang::vm::VM;
+
+use crate::analysis::{offset_at, position_at};
+use crate::document::Analysis;
+use crate::resolver::{Resolution, Target};
+use crate::visit::{walk, Node};
+
+/// Parameter help for the call the cursor is in
+pub fn signature_help(analysis: &Analysis, position: Position) -> Option<SignatureHelp> {
+    let offset = offset_at(&analysis.text, position);
+    let (tokens, resolution) = (&analysis.tokens, &analysis.resolution);
+    let declared = declared_signatures(&analysis.statements);
+
+    let (open_paren, commas) = open_call(tokens, offset)?;
+    let callee = open_paren.checked_sub(1).map(|i| &tokens[i])?;
+    let TokenType::Identifier(name) = &callee.token_type else {
+        return None;
+    };
+    // The parameter list of a declaration is not a call
+    if open_paren >= 2 && tokens[open_paren - 2].token_type == TokenType::Funksie {
+        return None;
+    }
+    let params = callee_params(resolution, &declared, name, callee.span.start)?;
+
+    // `x -> f(a)` passes `x` as the first argument
+    let piped = open_paren >= 2 && tokens[open_paren - 2].token_type == TokenType::Arrow;
+    let active = commas + usize::from(piped);
+
+    let mut label = format!("{}(", name);
+    let mut parameters = Vec::new();
+    for (i, param) in params.iter().enumerate() {
+        if i > 0 {
+            label.push_str(", ");
+        }
+        let start = label.encode_utf16().count() as u32;
+        label.push_str(param);
+        let end = label.encode_utf16().count() as u32;
+        parameters.push(ParameterInformation {
+            label: ParameterLabel::LabelOffsets([start, end]),
+            documentation: None,
+        });
+    }
+    label.push(')');
+
+    Some(SignatureHelp {
+        signatures: vec![SignatureInformation {
+            label,
+            documentation: None,
+            parameters: Some(parameters),
+            active_parameter: None,
+        }],
+        active_signature: Some(0),
+        active_parameter: Some(active as u32),
+    })
+}
+
+/// Parameter names shown before the arguments of calls within a range
+pub fn inlay_hints(analysis: &Analysis, range: Range) -> Vec<InlayHint> {
+    let text = &analysis.text;
+    let declared = declared_signatures(&analysis.statements);
+    let (start, end) = (offset_at(text, range.start), offset_at(text, range.end));
+
+    let mut hints = Vec::new();
+    walk(&analysis.statements, &mut |node| {
+        let Node::Expr(Expr {
+            kind: ExprKind::Call { callee, arguments },
+            span,
+        }) = node
+        else {
+            return;
+        };
+        let ExprKind::Variable(name) = &callee.kind else {
+            return;
+        };
+        if span.end < start || span.start > end {
+            return;
+        }
+        let Some(params) = callee_params(&analysis.resolution, &declared, name, callee.span.start) else {
+            return;
+        };
+
+        for (argument, param) in arguments.iter().zip(&params) {
+            // A piped value sits before the function, where a hint would read oddly
+            if argument.span.start < callee.span.start {
+                continue;
+            }
+            // `f(lys)` for a parameter called `lys` says it already
+            if matches!(&argument.kind, ExprKind::Variable(arg) if arg == param) {
+                continue;
+            }
+            hints.push(InlayHint {
+                position: position_at(text, argument.span.start),
+                label: InlayHintLabel::String(format!("{}:", param)),
+                kind: Some(InlayHintKind::PARAMETER),
+                text_edits: None,
+                tooltip: None,
+                padding_left: None,
+                padding_right: Some(true),
+                data: None,
+            });
+        }
+    });
+
+    hints
+}
+
+/// The innermost `(` still open at an offset, and how many of its arguments
+/// have been finished with a comma
+fn open_call(tokens: &[Token], offset: usize) -> Option<(usize, usize)> {
+    let mut open: Vec<(usize, usize)> = Vec::new();  // Index of each open bracket, commas after it
+
+    for (i, token) in tokens.iter().enumerate() {
+        if token.span.start >= offset {
+            break;
+        }
+        match token.token_type {
+            TokenType::LeftParen | TokenType::LeftBracket | TokenType::LeftBrace => open.push((i, 0)),
+            TokenType::RightParen | TokenType::RightBracket | TokenType::RightBrace => {
+                open.pop();
+            }
+            TokenType::Comma => {
+                if let Some((_, commas)) = open.last_mut() {
+                    *commas += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    open.into_iter()
+        .rev()
+        .find(|&(i, _)| tokens[i].token_type == TokenType::LeftParen)
+}
+
+/// Parameter names of what a call's callee refers to: a function, a lambda
+/// bound with `laat`, a constructor, or a builtin
+fn callee_params(
+    resolution: &Resolution,
+    declared: &HashMap<usize, Vec<String>>,
+    name: &str,
+    at: usize,
+) -> Option<Vec<String>> {
+    match resolution.target_at(at) {
+        Some(Target::Symbol(id)) => declared.get(&resolution.symbols[id].span.start).cloned(),
+        Some(Target::ModuleMember { .. }) => None,
+        None => VM::natives()
+            .into_iter()
+            .find(|native| native.name == name)
+            .map(|native| native.params.iter().map(|param| param.to_string()).collect()),
+    }
+}
+
+/// Parameters of everything the document declares that can be called, keyed
+/// by where its name is declared
+fn declared_signatures(statements: &[Stmt]) -> HashMap<usize, Vec<String>> {
+    let mut declared = HashMap::new();
+    let names = |params: &[Ident]| -> Vec<String> { params.iter().map(|param| param.name.clone()).collect() };
+
+    walk(statements, &mut |node| {
+        let Node::Stmt(stmt) = node else {
+            return;
+        };
+        match &stmt.kind {
+            StmtKind::FunctionDecl { name, params, .. } | StmtKind::ExportFunctionDecl { name, params, .. } => {
+                declared.insert(name.span.start, names(params));
+            }
+            // A `stel` binding may be given another value later
+            StmtKind::VarDecl {
+                name,
+                initializer,
+                mutable: false,
+            }
+            | StmtKind::ExportVarDecl { name, initializer } => {
+                if let ExprKind::Lambda { params, .. } = &initializer.kind {
+                    declared.insert(name.span.start, names(params));
+                }
+            }
+            StmtKind::TypeDecl { constructors, .. } => {
+                for constructor in constructors {
+                    declared.insert(constructor.name.span.start, constructor.fields.clone());
+                }
+            }
+            _ => {}
+        }
+    });
+
+    declared
+}
@@ -0,0 +1,263 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use tower_lsp::lsp_types::*;
+
+use arkaan_lang::ast::{Expr, ExprKind, Ident, LambdaBody, Stmt, StmtKind};
+use arkaan_lang::span::Span;
+
+use crate::analysis::{position_at, span_range};
+use crate::document::Analysis;
+
+/// Outline of a document: its top-level bindings, functions and types
+pub fn document_symbols(analysis: &Analysis) -> Vec<DocumentSymbol> {
+    let text = &analysis.text;
+
+    analysis
+        .statements
+        .iter()
+        .filter_map(|stmt| {
+            let (name, kind, detail, children) = match &stmt.kind {
+                StmtKind::VarDecl { name, mutable, .. } => {
+                    let kind = if *mutable { SymbolKind::VARIABLE } else { SymbolKind::CONSTANT };
+                    (name, kind, None, None)
+                }
+                StmtKind::ExportVarDecl { name, .. } => {
+                    (name, SymbolKind::CONSTANT, Some("verskaf".to_string()), None)
+                }
+                StmtKind::FunctionDecl { name, params, .. } => {
+                    (name, SymbolKind::FUNCTION, Some(parameter_list(params)), None)
+                }
+                StmtKind::ExportFunctionDecl { name, params, .. } => {
+                    let detail = format!("verskaf {}", parameter_list(params));
+                    (name, SymbolKind::FUNCTION, Some(detail), None)
+                }
+                StmtKind::TypeDecl { name, constructors } => {
+                    let children = constructors
+                        .iter()
+                        .map(|constructor| {
+                            let detail = (!constructor.fields.is_empty())
+                                .then(|| format!("({})", constructor.fields.join(", ")));
+                            symbol(text, &constructor.name, SymbolKind::ENUM_MEMBER, detail, constructor.name.span, None)
+                        })
+                        .collect();
+                    (name, SymbolKind::ENUM, None, Some(children))
+                }
+                _ => return None,
+            };
+            Some(symbol(text, name, kind, detail, stmt.span, children))
+        })
+        .collect()
+}
+
+fn parameter_list(params: &[Ident]) -> String {
+    let names: Vec<&str> = params.iter().map(|param| param.name.as_str()).collect();
+    format!("({})", names.join(", "))
+}
+
+#[allow(deprecated)] // `DocumentSymbol::deprecated` must still be given
+fn symbol(
+    text: &str,
+    name: &Ident,
+    kind: SymbolKind,
+    detail: Option<String>,
+    span: Span,
+    children: Option<Vec<DocumentSymbol>>,
+) -> DocumentSymbol {
+    DocumentSymbol {
+        name: name.name.clone(),
+        detail,
+        kind,
+        tags: None,
+        deprecated: None,
+        range: span_range(text, span),
+        selection_range: span_range(text, name.span),
+        children,
+    }
+}
+
+/// Symbols across the given documents whose name contains the query
+#[allow(deprecated)] // `SymbolInformation::deprecated` must still be given
+pub fn workspace_symbols(query: &str, documents: &[(Url, Arc<Analysis>)]) -> Vec<SymbolInformation> {
+    let query = query.to_lowercase();
+    let mut found = Vec::new();
+
+    for (uri, analysis) in documents {
+        let mut pending: Vec<(DocumentSymbol, Option<String>)> =
+            document_symbols(analysis).into_iter().map(|symbol| (symbol, None)).collect();
+
+        while let Some((symbol, container_name)) = pending.pop() {
+            for child in symbol.children.iter().flatten() {
+                pending.push((child.clone(), Some(symbol.name.clone())));
+            }
+            if symbol.name.to_lowercase().contains(&query) {
+                found.push(SymbolInformation {
+                    name: symbol.name,
+                    kind: symbol.kind,
+                    tags: None,
+                    deprecated: None,
+                    location: Location::new(uri.clone(), symbol.selection_range),
+                    container_name,
+                });
+            }
+        }
+    }
+
+    found
+}
+
+/// All Arkaan source files below a directory, skipping hidden and build directories
+pub fn ark_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut directories = vec![root.to_path_buf()];
+
+    while let Some(directory) = directories.pop() {
+        let Ok(entries) = fs::read_dir(&directory) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if path.is_dir() {
+                if !name.starts_with('.') && name != "target" && name != "node_modules" {
+                    directories.push(path);
+                }
+            } else if path.extension().is_some_and(|extension| extension == "ark") {
+                files.push(path);
+            }
+        }
+    }
+
+    files
+}
+
+/// Folding ranges for blocks, function bodies, `pas` expressions and `tipe` declarations
+pub fn folding_ranges(analysis: &Analysis) -> Vec<FoldingRange> {
+    let mut folder = Folder {
+        text: &analysis.text,
+        ranges: Vec::new(),
+    };
+    folder.stmts(&analysis.statements);
+    folder.ranges
+}
+
+struct Folder<'a> {
+    text: &'a str,
+    ranges: Vec<FoldingRange>,
+}
+
+impl Folder<'_> {
+    fn fold(&mut self, span: Span) {
+        let start_line = position_at(self.text, span.start).line;
+        let end_line = position_at(self.text, span.end).line;
+
+        // Leave the line with the closing '}' visible
+        if end_line > start_line + 1 {
+            self.ranges.push(FoldingRange {
+                start_line,
+                end_line: end_line - 1,
+                kind: Some(FoldingRangeKind::Region),
+                ..Default::default()
+            });
+        }
+    }
+
+    fn stmts(&mut self, statements: &[Stmt]) {
+        for stmt in statements {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Expression(expr) | StmtKind::Print(expr) => self.expr(expr),
+            StmtKind::VarDecl { initializer, .. } | StmtKind::ExportVarDecl { initializer, .. } => {
+                self.expr(initializer)
+            }
+            StmtKind::Block(statements) => {
+                self.fold(stmt.span);
+                self.stmts(statements);
+            }
+            StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expr(condition);
+                self.stmt(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.stmt(else_branch);
+                }
+            }
+            StmtKind::While { condition, body } => {
+                self.expr(condition);
+                self.stmt(body);
+            }
+            StmtKind::Return { value } => {
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+            }
+            StmtKind::TypeDecl { .. } => self.fold(stmt.span),
+            StmtKind::Import { .. } => {}
+            StmtKind::FunctionDecl { body, .. } | StmtKind::ExportFunctionDecl { body, .. } => {
+                self.fold(stmt.span);
+                self.stmts(body);
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Binary { left, right, .. } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            ExprKind::Unary { right, .. } => self.expr(right),
+            ExprKind::Literal(_) | ExprKind::Variable(_) => {}
+            ExprKind::Grouping(inner) => self.expr(inner),
+            ExprKind::Call { callee, arguments } => {
+                self.expr(callee);
+                for argument in arguments {
+                    self.expr(argument);
+                }
+            }
+            ExprKind::Lambda { body, .. } => match body {
+                LambdaBody::Expr(body) => self.expr(body),
+                LambdaBody::Block(statements) => {
+                    self.fold(expr.span);
+                    self.stmts(statements);
+                }
+            },
+            ExprKind::List(items) => {
+                for item in items {
+                    self.expr(item);
+                }
+            }
+            ExprKind::Index { object, index } => {
+                self.expr(object);
+                self.expr(index);
+            }
+            ExprKind::Match { value, arms } => {
+                self.fold(expr.span);
+                self.expr(value);
+                for arm in arms {
+                    self.expr(&arm.body);
+                }
+            }
+            ExprKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expr(condition);
+                self.expr(then_branch);
+                self.expr(else_branch);
+            }
+            ExprKind::MemberAccess { object, .. } => self.expr(object),
+            ExprKind::Assign { value, .. } => self.expr(value),
+        }
+    }
+}
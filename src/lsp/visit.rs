@@ -0,0 +1,97 @@
+use arkaan_lang::ast::{Expr, ExprKind, LambdaBody, Stmt, StmtKind};
+
+/// A statement or expression met while walking a syntax tree
+pub enum Node<'a> {
+    Stmt(&'a Stmt),
+    Expr(&'a Expr),
+}
+
+/// Visits every statement and expression, outermost first
+pub fn walk<'a>(statements: &'a [Stmt], visit: &mut dyn FnMut(Node<'a>)) {
+    for stmt in statements {
+        walk_stmt(stmt, visit);
+    }
+}
+
+fn walk_stmt<'a>(stmt: &'a Stmt, visit: &mut dyn FnMut(Node<'a>)) {
+    visit(Node::Stmt(stmt));
+    match &stmt.kind {
+        StmtKind::Expression(expr) | StmtKind::Print(expr) => walk_expr(expr, visit),
+        StmtKind::VarDecl { initializer, .. } | StmtKind::ExportVarDecl { initializer, .. } => {
+            walk_expr(initializer, visit)
+        }
+        StmtKind::Block(statements)
+        | StmtKind::FunctionDecl { body: statements, .. }
+        | StmtKind::ExportFunctionDecl { body: statements, .. } => walk(statements, visit),
+        StmtKind::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            walk_expr(condition, visit);
+            walk_stmt(then_branch, visit);
+            if let Some(else_branch) = else_branch {
+                walk_stmt(else_branch, visit);
+            }
+        }
+        StmtKind::While { condition, body } => {
+            walk_expr(condition, visit);
+            walk_stmt(body, visit);
+        }
+        StmtKind::Return { value } => {
+            if let Some(value) = value {
+                walk_expr(value, visit);
+            }
+        }
+        StmtKind::TypeDecl { .. } | StmtKind::Import { .. } => {}
+    }
+}
+
+fn walk_expr<'a>(expr: &'a Expr, visit: &mut dyn FnMut(Node<'a>)) {
+    visit(Node::Expr(expr));
+    match &expr.kind {
+        ExprKind::Binary { left, right, .. } => {
+            walk_expr(left, visit);
+            walk_expr(right, visit);
+        }
+        ExprKind::Unary { right: inner, .. }
+        | ExprKind::Grouping(inner)
+        | ExprKind::MemberAccess { object: inner, .. }
+        | ExprKind::Assign { value: inner, .. } => walk_expr(inner, visit),
+        ExprKind::Literal(_) | ExprKind::Variable(_) => {}
+        ExprKind::Call { callee, arguments } => {
+            walk_expr(callee, visit);
+            for argument in arguments {
+                walk_expr(argument, visit);
+            }
+        }
+        ExprKind::Lambda { body, .. } => match body {
+            LambdaBody::Expr(body) => walk_expr(body, visit),
+            LambdaBody::Block(statements) => walk(statements, visit),
+        },
+        ExprKind::List(items) => {
+            for item in items {
+                walk_expr(item, visit);
+            }
+        }
+        ExprKind::Index { object, index } => {
+            walk_expr(object, visit);
+            walk_expr(index, visit);
+        }
+        ExprKind::Match { value, arms } => {
+            walk_expr(value, visit);
+            for arm in arms {
+                walk_expr(&arm.body, visit);
+            }
+        }
+        ExprKind::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            walk_expr(condition, visit);
+            walk_expr(then_branch, visit);
+            walk_expr(else_branch, visit);
+        }
+    }
+}
@@ -1,65 +1,637 @@
-mod ast;
-mod bytecode;
-mod compiler;
-mod lexer;
-mod parser;
-mod token;
-mod value;
-mod vm;
-
+use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::io::{self, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+use arkaan_lang::arkc::{self, CompiledFile};
+use arkaan_lang::debugger::{DebugHandler, PauseReason, Step};
+use arkaan_lang::disassembler::disassemble;
+use arkaan_lang::engine::Engine;
+use arkaan_lang::error::{ArkaanError, ErrorKind, Stage};
+use arkaan_lang::formatter::format_source;
+use arkaan_lang::lexer::Lexer;
+use arkaan_lang::sandbox::{Limits, ModuleAccess};
+use arkaan_lang::span::SourceFile;
+use arkaan_lang::token::TokenType;
+use arkaan_lang::value::Value;
+use arkaan_lang::vm::VM;
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// Exit codes, after sysexits
+const EXIT_USAGE: i32 = 64;
+const EXIT_COMPILE: i32 = 65;   // Lex, parse or compile errors, or a damaged .arkc file
+const EXIT_NO_INPUT: i32 = 66;  // A file could not be read
+const EXIT_RUNTIME: i32 = 70;   // Errors while running
+const EXIT_CANT_CREATE: i32 = 73;  // An output file could not be written
+
+/// Native stack for running programs. Calls do not use it, but every native
+/// such as `kaart` calling back into Arkaan does, up to --max-callback-depth deep.
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+const USAGE: &str = "\
+Gebruik: arkaan [opdrag] [opsies]
+
+Opdragte:
+  run [opsies] <lêer> [-- argumente...]
+                                    Voer 'n program (.ark of .arkc) uit; met --cache-modules
+                                    word gelaaide modules as .arkc langs hul bron gestoor,
+                                    --max-call-depth <n> beperk hoe diep oproepe mag nes,
+                                    --max-callback-depth <n> hoe diep kaart, filter, vou
+                                    en vir_elk mag nes,
+                                    --gc-stats wys wat die vullisversamelaar gedoen het en
+                                    --gc-stress laat dit by elke geleentheid loop.
+                                    Vir programme wat nie vertrou word nie: --fuel <n>
+                                    beperk die aantal instruksies, --timeout <ms> die tyd,
+                                    --max-len <n> die lengte van elke lys en string,
+                                    --no-modules verbied 'laai' en --modules <gids> laat
+                                    net modules uit die gids toe
+  check <lêer.ark>...               Kontroleer programme sonder om hulle uit te voer
+  compile <lêer.ark> [-o uit.arkc]  Kompileer 'n program na greepkode
+  disasm <lêer>                     Wys die greepkode van 'n program (.ark of .arkc)
+  fmt [--check] <lêer.ark>...       Formateer programme
+  test [pad...]                     Voer die test_*.ark lêers in die paaie uit
+  debug <lêer.ark> [-- argumente...]
+                                    Voer 'n program stap vir stap uit, met breekpunte
+  repl                              Begin die interaktiewe REPL (ook sonder opdrag)
+
+Opsies:
+  -h, --help                        Wys hierdie hulp
+  -V, --version                     Wys die weergawe
 
-use compiler::Compiler;
-use lexer::Lexer;
-use parser::Parser;
-use vm::VM;
+'arkaan lêer.ark' is kort vir 'arkaan run lêer.ark'.";
+
+const RUN_USAGE: &str = "\
+Gebruik: arkaan run [--cache-modules] [--max-call-depth <n>] [--max-callback-depth <n>]
+                    [--gc-stats] [--gc-stress]
+                    [--fuel <n>] [--timeout <ms>] [--max-len <n>] [--no-modules | --modules <gids>...]
+                    <lêer> [-- argumente...]";
+
+/// Options for `arkaan run` that set up the VM
+#[derive(Default)]
+struct RunOptions {
+    cache_modules: bool,
+    limits: Limits,
+    gc_stats: bool,   // Report the collector's work on stderr afterwards
+    gc_stress: bool,  // Collect before every closure, to find values the collector misses
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let cli = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(cli)
+        .expect("could not start the interpreter thread");
+    // A panic has already been reported; exit as the main thread would have
+    if cli.join().is_err() {
+        exit(101);
+    }
+}
+
+fn cli() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let Some(command) = args.first() else {
+        repl();
+        return;
+    };
+    let rest = &args[1..];
+
+    match command.as_str() {
+        "run" => {
+            let mut options = RunOptions::default();
+            let mut rest = rest;
+            loop {
+                match rest {
+                    [flag, tail @ ..] if flag == "--cache-modules" => {
+                        options.cache_modules = true;
+                        rest = tail;
+                    }
+                    [flag, depth, tail @ ..] if flag == "--max-call-depth" => {
+                        options.limits.max_call_depth = positive(flag, depth);
+                        rest = tail;
+                    }
+                    [flag, depth, tail @ ..] if flag == "--max-callback-depth" => {
+                        options.limits.max_callback_depth = positive(flag, depth);
+                        rest = tail;
+                    }
+                    [flag, fuel, tail @ ..] if flag == "--fuel" => {
+                        options.limits.fuel = Some(positive(flag, fuel));
+                        rest = tail;
+                    }
+                    [flag, millis, tail @ ..] if flag == "--timeout" => {
+                        options.limits.timeout = Some(Duration::from_millis(positive(flag, millis)));
+                        rest = tail;
+                    }
+                    [flag, len, tail @ ..] if flag == "--max-len" => {
+                        options.limits.max_len = Some(positive(flag, len));
+                        rest = tail;
+                    }
+                    [flag, tail @ ..] if flag == "--no-modules" => {
+                        options.limits.modules = ModuleAccess::None;
+                        rest = tail;
+                    }
+                    [flag, dir, tail @ ..] if flag == "--modules" => {
+                        match &mut options.limits.modules {
+                            ModuleAccess::Within(dirs) => dirs.push(PathBuf::from(dir)),
+                            modules => *modules = ModuleAccess::Within(vec![PathBuf::from(dir)]),
+                        }
+                        rest = tail;
+                    }
+                    [flag, tail @ ..] if flag == "--gc-stats" => {
+                        options.gc_stats = true;
+                        rest = tail;
+                    }
+                    [flag, tail @ ..] if flag == "--gc-stress" => {
+                        options.gc_stress = true;
+                        rest = tail;
+                    }
+                    _ => break,
+                }
+            }
+            match rest.split_first() {
+                Some((path, program_args)) => run_file(path, program_args, options),
+                None => usage_error(RUN_USAGE),
+            }
+        }
+        "check" if !rest.is_empty() => check_files(rest),
+        "check" => usage_error("Gebruik: arkaan check <lêer.ark>..."),
+        "compile" => match rest {
+            [path] => compile_file(path, &Path::new(path).with_extension("arkc")),
+            [path, flag, output] if flag == "-o" => compile_file(path, Path::new(output)),
+            _ => usage_error("Gebruik: arkaan compile <lêer.ark> [-o uit.arkc]"),
+        },
+        "disasm" if rest.len() == 1 => disassemble_file(&rest[0]),
+        "disasm" => usage_error("Gebruik: arkaan disasm <lêer>"),
+        "fmt" => format_files(rest),
+        "test" => run_tests(rest),
+        "debug" => match rest.split_first() {
+            Some((path, program_args)) => debug_file(path, program_args),
+            None => usage_error("Gebruik: arkaan debug <lêer.ark> [-- argumente...]"),
+        },
+        "repl" if rest.is_empty() => repl(),
+        "-h" | "--help" => println!("{}", USAGE),
+        "-V" | "--version" => println!("arkaan {}", VERSION),
+        path if !path.starts_with('-') => run_file(path, rest, RunOptions::default()),
+        _ => usage_error(USAGE),
+    }
+}
+
+/// The value of a numeric flag, which must be above zero
+fn positive<T: FromStr + Default + PartialOrd>(flag: &str, value: &str) -> T {
+    match value.parse() {
+        Ok(value) if value > T::default() => value,
+        _ => usage_error(&format!("{} verwag 'n positiewe heelgetal.", flag)),
+    }
+}
+
+fn usage_error(usage: &str) -> ! {
+    eprintln!("{}", usage);
+    exit(EXIT_USAGE);
+}
 
-    match args.len() {
-        1 => repl(),
-        2 => run_file(&args[1]),
-        _ => {
-            eprintln!("Gebruik: arkaan [lêer.ark]");
-            std::process::exit(64);
+fn read_source(path: &str) -> Rc<SourceFile> {
+    match fs::read_to_string(path) {
+        Ok(content) => Rc::new(SourceFile::new(path, content)),
+        Err(e) => {
+            eprintln!("Kon nie {} lees nie: {}", path, e);
+            exit(EXIT_NO_INPUT);
         }
     }
 }
 
-fn run_file(path: &str) {
-    let source = match fs::read_to_string(path) {
-        Ok(content) => content,
+/// Compiles a source file, or reads an `.arkc` file compiled earlier
+fn load(path: &str) -> Result<CompiledFile, Vec<ArkaanError>> {
+    if !path.ends_with(".arkc") {
+        return Engine::compile(&read_source(path));
+    }
+    match fs::read(path) {
+        Ok(bytes) => arkc::decode(&bytes).map_err(|e| vec![e]),
         Err(e) => {
-            eprintln!("Kon nie lêer lees nie: {}", e);
-            std::process::exit(66);
+            eprintln!("Kon nie {} lees nie: {}", path, e);
+            exit(EXIT_NO_INPUT);
+        }
+    }
+}
+
+/// `arkaan run`: the arguments after the file, past an optional `--`, are
+/// the program's `argumente`
+fn run_file(path: &str, program_args: &[String], options: RunOptions) {
+    let program_args = strip_dashes(program_args);
+
+    let result = load(path).and_then(|compiled| {
+        let mut engine = engine_for(path, program_args.to_vec());
+        let vm = engine.vm_mut();
+        vm.set_module_caching(options.cache_modules);
+        vm.set_limits(options.limits);
+        vm.set_gc_stress(options.gc_stress);
+        let result = engine.execute(compiled).map_err(|e| vec![e]);
+        if options.gc_stats {
+            let stats = engine.vm().gc_stats();
+            eprintln!(
+                "Vullisversameling: {} keer; {} afsluitings en opwaardes gemaak, {} in siklusse vrygestel, {} nog lewend",
+                stats.collections, stats.allocated, stats.freed, stats.live
+            );
+        }
+        result
+    });
+    if let Err(errors) = result {
+        report_errors(&errors);
+        exit(exit_code(&errors));
+    }
+}
+
+/// `arkaan check`: lexes, parses and compiles without running anything
+fn check_files(paths: &[String]) {
+    let mut failed = false;
+    for path in paths {
+        if let Err(errors) = Engine::compile(&read_source(path)) {
+            report_errors(&errors);
+            failed = true;
+        }
+    }
+    if failed {
+        exit(EXIT_COMPILE);
+    }
+}
+
+/// `arkaan compile`: writes the bytecode of a source file to an `.arkc` file
+fn compile_file(path: &str, output: &Path) {
+    let compiled = match Engine::compile(&read_source(path)) {
+        Ok(compiled) => compiled,
+        Err(errors) => {
+            report_errors(&errors);
+            exit(EXIT_COMPILE);
+        }
+    };
+    let written = arkc::encode(&compiled).map(|bytes| fs::write(output, bytes));
+    match written {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => {
+            eprintln!("Kon nie {} skryf nie: {}", output.display(), e);
+            exit(EXIT_CANT_CREATE);
+        }
+        Err(e) => {
+            report_error(&e);
+            exit(EXIT_RUNTIME);
+        }
+    }
+}
+
+/// `arkaan disasm`: lists the instructions and constants of the script and
+/// of every function in it
+fn disassemble_file(path: &str) {
+    let CompiledFile { chunk, globals, .. } = match load(path) {
+        Ok(compiled) => compiled,
+        Err(errors) => {
+            report_errors(&errors);
+            exit(EXIT_COMPILE);
+        }
+    };
+    print!("{}", disassemble("<script>", &chunk, &globals));
+}
+
+/// `arkaan fmt`: rewrites files in the canonical style, or with `--check`
+/// only lists the ones that are not in it yet
+fn format_files(args: &[String]) {
+    let check = args.iter().any(|arg| arg == "--check");
+    let paths: Vec<&String> = args.iter().filter(|arg| *arg != "--check").collect();
+    if paths.is_empty() {
+        usage_error("Gebruik: arkaan fmt [--check] lêer.ark...");
+    }
+
+    let mut failed = false;
+    let mut unformatted = false;
+    for path in paths {
+        let source = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("Kon nie {} lees nie: {}", path, e);
+                failed = true;
+                continue;
+            }
+        };
+
+        let formatted = match format_source(&source) {
+            Ok(formatted) => formatted,
+            Err(errors) => {
+                // Only source that parses is formatted
+                let file = Rc::new(SourceFile::new(path.as_str(), source));
+                for e in errors {
+                    report_error(&e.in_file(&file));
+                }
+                failed = true;
+                continue;
+            }
+        };
+        if formatted == source {
+            continue;
+        }
+
+        if check {
+            println!("Nie geformateer nie: {}", path);
+            unformatted = true;
+        } else if let Err(e) = fs::write(path, formatted) {
+            eprintln!("Kon nie {} skryf nie: {}", path, e);
+            failed = true;
+        }
+    }
+
+    if failed {
+        exit(EXIT_COMPILE);
+    }
+    if unformatted {
+        exit(1);
+    }
+}
+
+/// `arkaan test`: runs every `test_*.ark` file in the given files and
+/// directories. A test passes when it runs without errors, or, if it has a
+/// `// verwag fout: E0301` line, when it stops with that error.
+fn run_tests(paths: &[String]) {
+    let roots = if paths.is_empty() { vec![String::from(".")] } else { paths.to_vec() };
+    let mut files = Vec::new();
+    for root in &roots {
+        let root = Path::new(root);
+        if root.is_dir() {
+            files.extend(test_files(root));
+        } else {
+            files.push(root.to_path_buf());
+        }
+    }
+
+    let mut failed = Vec::new();
+    for path in &files {
+        let path = path.display().to_string();
+        let file = read_source(&path);
+        let expected = file.text.lines().find_map(|line| line.trim().strip_prefix("// verwag fout:")).map(str::trim);
+
+        let result = Engine::compile(&file)
+            .and_then(|compiled| engine_for(&path, Vec::new()).execute(compiled).map_err(|e| vec![e]));
+        let passed = match (&result, expected) {
+            (Ok(_), None) => true,
+            (Err(errors), Some(code)) => errors[0].code() == code,
+            _ => false,
+        };
+
+        if passed {
+            println!("toets {} ... ok", path);
+            continue;
+        }
+        println!("toets {} ... GEFAAL", path);
+        match result {
+            Ok(_) => eprintln!("Verwag fout {} maar die program het sonder foute geloop.", expected.unwrap_or_default()),
+            Err(errors) => report_errors(&errors),
+        }
+        failed.push(path);
+    }
+
+    println!("\n{} geslaag, {} gefaal", files.len() - failed.len(), failed.len());
+    if !failed.is_empty() {
+        exit(1);
+    }
+}
+
+/// The `test_*.ark` files under a directory, in name order
+fn test_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files = Vec::new();
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.is_dir() {
+            files.extend(test_files(&path));
+        } else if path.extension().is_some_and(|ext| ext == "ark")
+            && path.file_name().is_some_and(|name| name.to_string_lossy().starts_with("test_"))
+        {
+            files.push(path);
+        }
+    }
+    files.sort();
+    files
+}
+
+/// The program's own arguments: those after the file, past an optional `--`
+fn strip_dashes(program_args: &[String]) -> &[String] {
+    match program_args.split_first() {
+        Some((dashes, rest)) if dashes == "--" => rest,
+        _ => program_args,
+    }
+}
+
+const DEBUG_HELP: &str = "\
+Opdragte:
+  gaan, c                 Gaan voort tot by die volgende breekpunt
+  stap, s                 Gaan na die volgende lyn, ook binne 'n oproep
+  volgende, n             Gaan na die volgende lyn van hierdie funksie
+  uit, o                  Gaan voort tot terug in die roeper
+  breek, b [lêer:]<lyn>   Stel 'n breekpunt; sonder lyn, wys die breekpunte
+  verwyder, d [lêer:]<lyn>
+                          Verwyder 'n breekpunt
+  stapel, bt              Wys die oproepstapel
+  raam, f <n>             Kyk na raam n van die stapel (0 is die binneste)
+  lokaal, l               Wys die lokale en vasgevangde veranderlikes van die raam
+  globaal, g              Wys die globale veranderlikes
+  druk, p <uitdrukking>   Evalueer 'n uitdrukking in die raam
+  hulp, h                 Wys hierdie hulp
+  verlaat, q              Stop die program";
+
+/// `arkaan debug`: runs a program paused at its first line, with commands
+/// read from stdin at every pause
+fn debug_file(path: &str, program_args: &[String]) {
+    let compiled = match Engine::compile(&read_source(path)) {
+        Ok(compiled) => compiled,
+        Err(errors) => {
+            report_errors(&errors);
+            exit(EXIT_COMPILE);
         }
     };
+    let mut engine = engine_for(path, strip_dashes(program_args).to_vec());
+    engine.vm_mut().attach_debugger(Console::new(path));
+    println!("Tik 'hulp' vir die opdragte.");
 
-    if let Err(e) = run_with_path(&source, path) {
-        eprintln!("Fout: {}", e);
-        std::process::exit(70);
+    if let Err(e) = engine.execute(compiled) {
+        report_error(&e);
+        exit(exit_code(&[e]));
     }
+    println!("Die program het klaar geloop.");
 }
 
-fn run_with_path(source: &str, path: &str) -> Result<(), String> {
-    // Lexing
-    let mut lexer = Lexer::new(source);
-    let tokens = lexer.scan_tokens()?;
+/// The debugger's command line
+struct Console {
+    main_file: PathBuf,  // Where a breakpoint without a file goes
+    frame: usize,        // Frame the commands look at, from the top of the stack
+    sources: HashMap<String, Vec<String>>,  // Lines of each file shown so far
+}
+
+impl Console {
+    fn new(main_file: &str) -> Self {
+        Console {
+            main_file: PathBuf::from(main_file),
+            frame: 0,
+            sources: HashMap::new(),
+        }
+    }
 
-    // Parsing
-    let mut parser = Parser::new(tokens);
-    let statements = parser.parse()?;
+    /// Where the selected frame is, with its line of source
+    fn show_location(&mut self, vm: &VM, at_breakpoint: bool) {
+        let Some(frame) = vm.call_stack().into_iter().nth(self.frame) else {
+            return;
+        };
+        let file = frame.file.unwrap_or_default();
+        let why = if at_breakpoint { " (breekpunt)" } else { "" };
+        println!("Gestop in {} by {}:{}{}", frame.function, file, frame.line, why);
 
-    // Compiling
-    let mut compiler = Compiler::new();
-    let (chunk, functions) = compiler.compile(statements)?;
+        let lines = self.sources.entry(file.clone()).or_insert_with(|| {
+            fs::read_to_string(&file).map(|text| text.lines().map(String::from).collect()).unwrap_or_default()
+        });
+        if let Some(text) = frame.line.checked_sub(1).and_then(|index| lines.get(index)) {
+            println!("{:>4} | {}", frame.line, text);
+        }
+    }
 
-    // Executing
-    let mut vm = VM::new(chunk, functions);
+    /// `breek` and `verwyder`: a breakpoint at `[lêer:]lyn`
+    fn breakpoint(&self, vm: &mut VM, argument: &str, add: bool) {
+        let Some(debugger) = vm.debugger() else {
+            return;
+        };
+        if argument.is_empty() && add {
+            for (file, line) in debugger.breakpoints() {
+                println!("  {}:{}", file.display(), line);
+            }
+            return;
+        }
+
+        let (file, line) = match argument.rsplit_once(':') {
+            Some((file, line)) => (PathBuf::from(file), line),
+            None => (self.main_file.clone(), argument),
+        };
+        let Ok(line) = line.parse::<usize>() else {
+            println!("Verwag 'n lynnommer, soos 'breek 12' of 'breek lêer.ark:12'.");
+            return;
+        };
+        if add {
+            debugger.add_breakpoint(&file, line);
+            println!("Breekpunt by {}:{}", file.display(), line);
+        } else if !debugger.remove_breakpoint(&file, line) {
+            println!("Daar is geen breekpunt by {}:{} nie.", file.display(), line);
+        }
+    }
+}
+
+impl DebugHandler for Console {
+    fn paused(&mut self, vm: &mut VM, reason: PauseReason) -> Step {
+        self.frame = 0;
+        self.show_location(vm, reason == PauseReason::Breakpoint);
+
+        loop {
+            print!("(debug) ");
+            io::stdout().flush().unwrap();
+            let mut line = String::new();
+            if matches!(io::stdin().read_line(&mut line), Ok(0) | Err(_)) {
+                // Without more commands, let the program finish
+                if let Some(debugger) = vm.debugger() {
+                    for (file, line) in debugger.breakpoints() {
+                        debugger.remove_breakpoint(&file, line);
+                    }
+                }
+                return Step::Continue;
+            }
+
+            let line = line.trim();
+            let (command, argument) = line.split_once(' ').map_or((line, ""), |(command, argument)| (command, argument.trim()));
+            match command {
+                "" => {}
+                "gaan" | "c" => return Step::Continue,
+                "stap" | "s" => return Step::Into,
+                "volgende" | "n" => return Step::Over,
+                "uit" | "o" => return Step::Out,
+                "breek" | "b" => self.breakpoint(vm, argument, true),
+                "verwyder" | "d" => self.breakpoint(vm, argument, false),
+                "stapel" | "bt" => {
+                    for (index, frame) in vm.call_stack().iter().enumerate() {
+                        let marker = if index == self.frame { '>' } else { ' ' };
+                        println!("{} {:>2} {} ({}:{})", marker, index, frame.function, frame.file.as_deref().unwrap_or("?"), frame.line);
+                    }
+                }
+                "raam" | "f" => match argument.parse::<usize>() {
+                    Ok(index) if index < vm.call_stack().len() => {
+                        self.frame = index;
+                        self.show_location(vm, false);
+                    }
+                    _ => println!("Verwag 'n raam van 0 tot {}.", vm.call_stack().len().saturating_sub(1)),
+                },
+                "lokaal" | "l" => {
+                    for (name, value) in vm.frame_locals(self.frame) {
+                        println!("  {} = {}", name, value);
+                    }
+                    for (name, value) in vm.frame_upvalues(self.frame) {
+                        println!("  {} = {} (vasgevang)", name, value);
+                    }
+                }
+                "globaal" | "g" => {
+                    for (name, value) in vm.frame_globals(self.frame) {
+                        println!("  {} = {}", name, value);
+                    }
+                }
+                "druk" | "p" => match vm.eval_in_frame(self.frame, argument) {
+                    Ok(value) => println!("{}", value),
+                    Err(e) => eprintln!("Fout[{}]: {}", e.code(), e),
+                },
+                "hulp" | "h" => println!("{}", DEBUG_HELP),
+                "verlaat" | "q" => exit(0),
+                _ => println!("Onbekende opdrag '{}'; tik 'hulp' vir die opdragte.", command),
+            }
+        }
+    }
+}
+
+fn report_errors(errors: &[ArkaanError]) {
+    for (i, e) in errors.iter().enumerate() {
+        if i > 0 {
+            eprintln!();
+        }
+        report_error(e);
+    }
+}
+
+fn report_error(e: &ArkaanError) {
+    eprintln!("Fout[{}]: {}", e.code(), e);
+    if !e.trace().is_empty() {
+        eprintln!("\nStapelspoor (binneste oproep eerste):");
+        // Deep recursion repeats the same frame many times; show it once
+        let mut frames = e.trace().iter().peekable();
+        while let Some(frame) = frames.next() {
+            eprintln!("  {}", frame);
+            let mut repeats = 0;
+            while frames.next_if_eq(&frame).is_some() {
+                repeats += 1;
+            }
+            if repeats > 0 {
+                eprintln!("  ... nog {} keer", repeats);
+            }
+        }
+    }
+}
+
+/// 65 for errors in the source, 70 for failures while running
+fn exit_code(errors: &[ArkaanError]) -> i32 {
+    if errors[0].stage() == Stage::Runtime && errors[0].kind != ErrorKind::InvalidBytecode {
+        EXIT_RUNTIME
+    } else {
+        EXIT_COMPILE
+    }
+}
+
+/// A fresh engine to run the file at `path` with the given program arguments
+fn engine_for(path: &str, program_args: Vec<String>) -> Engine {
+    let mut engine = Engine::new();
+    let vm = engine.vm_mut();
+    vm.set_args(program_args);
 
     // Set the current file path for relative imports
     let file_path = PathBuf::from(path);
@@ -68,16 +640,20 @@ fn run_with_path(source: &str, path: &str) -> Result<(), String> {
     } else {
         vm.set_current_file(file_path);
     }
-
-    vm.run()
+    engine
 }
 
 fn repl() {
-    println!("Arkaan v0.1.0 - 'n Afrikaanse programmeertaal");
-    println!("Tik 'verlaat' om te stop.\n");
+    println!("Arkaan v{} - 'n Afrikaanse programmeertaal", VERSION);
+    println!("Tik 'verlaat' om te stop, of 'disasm' om die greepkode van elke invoer te sien.\n");
+
+    // One engine for the whole session so declarations carry over
+    let mut engine = Engine::new();
+    let mut source = String::new();
+    let mut show_bytecode = false;
 
     loop {
-        print!("arkaan> ");
+        print!("{}", if source.is_empty() { "arkaan> " } else { "...     " });
         io::stdout().flush().unwrap();
 
         let mut line = String::new();
@@ -87,36 +663,62 @@ fn repl() {
             Err(_) => break,
         }
 
-        let line = line.trim();
-        if line == "verlaat" {
-            println!("Totsiens!");
-            break;
+        if source.is_empty() {
+            let trimmed = line.trim();
+            if trimmed == "verlaat" {
+                println!("Totsiens!");
+                break;
+            }
+            if trimmed == "disasm" {
+                show_bytecode = !show_bytecode;
+                println!("Greepkode word {}gewys.", if show_bytecode { "" } else { "nie meer " });
+                continue;
+            }
+            if trimmed.is_empty() {
+                continue;
+            }
         }
 
-        if line.is_empty() {
+        source.push_str(&line);
+
+        // Keep reading while a block or list is still open
+        if is_incomplete(&source) {
             continue;
         }
 
-        if let Err(e) = run(line) {
-            eprintln!("Fout: {}", e);
+        match run_repl_entry(&source, &mut engine, show_bytecode) {
+            Ok(Value::Nil) => {}
+            Ok(value) => println!("{}", value),
+            Err(e) => eprintln!("Fout[{}]: {}", e.code(), e),
         }
+        source.clear();
     }
 }
 
-fn run(source: &str) -> Result<(), String> {
-    // Lexing
-    let mut lexer = Lexer::new(source);
-    let tokens = lexer.scan_tokens()?;
-
-    // Parsing
-    let mut parser = Parser::new(tokens);
-    let statements = parser.parse()?;
+fn run_repl_entry(source: &str, engine: &mut Engine, show_bytecode: bool) -> Result<Value, ArkaanError> {
+    let compiled = engine.compile_entry("<repl>", source)?;
+    if show_bytecode {
+        print!("{}", disassemble("<repl>", &compiled.chunk, &compiled.globals));
+    }
+    engine.execute(compiled)
+}
 
-    // Compiling
-    let mut compiler = Compiler::new();
-    let (chunk, functions) = compiler.compile(statements)?;
+/// Returns true if the input has more opening than closing braces. Only
+/// blocks can span lines, so an open parenthesis or bracket is left for the
+/// parser to report. Input that fails to lex is considered complete so the
+/// error gets reported.
+fn is_incomplete(source: &str) -> bool {
+    let Ok(tokens) = Lexer::new(source).scan_tokens() else {
+        return false;
+    };
 
-    // Executing
-    let mut vm = VM::new(chunk, functions);
-    vm.run()
+    let mut depth = 0i32;
+    for token in &tokens {
+        match token.token_type {
+            TokenType::LeftBrace => depth += 1,
+            TokenType::RightBrace => depth -= 1,
+            _ => {}
+        }
+    }
+    depth > 0
 }
@@ -1,30 +1,103 @@
-use crate::ast::{Expr, LambdaBody, Literal, MatchArm, Pattern, Stmt, TypeConstructor};
+use crate::ast::{Expr, ExprKind, Ident, LambdaBody, Literal, MatchArm, Pattern, Stmt, StmtKind, TypeConstructor};
+use crate::error::{ArkaanError, ErrorKind};
+use crate::span::Span;
 use crate::token::{Token, TokenType};
 
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    errors: Vec<ArkaanError>,  // Syntax errors recovered from so far
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Parser { tokens, current: 0 }
+        Parser {
+            tokens,
+            current: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Parses the program, stopping at the first syntax error
+    pub fn parse(&mut self) -> Result<Vec<Stmt>, ArkaanError> {
+        let (statements, mut errors) = self.parse_with_recovery();
+        if errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(errors.remove(0))
+        }
     }
 
-    pub fn parse(&mut self) -> Result<Vec<Stmt>, String> {
+    /// Parses the program, skipping past syntax errors so that all of them are
+    /// reported. Returns the statements that did parse along with the errors.
+    pub fn parse_with_recovery(&mut self) -> (Vec<Stmt>, Vec<ArkaanError>) {
         let mut statements = Vec::new();
 
         while !self.is_at_end() {
             self.skip_newlines();
-            if !self.is_at_end() {
-                statements.push(self.declaration()?);
+            if self.is_at_end() {
+                break;
+            }
+
+            let start = self.current;
+            match self.declaration() {
+                Ok(stmt) => statements.push(stmt),
+                Err(error) => {
+                    self.errors.push(error);
+                    self.synchronize(start);
+
+                    // A stray '}' stops synchronizing without being consumed
+                    if self.current == start {
+                        self.advance();
+                    }
+                }
             }
         }
 
-        Ok(statements)
+        (statements, std::mem::take(&mut self.errors))
+    }
+
+    /// Skips the rest of a broken statement that began at token `start`: up
+    /// to and including the next newline, or up to a '}' or a keyword that
+    /// starts a new statement. Braces opened since `start` are skipped to
+    /// their '}' first, so that one does not end the enclosing block.
+    fn synchronize(&mut self, start: usize) {
+        let mut depth = self.tokens[start..self.current].iter().fold(0usize, |depth, token| match token.token_type {
+            TokenType::LeftBrace => depth + 1,
+            TokenType::RightBrace => depth.saturating_sub(1),
+            _ => depth,
+        });
+
+        while !self.is_at_end() {
+            if depth == 0 {
+                match self.peek().token_type {
+                    TokenType::Newline => {
+                        self.advance();
+                        return;
+                    }
+                    TokenType::RightBrace
+                    | TokenType::Laat
+                    | TokenType::Stel
+                    | TokenType::Funksie
+                    | TokenType::As
+                    | TokenType::Terwyl
+                    | TokenType::Druk
+                    | TokenType::Gee
+                    | TokenType::Tipe
+                    | TokenType::Laai
+                    | TokenType::Verskaf => return,
+                    _ => {}
+                }
+            }
+            match self.advance().token_type {
+                TokenType::LeftBrace => depth += 1,
+                TokenType::RightBrace => depth -= 1,
+                _ => {}
+            }
+        }
     }
 
-    fn declaration(&mut self) -> Result<Stmt, String> {
+    fn declaration(&mut self) -> Result<Stmt, ArkaanError> {
         if self.check(&TokenType::Laai) {
             self.advance();
             self.import_declaration()
@@ -33,61 +106,72 @@ impl Parser {
             self.export_declaration()
         } else if self.check(&TokenType::Laat) {
             self.advance();
-            self.var_declaration()
+            self.var_declaration(false)
+        } else if self.check(&TokenType::Stel) {
+            self.advance();
+            self.var_declaration(true)
         } else if self.check(&TokenType::Tipe) {
             self.advance();
             self.type_declaration()
+        } else if self.check(&TokenType::Funksie) {
+            self.advance();
+            self.function_declaration()
         } else {
             self.statement()
         }
     }
 
-    fn import_declaration(&mut self) -> Result<Stmt, String> {
+    fn import_declaration(&mut self) -> Result<Stmt, ArkaanError> {
         // laai "path" as name
+        let start = self.previous().span;
         let path = if let TokenType::Str(s) = &self.peek().token_type {
             let path = s.clone();
             self.advance();
             path
         } else {
-            return Err(format!(
-                "Verwag string lêerpad na 'laai'. (lyn {})",
-                self.peek().line
-            ));
+            return Err(self.error("Verwag string lêerpad na 'laai'."));
         };
 
         // Expect "as" keyword (As is the token for "if" in Arkaan, reusing it here for import alias)
         if self.check(&TokenType::As) {
             self.advance();
         } else {
-            return Err(format!(
-                "Verwag 'as' na lêerpad. (lyn {})",
-                self.peek().line
-            ));
+            return Err(self.error("Verwag 'as' na lêerpad."));
         }
 
         let alias = self.consume_identifier("Verwag module naam na 'as'.")?;
+        let span = self.span_from(start);
         self.consume_newline_or_eof()?;
 
-        Ok(Stmt::Import { path, alias })
+        Ok(Stmt::new(StmtKind::Import { path, alias }, span))
     }
 
-    fn export_declaration(&mut self) -> Result<Stmt, String> {
-        // verskaf laat name = ...
+    fn export_declaration(&mut self) -> Result<Stmt, ArkaanError> {
+        // verskaf laat name = ... | verskaf funksie name(params) { ... }
+        let start = self.previous().span;
         if self.check(&TokenType::Laat) {
             self.advance();
-            let Stmt::VarDecl { name, initializer } = self.var_declaration()? else {
+            let declaration = self.var_declaration(false)?;
+            let StmtKind::VarDecl { name, initializer, .. } = declaration.kind else {
+                unreachable!()
+            };
+            let span = start.to(declaration.span);
+            Ok(Stmt::new(StmtKind::ExportVarDecl { name, initializer }, span))
+        } else if self.check(&TokenType::Funksie) {
+            self.advance();
+            let declaration = self.function_declaration()?;
+            let StmtKind::FunctionDecl { name, params, body } = declaration.kind else {
                 unreachable!()
             };
-            Ok(Stmt::ExportVarDecl { name, initializer })
+            let span = start.to(declaration.span);
+            Ok(Stmt::new(StmtKind::ExportFunctionDecl { name, params, body }, span))
         } else {
-            Err(format!(
-                "Verwag 'laat' na 'verskaf'. (lyn {})",
-                self.peek().line
-            ))
+            Err(self.error("Verwag 'laat' of 'funksie' na 'verskaf'."))
         }
     }
 
-    fn type_declaration(&mut self) -> Result<Stmt, String> {
+    fn type_declaration(&mut self) -> Result<Stmt, ArkaanError> {
+        let start = self.previous().span;
         let name = self.consume_identifier("Verwag tipe naam.")?;
         self.skip_newlines();
         self.consume(&TokenType::LeftBrace, "Verwag '{' na tipe naam.")?;
@@ -102,18 +186,20 @@ impl Parser {
         }
 
         self.consume(&TokenType::RightBrace, "Verwag '}' na tipe definisie.")?;
+        let span = self.span_from(start);
 
         if constructors.is_empty() {
-            return Err(format!(
-                "Tipe '{}' moet ten minste een konstruktor hê.",
-                name
+            return Err(ArkaanError::at(
+                ErrorKind::EmptyType,
+                format!("Tipe '{}' moet ten minste een konstruktor hê.", name.name),
+                span,
             ));
         }
 
-        Ok(Stmt::TypeDecl { name, constructors })
+        Ok(Stmt::new(StmtKind::TypeDecl { name, constructors }, span))
     }
 
-    fn parse_constructor(&mut self) -> Result<TypeConstructor, String> {
+    fn parse_constructor(&mut self) -> Result<TypeConstructor, ArkaanError> {
         let name = self.consume_identifier("Verwag konstruktor naam.")?;
 
         let fields = if self.check(&TokenType::LeftParen) {
@@ -122,7 +208,7 @@ impl Parser {
 
             if !self.check(&TokenType::RightParen) {
                 loop {
-                    fields.push(self.consume_identifier("Verwag veld naam.")?);
+                    fields.push(self.consume_identifier("Verwag veld naam.")?.name);
                     if !self.check(&TokenType::Comma) {
                         break;
                     }
@@ -136,18 +222,43 @@ impl Parser {
             Vec::new()
         };
 
-        Ok(TypeConstructor { name, fields })
+        let span = name.span.to(self.previous().span);
+        Ok(TypeConstructor { name, fields, span })
     }
 
-    fn var_declaration(&mut self) -> Result<Stmt, String> {
-        let name = self.consume_identifier("Verwag konstante naam.")?;
-        self.consume(&TokenType::Equal, "Verwag '=' na konstante naam.")?;
+    fn var_declaration(&mut self, mutable: bool) -> Result<Stmt, ArkaanError> {
+        let start = self.previous().span;
+        let kind = if mutable { "veranderlike" } else { "konstante" };
+        let name = self.consume_identifier(&format!("Verwag {} naam.", kind))?;
+        self.consume(&TokenType::Equal, &format!("Verwag '=' na {} naam.", kind))?;
         let initializer = self.expression()?;
+        let span = self.span_from(start);
         self.consume_newline_or_eof()?;
-        Ok(Stmt::VarDecl { name, initializer })
+        Ok(Stmt::new(
+            StmtKind::VarDecl {
+                name,
+                initializer,
+                mutable,
+            },
+            span,
+        ))
+    }
+
+    fn function_declaration(&mut self) -> Result<Stmt, ArkaanError> {
+        // funksie name(params) { stmts }
+        let start = self.previous().span;
+        let name = self.consume_identifier("Verwag funksie naam.")?;
+        self.consume(&TokenType::LeftParen, "Verwag '(' na funksie naam.")?;
+        let params = self.parameters()?;
+        self.skip_newlines();
+
+        self.consume(&TokenType::LeftBrace, "Verwag '{' voor funksie liggaam.")?;
+        let body = self.block()?;
+
+        Ok(Stmt::new(StmtKind::FunctionDecl { name, params, body }, self.span_from(start)))
     }
 
-    fn statement(&mut self) -> Result<Stmt, String> {
+    fn statement(&mut self) -> Result<Stmt, ArkaanError> {
         if self.check(&TokenType::Druk) {
             self.advance();
             self.print_statement()
@@ -161,80 +272,106 @@ impl Parser {
             self.advance();
             self.while_statement()
         } else if self.check(&TokenType::LeftBrace) {
-            self.advance();
-            Ok(Stmt::Block(self.block()?))
+            let start = self.advance().span;
+            let statements = self.block()?;
+            Ok(Stmt::new(StmtKind::Block(statements), self.span_from(start)))
         } else {
             self.expression_statement()
         }
     }
 
-    fn return_statement(&mut self) -> Result<Stmt, String> {
+    fn return_statement(&mut self) -> Result<Stmt, ArkaanError> {
+        let start = self.previous().span;
         let value = if self.check(&TokenType::Newline) || self.is_at_end() || self.check(&TokenType::RightBrace) {
             None
         } else {
             Some(self.expression()?)
         };
 
+        let span = self.span_from(start);
         self.consume_newline_or_eof()?;
-        Ok(Stmt::Return { value })
+        Ok(Stmt::new(StmtKind::Return { value }, span))
     }
 
-    fn print_statement(&mut self) -> Result<Stmt, String> {
+    fn print_statement(&mut self) -> Result<Stmt, ArkaanError> {
+        let start = self.previous().span;
         self.consume(&TokenType::LeftParen, "Verwag '(' na 'druk'.")?;
         let value = self.expression()?;
         self.consume(&TokenType::RightParen, "Verwag ')' na uitdrukking.")?;
+        let span = self.span_from(start);
         self.consume_newline_or_eof()?;
-        Ok(Stmt::Print(value))
+        Ok(Stmt::new(StmtKind::Print(value), span))
     }
 
-    fn if_statement(&mut self) -> Result<Stmt, String> {
+    fn if_statement(&mut self) -> Result<Stmt, ArkaanError> {
+        let start = self.previous().span;
         if self.check(&TokenType::LeftParen) {
-            return Err("Moenie hakies gebruik na 'as' nie. Skryf: as voorwaarde { ... }".to_string());
+            return Err(self
+                .error("Moenie hakies gebruik na 'as' nie.")
+                .with_help("Skryf: as voorwaarde { ... }"));
         }
         let condition = self.expression()?;
         self.skip_newlines();
 
-        self.consume(&TokenType::LeftBrace, "Verwag '{' na 'as' voorwaarde.")?;
-        let then_branch = Stmt::Block(self.block()?);
+        let then_start = self.consume(&TokenType::LeftBrace, "Verwag '{' na 'as' voorwaarde.")?.span;
+        let then_branch = Stmt::new(StmtKind::Block(self.block()?), self.span_from(then_start));
+        let mut span = self.span_from(start);
         self.skip_newlines();
 
         let else_branch = if self.check(&TokenType::Anders) {
             self.advance();
             self.skip_newlines();
-            self.consume(&TokenType::LeftBrace, "Verwag '{' na 'anders'.")?;
-            Some(Box::new(Stmt::Block(self.block()?)))
+            let else_start = self.consume(&TokenType::LeftBrace, "Verwag '{' na 'anders'.")?.span;
+            let else_block = Stmt::new(StmtKind::Block(self.block()?), self.span_from(else_start));
+            span = self.span_from(start);
+            Some(Box::new(else_block))
         } else {
             None
         };
 
-        Ok(Stmt::If {
-            condition,
-            then_branch: Box::new(then_branch),
-            else_branch,
-        })
+        Ok(Stmt::new(
+            StmtKind::If {
+                condition,
+                then_branch: Box::new(then_branch),
+                else_branch,
+            },
+            span,
+        ))
     }
 
-    fn while_statement(&mut self) -> Result<Stmt, String> {
+    fn while_statement(&mut self) -> Result<Stmt, ArkaanError> {
+        let start = self.previous().span;
         self.consume(&TokenType::LeftParen, "Verwag '(' na 'terwyl'.")?;
         let condition = self.expression()?;
         self.consume(&TokenType::RightParen, "Verwag ')' na voorwaarde.")?;
         self.skip_newlines();
 
-        self.consume(&TokenType::LeftBrace, "Verwag '{' na 'terwyl' voorwaarde.")?;
-        let body = Stmt::Block(self.block()?);
+        let body_start = self.consume(&TokenType::LeftBrace, "Verwag '{' na 'terwyl' voorwaarde.")?.span;
+        let body = Stmt::new(StmtKind::Block(self.block()?), self.span_from(body_start));
 
-        Ok(Stmt::While {
-            condition,
-            body: Box::new(body),
-        })
+        Ok(Stmt::new(
+            StmtKind::While {
+                condition,
+                body: Box::new(body),
+            },
+            self.span_from(start),
+        ))
     }
 
-    fn block(&mut self) -> Result<Vec<Stmt>, String> {
+    fn block(&mut self) -> Result<Vec<Stmt>, ArkaanError> {
         let mut statements = Vec::new();
 
         self.skip_newlines();
         while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
-            statements.push(self.declaration()?);
+            let start = self.current;
+            match self.declaration() {
+                Ok(stmt) => statements.push(stmt),
+                Err(error) => {
+                    // Keep parsing the rest of the block
+                    self.errors.push(error);
+                    self.synchronize(start);
+                }
+            }
             self.skip_newlines();
         }
 
@@ -242,95 +379,111 @@ impl Parser {
         Ok(statements)
     }
 
-    fn expression_statement(&mut self) -> Result<Stmt, String> {
+    fn expression_statement(&mut self) -> Result<Stmt, ArkaanError> {
         let expr = self.expression()?;
+        let span = expr.span;
         self.consume_newline_or_eof()?;
-        Ok(Stmt::Expression(expr))
+        Ok(Stmt::new(StmtKind::Expression(expr), span))
     }
 
-    fn expression(&mut self) -> Result<Expr, String> {
-        self.pipe()
+    fn expression(&mut self) -> Result<Expr, ArkaanError> {
+        self.assignment()
     }
 
-    fn pipe(&mut self) -> Result<Expr, String> {
+    fn assignment(&mut self) -> Result<Expr, ArkaanError> {
+        let expr = self.pipe()?;
+
+        if self.check(&TokenType::Equal) {
+            let equals = self.advance().span;
+            // Right-associative: a = b = c assigns c to both
+            let value = self.assignment()?;
+            let span = expr.span.to(value.span);
+
+            return match expr.kind {
+                ExprKind::Variable(name) => Ok(Expr::new(
+                    ExprKind::Assign {
+                        name: Ident::new(name, expr.span),
+                        value: Box::new(value),
+                    },
+                    span,
+                )),
+                _ => Err(ArkaanError::at(ErrorKind::InvalidAssignmentTarget, "Ongeldige toewysingsteiken.", expr.span.to(equals))),
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn pipe(&mut self) -> Result<Expr, ArkaanError> {
         let mut expr = self.or()?;
 
         while self.check(&TokenType::Arrow) {
             self.advance();
             let right = self.or()?;
+            let span = expr.span.to(right.span);
 
-            expr = match right {
-                Expr::Call { callee, mut arguments } => {
+            let kind = match right.kind {
+                ExprKind::Call { callee, mut arguments } => {
                     arguments.insert(0, expr);
-                    Expr::Call { callee, arguments }
+                    ExprKind::Call { callee, arguments }
                 }
-                Expr::Variable(_) | Expr::MemberAccess { .. } |
-                Expr::Lambda { .. } | Expr::Grouping(_) => {
-                    Expr::Call {
+                ExprKind::Variable(_) | ExprKind::MemberAccess { .. } |
+                ExprKind::Lambda { .. } | ExprKind::Grouping(_) => {
+                    ExprKind::Call {
                         callee: Box::new(right),
                         arguments: vec![expr],
                     }
                 }
                 _ => {
-                    return Err(format!(
-                        "Die regterkant van '->' moet 'n funksie of funksie-oproep wees. (lyn {})",
-                        self.peek().line
+                    return Err(ArkaanError::at(
+                        ErrorKind::InvalidPipeTarget,
+                        "Die regterkant van '->' moet 'n funksie of funksie-oproep wees.",
+                        right.span,
                     ));
                 }
             };
+            expr = Expr::new(kind, span);
         }
         Ok(expr)
     }
 
-    fn or(&mut self) -> Result<Expr, String> {
+    fn or(&mut self) -> Result<Expr, ArkaanError> {
         let mut expr = self.and()?;
 
         while self.check(&TokenType::Or) {
             let operator = self.advance().clone();
             let right = self.and()?;
-            expr = Expr::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+            expr = Self::binary(expr, operator, right);
         }
 
         Ok(expr)
     }
 
-    fn and(&mut self) -> Result<Expr, String> {
+    fn and(&mut self) -> Result<Expr, ArkaanError> {
         let mut expr = self.equality()?;
 
         while self.check(&TokenType::And) {
             let operator = self.advance().clone();
             let right = self.equality()?;
-            expr = Expr::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+            expr = Self::binary(expr, operator, right);
         }
 
         Ok(expr)
     }
 
-    fn equality(&mut self) -> Result<Expr, String> {
+    fn equality(&mut self) -> Result<Expr, ArkaanError> {
         let mut expr = self.comparison()?;
 
         while self.check(&TokenType::EqualEqual) || self.check(&TokenType::BangEqual) {
             let operator = self.advance().clone();
             let right = self.comparison()?;
-            expr = Expr::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+            expr = Self::binary(expr, operator, right);
         }
 
         Ok(expr)
     }
 
-    fn comparison(&mut self) -> Result<Expr, String> {
+    fn comparison(&mut self) -> Result<Expr, ArkaanError> {
         let mut expr = self.term()?;
 
         while self.check(&TokenType::Less)
@@ -340,33 +493,25 @@ impl Parser {
         {
             let operator = self.advance().clone();
             let right = self.term()?;
-            expr = Expr::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+            expr = Self::binary(expr, operator, right);
         }
 
         Ok(expr)
     }
 
-    fn term(&mut self) -> Result<Expr, String> {
+    fn term(&mut self) -> Result<Expr, ArkaanError> {
         let mut expr = self.factor()?;
 
         while self.check(&TokenType::Plus) || self.check(&TokenType::Minus) {
             let operator = self.advance().clone();
             let right = self.factor()?;
-            expr = Expr::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+            expr = Self::binary(expr, operator, right);
         }
 
         Ok(expr)
     }
 
-    fn factor(&mut self) -> Result<Expr, String> {
+    fn factor(&mut self) -> Result<Expr, ArkaanError> {
         let mut expr = self.unary()?;
 
         while self.check(&TokenType::Star)
@@ -375,30 +520,42 @@ impl Parser {
         {
             let operator = self.advance().clone();
             let right = self.unary()?;
-            expr = Expr::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+            expr = Self::binary(expr, operator, right);
         }
 
         Ok(expr)
     }
 
-    fn unary(&mut self) -> Result<Expr, String> {
+    fn binary(left: Expr, operator: Token, right: Expr) -> Expr {
+        let span = left.span.to(right.span);
+        Expr::new(
+            ExprKind::Binary {
+                left: Box::new(left),
+                operator,
+                right: Box::new(right),
+            },
+            span,
+        )
+    }
+
+    fn unary(&mut self) -> Result<Expr, ArkaanError> {
         if self.check(&TokenType::Bang) || self.check(&TokenType::Minus) {
             let operator = self.advance().clone();
             let right = self.unary()?;
-            return Ok(Expr::Unary {
-                operator,
-                right: Box::new(right),
-            });
+            let span = operator.span.to(right.span);
+            return Ok(Expr::new(
+                ExprKind::Unary {
+                    operator,
+                    right: Box::new(right),
+                },
+                span,
+            ));
         }
 
         self.call()
     }
 
-    fn call(&mut self) -> Result<Expr, String> {
+    fn call(&mut self) -> Result<Expr, ArkaanError> {
         let mut expr = self.primary()?;
 
         loop {
@@ -409,17 +566,25 @@ impl Parser {
                 self.advance();
                 let index = self.expression()?;
                 self.consume(&TokenType::RightBracket, "Verwag ']' na indeks.")?;
-                expr = Expr::Index {
-                    object: Box::new(expr),
-                    index: Box::new(index),
-                };
+                let span = self.span_from(expr.span);
+                expr = Expr::new(
+                    ExprKind::Index {
+                        object: Box::new(expr),
+                        index: Box::new(index),
+                    },
+                    span,
+                );
             } else if self.check(&TokenType::Dot) {
                 self.advance();
                 let member = self.consume_identifier("Verwag lid naam na '.'")?;
-                expr = Expr::MemberAccess {
-                    object: Box::new(expr),
-                    member,
-                };
+                let span = self.span_from(expr.span);
+                expr = Expr::new(
+                    ExprKind::MemberAccess {
+                        object: Box::new(expr),
+                        member,
+                    },
+                    span,
+                );
             } else {
                 break;
             }
@@ -428,15 +593,16 @@ impl Parser {
         Ok(expr)
     }
 
-    fn finish_call(&mut self, callee: Expr) -> Result<Expr, String> {
+    fn finish_call(&mut self, callee: Expr) -> Result<Expr, ArkaanError> {
         let mut arguments = Vec::new();
 
         if !self.check(&TokenType::RightParen) {
             loop {
                 if arguments.len() >= 255 {
-                    return Err(format!(
-                        "Kan nie meer as 255 argumente hê nie. (lyn {})",
-                        self.peek().line
+                    return Err(ArkaanError::at(
+                        ErrorKind::TooManyArguments,
+                        "Kan nie meer as 255 argumente hê nie.",
+                        self.peek().span,
                     ));
                 }
                 arguments.push(self.expression()?);
@@ -449,47 +615,53 @@ impl Parser {
         }
 
         self.consume(&TokenType::RightParen, "Verwag ')' na argumente.")?;
-
-        Ok(Expr::Call {
-            callee: Box::new(callee),
-            arguments,
-        })
+        let span = self.span_from(callee.span);
+
+        Ok(Expr::new(
+            ExprKind::Call {
+                callee: Box::new(callee),
+                arguments,
+            },
+            span,
+        ))
     }
 
-    fn primary(&mut self) -> Result<Expr, String> {
+    fn primary(&mut self) -> Result<Expr, ArkaanError> {
+        let start = self.peek().span;
+
         if self.check(&TokenType::Waar) {
             self.advance();
-            return Ok(Expr::Literal(Literal::Boolean(true)));
+            return Ok(Expr::new(ExprKind::Literal(Literal::Boolean(true)), start));
         }
 
         if self.check(&TokenType::Vals) {
             self.advance();
-            return Ok(Expr::Literal(Literal::Boolean(false)));
+            return Ok(Expr::new(ExprKind::Literal(Literal::Boolean(false)), start));
         }
 
         if let TokenType::Number(n) = &self.peek().token_type {
             let value = *n;
             self.advance();
-            return Ok(Expr::Literal(Literal::Number(value)));
+            return Ok(Expr::new(ExprKind::Literal(Literal::Number(value)), start));
         }
 
         if let TokenType::Str(s) = &self.peek().token_type {
             let value = s.clone();
             self.advance();
-            return Ok(Expr::Literal(Literal::String(value)));
+            return Ok(Expr::new(ExprKind::Literal(Literal::String(value)), start));
         }
 
         if let TokenType::Identifier(name) = &self.peek().token_type {
             let name = name.clone();
             self.advance();
-            return Ok(Expr::Variable(name));
+            return Ok(Expr::new(ExprKind::Variable(name), start));
         }
 
         if self.check(&TokenType::LeftParen) {
             self.advance();
             let expr = self.expression()?;
             self.consume(&TokenType::RightParen, "Verwag ')' na uitdrukking.")?;
-            return Ok(Expr::Grouping(Box::new(expr)));
+            return Ok(Expr::new(ExprKind::Grouping(Box::new(expr)), self.span_from(start)));
         }
 
         // List literal: [a, b, c]
@@ -508,7 +680,7 @@ impl Parser {
             }
 
             self.consume(&TokenType::RightBracket, "Verwag ']' na lys elemente.")?;
-            return Ok(Expr::List(elements));
+            return Ok(Expr::new(ExprKind::List(elements), self.span_from(start)));
         }
 
         // Lambda expression: fn(params) expr or fn(params) { stmts }
@@ -529,13 +701,14 @@ impl Parser {
             return self.if_expr();
         }
 
-        Err(format!(
-            "Verwag uitdrukking op lyn {}.",
-            self.peek().line
-        ))
+        Err(self.error(format!(
+            "Verwag uitdrukking, maar het {} gekry.",
+            Self::describe(self.peek())
+        )))
     }
 
-    fn match_expr(&mut self) -> Result<Expr, String> {
+    fn match_expr(&mut self) -> Result<Expr, ArkaanError> {
+        let start = self.previous().span;
         self.consume(&TokenType::LeftParen, "Verwag '(' na 'pas'.")?;
         let value = self.expression()?;
         self.consume(&TokenType::RightParen, "Verwag ')' na waarde.")?;
@@ -559,20 +732,27 @@ impl Parser {
         }
 
         self.consume(&TokenType::RightBrace, "Verwag '}' na pas-gevalle.")?;
+        let span = self.span_from(start);
 
         if arms.is_empty() {
-            return Err("Pas-uitdrukking moet ten minste een geval hê.".to_string());
+            return Err(ArkaanError::at(ErrorKind::EmptyMatch, "Pas-uitdrukking moet ten minste een geval hê.", span));
         }
 
-        Ok(Expr::Match {
-            value: Box::new(value),
-            arms,
-        })
+        Ok(Expr::new(
+            ExprKind::Match {
+                value: Box::new(value),
+                arms,
+            },
+            span,
+        ))
     }
 
-    fn if_expr(&mut self) -> Result<Expr, String> {
+    fn if_expr(&mut self) -> Result<Expr, ArkaanError> {
+        let start = self.previous().span;
         if self.check(&TokenType::LeftParen) {
-            return Err("Moenie hakies gebruik na 'as' nie.".to_string());
+            return Err(self
+                .error("Moenie hakies gebruik na 'as' nie.")
+                .with_help("Skryf: as voorwaarde waarde anders waarde"));
         }
         let condition = self.or()?; // Use or() to stop before 'anders'
 
@@ -581,15 +761,19 @@ impl Parser {
         self.consume(&TokenType::Anders, "Verwag 'anders' in as-uitdrukking.")?;
 
         let else_branch = self.expression()?;
-
-        Ok(Expr::IfExpr {
-            condition: Box::new(condition),
-            then_branch: Box::new(then_branch),
-            else_branch: Box::new(else_branch),
-        })
+        let span = start.to(else_branch.span);
+
+        Ok(Expr::new(
+            ExprKind::If {
+                condition: Box::new(condition),
+                then_branch: Box::new(then_branch),
+                else_branch: Box::new(else_branch),
+            },
+            span,
+        ))
     }
 
-    fn parse_pattern(&mut self) -> Result<Pattern, String> {
+    fn parse_pattern(&mut self) -> Result<Pattern, ArkaanError> {
         // Wildcard: _
         if self.check(&TokenType::Underscore) {
             self.advance();
@@ -623,7 +807,7 @@ impl Parser {
 
         // Identifier - could be a variable binding or a constructor
         if let TokenType::Identifier(name) = &self.peek().token_type {
-            let name = name.clone();
+            let name = Ident::new(name.clone(), self.peek().span);
             self.advance();
 
             // Check if it's a constructor (followed by parens)
@@ -647,7 +831,7 @@ impl Parser {
 
             // Check if it's a unit constructor (uppercase first letter convention)
             // For now, we treat any identifier that starts with uppercase as a constructor
-            if name.chars().next().map(|c| c.is_uppercase()).unwrap_or(false) {
+            if name.name.chars().next().map(|c| c.is_uppercase()).unwrap_or(false) {
                 // Could be a unit constructor - we'll treat it as a constructor with no fields
                 return Ok(Pattern::Constructor {
                     name,
@@ -659,22 +843,38 @@ impl Parser {
             return Ok(Pattern::Variable(name));
         }
 
-        Err(format!(
-            "Verwag patroon op lyn {}.",
-            self.peek().line
-        ))
+        Err(self.error("Verwag patroon."))
     }
 
-    fn lambda(&mut self) -> Result<Expr, String> {
+    fn lambda(&mut self) -> Result<Expr, ArkaanError> {
+        let start = self.previous().span;
         self.consume(&TokenType::LeftParen, "Verwag '(' na 'fn'.")?;
+        let params = self.parameters()?;
+
+        // Check if body is a block or an expression
+        let body = if self.check(&TokenType::LeftBrace) {
+            self.advance();
+            let stmts = self.block()?;
+            LambdaBody::Block(stmts)
+        } else {
+            let expr = self.expression()?;
+            LambdaBody::Expr(Box::new(expr))
+        };
+
+        Ok(Expr::new(ExprKind::Lambda { params, body }, self.span_from(start)))
+    }
+
+    /// Parses a parameter list after the opening '(' up to and including ')'
+    fn parameters(&mut self) -> Result<Vec<Ident>, ArkaanError> {
         let mut params = Vec::new();
 
         if !self.check(&TokenType::RightParen) {
             loop {
                 if params.len() >= 255 {
-                    return Err(format!(
-                        "Kan nie meer as 255 parameters hê nie. (lyn {})",
-                        self.peek().line
+                    return Err(ArkaanError::at(
+                        ErrorKind::TooManyArguments,
+                        "Kan nie meer as 255 parameters hê nie.",
+                        self.peek().span,
                     ));
                 }
                 params.push(self.consume_identifier("Verwag parameter naam.")?);
@@ -687,26 +887,38 @@ impl Parser {
         }
 
         self.consume(&TokenType::RightParen, "Verwag ')' na parameters.")?;
+        Ok(params)
+    }
 
-        // Check if body is a block or an expression
-        let body = if self.check(&TokenType::LeftBrace) {
-            self.advance();
-            let stmts = self.block()?;
-            LambdaBody::Block(stmts)
-        } else {
-            let expr = self.expression()?;
-            LambdaBody::Expr(Box::new(expr))
-        };
+    // Helper methods
+
+    /// Human-readable description of a token for error messages
+    fn describe(token: &Token) -> String {
+        match token.token_type {
+            TokenType::Newline => String::from("'n nuwe lyn"),
+            TokenType::Eof => String::from("die einde van die lêer"),
+            _ => format!("'{}'", token.lexeme),
+        }
+    }
 
-        Ok(Expr::Lambda { params, body })
+    /// An unexpected-token error at the current token
+    fn error(&self, message: impl Into<String>) -> ArkaanError {
+        ArkaanError::at(ErrorKind::UnexpectedToken, message, self.peek().span)
     }
 
-    // Helper methods
+    /// Span from `start` to the end of the most recently consumed token
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.previous().span)
+    }
 
     fn peek(&self) -> &Token {
         &self.tokens[self.current]
     }
 
+    fn previous(&self) -> &Token {
+        &self.tokens[self.current.saturating_sub(1)]
+    }
+
     fn is_at_end(&self) -> bool {
         matches!(self.peek().token_type, TokenType::Eof)
     }
@@ -725,35 +937,32 @@ impl Parser {
         std::mem::discriminant(&self.peek().token_type) == std::mem::discriminant(token_type)
     }
 
-    fn consume(&mut self, token_type: &TokenType, message: &str) -> Result<&Token, String> {
+    fn consume(&mut self, token_type: &TokenType, message: &str) -> Result<&Token, ArkaanError> {
         if self.check(token_type) {
             Ok(self.advance())
         } else {
-            Err(format!("{} (lyn {})", message, self.peek().line))
+            Err(self.error(message))
         }
     }
 
-    fn consume_identifier(&mut self, message: &str) -> Result<String, String> {
+    fn consume_identifier(&mut self, message: &str) -> Result<Ident, ArkaanError> {
         if let TokenType::Identifier(name) = &self.peek().token_type {
-            let name = name.clone();
+            let ident = Ident::new(name.clone(), self.peek().span);
             self.advance();
-            Ok(name)
+            Ok(ident)
         } else {
-            Err(format!("{} (lyn {})", message, self.peek().line))
+            Err(self.error(message))
         }
     }
 
-    fn consume_newline_or_eof(&mut self) -> Result<(), String> {
+    fn consume_newline_or_eof(&mut self) -> Result<(), ArkaanError> {
         if self.check(&TokenType::Newline) {
             self.advance();
             Ok(())
         } else if self.is_at_end() || self.check(&TokenType::RightBrace) {
             Ok(())
         } else {
-            Err(format!(
-                "Verwag nuwe lyn na stelling. (lyn {})",
-                self.peek().line
-            ))
+            Err(self.error("Verwag nuwe lyn na stelling."))
         }
     }
 
@@ -0,0 +1,215 @@
+//! Limits for running programs that are not trusted: how deep calls may nest,
+//! how many instructions and how much time one run may take, how long any one
+//! of its lists and strings may grow and which modules it may load. Each limit
+//! stops the program with an error code of its own.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::error::{ArkaanError, ErrorKind};
+use crate::vm::{DEFAULT_MAX_CALLBACK_DEPTH, DEFAULT_MAX_CALL_DEPTH};
+
+/// Instructions between two looks at the clock when a run has a timeout
+const CLOCK_INTERVAL: u64 = 10_000;
+
+/// What a program may use. The default only limits call depth.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Limits {
+    pub max_call_depth: usize,
+    pub max_callback_depth: usize,    // Natives like `kaart` calling back into Arkaan, or modules loading modules, nested in each other
+    pub fuel: Option<u64>,            // Instructions one run may execute; making a list or string also uses one per element
+    pub timeout: Option<Duration>,    // Wall-clock time one run may take
+    pub max_len: Option<usize>,       // Most elements any one list, or bytes any one string, may have; only fuel bounds the total
+    pub modules: ModuleAccess,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            max_callback_depth: DEFAULT_MAX_CALLBACK_DEPTH,
+            fuel: None,
+            timeout: None,
+            max_len: None,
+            modules: ModuleAccess::Any,
+        }
+    }
+}
+
+/// Which files `laai` may load
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ModuleAccess {
+    #[default]
+    Any,
+    None,
+    Within(Vec<PathBuf>),  // Only files inside these directories
+}
+
+impl ModuleAccess {
+    /// Refuses a module outside what is allowed; `path` is the one the program wrote
+    pub(crate) fn check(&self, resolved: &Path, path: &str) -> Result<(), ArkaanError> {
+        let note = match self {
+            ModuleAccess::Any => return Ok(()),
+            ModuleAccess::None => String::from("Hierdie program mag geen modules laai nie."),
+            ModuleAccess::Within(dirs) => {
+                // Canonical paths, so neither `..` nor a symlink leads outside
+                let inside = resolved.canonicalize().is_ok_and(|file| {
+                    dirs.iter().filter_map(|dir| dir.canonicalize().ok()).any(|dir| file.starts_with(dir))
+                });
+                if inside {
+                    return Ok(());
+                }
+                let dirs: Vec<String> = dirs.iter().map(|dir| dir.display().to_string()).collect();
+                format!("Modules mag net uit {} gelaai word.", dirs.join(", "))
+            }
+        };
+        Err(ArkaanError::new(ErrorKind::ModuleDenied, format!("Module '{}' mag nie gelaai word nie.", path)).with_note(note))
+    }
+}
+
+/// The fuel and time left to the current run
+pub(crate) struct Budget {
+    ticks: u64,            // Instructions left before the next check
+    fuel: Option<u64>,     // Fuel left besides `ticks`
+    deadline: Option<Instant>,
+    stepping: bool,        // Check before every instruction, for the debugger
+    limits: (Option<u64>, Option<Duration>),  // For the error messages
+}
+
+impl Budget {
+    pub fn start(limits: &Limits, stepping: bool) -> Self {
+        Budget {
+            // Checked before the first instruction
+            ticks: 0,
+            fuel: limits.fuel,
+            deadline: limits.timeout.map(|timeout| Instant::now() + timeout),
+            stepping,
+            limits: (limits.fuel, limits.timeout),
+        }
+    }
+
+    /// Whether the next instruction has to wait for `refill`. Only every so
+    /// often is it; the rest of the time paying is just counting down.
+    #[inline]
+    pub fn is_due(&self) -> bool {
+        self.ticks == 0
+    }
+
+    /// Pays for one instruction
+    #[inline]
+    pub fn tick(&mut self) {
+        self.ticks -= 1;
+    }
+
+    /// Pays for work done besides instructions, such as copying a list. Running
+    /// out only stops the program at its next instruction.
+    pub fn spend(&mut self, amount: u64) {
+        if amount <= self.ticks {
+            self.ticks -= amount;
+            return;
+        }
+        let debt = amount - self.ticks;
+        self.ticks = 0;
+        if let Some(fuel) = &mut self.fuel {
+            *fuel = fuel.saturating_sub(debt);
+        }
+    }
+
+    /// Checks the limits, and pays for the instructions until the next check
+    pub fn refill(&mut self) -> Result<(), ArkaanError> {
+        if let Some(deadline) = self.deadline {
+            if Instant::now() >= deadline {
+                let timeout = self.limits.1.unwrap_or_default();
+                return Err(ArkaanError::new(
+                    ErrorKind::DeadlineExceeded,
+                    format!("Die program het langer as {} ms geloop.", timeout.as_millis()),
+                )
+                .with_help("Kyk vir 'n lus wat nie eindig nie."));
+            }
+        }
+
+        let mut slice = match (self.stepping, self.deadline) {
+            (true, _) => 1,
+            (false, Some(_)) => CLOCK_INTERVAL,
+            (false, None) => u64::MAX,
+        };
+        if let Some(fuel) = &mut self.fuel {
+            if *fuel == 0 {
+                return Err(ArkaanError::new(
+                    ErrorKind::FuelExhausted,
+                    format!("Die program het meer as {} instruksies uitgevoer.", self.limits.0.unwrap_or_default()),
+                )
+                .with_help("Kyk vir 'n lus wat nie eindig nie."));
+            }
+            slice = slice.min(*fuel);
+            *fuel -= slice;
+        }
+        self.ticks = slice;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use super::{Limits, ModuleAccess};
+    use crate::engine::Engine;
+    use crate::error::ArkaanError;
+
+    fn run(limits: Limits, source: &str) -> Result<String, ArkaanError> {
+        let mut engine = Engine::new();
+        engine.vm_mut().set_limits(limits);
+        engine.eval(source).map(|value| value.to_string())
+    }
+
+    fn examples() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("examples")
+    }
+
+    #[test]
+    fn fuel_limits_instructions_and_elements_made() {
+        let limits = Limits { fuel: Some(10_000), ..Limits::default() };
+        let looping = "stel i = 0\nterwyl (i < 100000) {\n    i = i + 1\n}";
+        assert_eq!(run(limits.clone(), looping).unwrap_err().code(), "E0412");
+        assert_eq!(run(limits.clone(), "stel i = 0\nterwyl (i < 100) {\n    i = i + 1\n}\ni").unwrap(), "100");
+
+        // Few instructions, but each copies a long list
+        let copying = "stel l = []\nstel i = 0\nterwyl (i < 400) {\n    l = heg_aan(l, i)\n    i = i + 1\n}";
+        assert_eq!(run(limits, copying).unwrap_err().code(), "E0412");
+    }
+
+    #[test]
+    fn timeout_stops_a_loop_that_never_ends() {
+        let limits = Limits { timeout: Some(Duration::from_millis(50)), ..Limits::default() };
+        assert_eq!(run(limits, "terwyl (waar) {\n}").unwrap_err().code(), "E0413");
+    }
+
+    #[test]
+    fn max_len_limits_each_list_and_string() {
+        let limits = Limits { max_len: Some(10), ..Limits::default() };
+        assert_eq!(run(limits.clone(), "[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]").unwrap(), "[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]");
+        assert_eq!(run(limits.clone(), "heg_aan([1, 2, 3, 4, 5, 6, 7, 8, 9, 10], 11)").unwrap_err().code(), "E0414");
+        assert_eq!(run(limits.clone(), "\"abcdef\" + \"ghijk\"").unwrap_err().code(), "E0414");
+        assert_eq!(run(limits.clone(), "\"lys: \" + [1, 2, 3]").unwrap_err().code(), "E0414");
+
+        // Written out, this list is far longer than it is as a value
+        let nested = "stel l = [1]\nstel i = 0\nterwyl (i < 60) {\n    l = [l, l]\n    i = i + 1\n}\n\"\" + l";
+        assert_eq!(run(limits, nested).unwrap_err().code(), "E0414");
+    }
+
+    #[test]
+    fn module_access_limits_what_laai_loads() {
+        let load = |modules: ModuleAccess| {
+            let mut engine = Engine::new();
+            engine.vm_mut().set_limits(Limits { modules, ..Limits::default() });
+            engine.eval_file(examples().join("test_module.ark"))
+        };
+        assert!(load(ModuleAccess::Any).is_ok());
+        assert!(load(ModuleAccess::Within(vec![examples()])).is_ok());
+        assert_eq!(load(ModuleAccess::None).unwrap_err().code(), "E0415");
+        let elsewhere = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src");
+        assert_eq!(load(ModuleAccess::Within(vec![elsewhere])).unwrap_err().code(), "E0415");
+    }
+}
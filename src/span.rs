@@ -0,0 +1,43 @@
+/// A region of source text: byte offsets plus the line and column where it starts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,   // Byte offset of the first character
+    pub end: usize,     // Byte offset just past the last character
+    pub line: usize,    // 1-based line of `start`
+    pub column: usize,  // 1-based column (in characters) of `start`
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize, column: usize) -> Self {
+        Span {
+            start,
+            end,
+            line,
+            column,
+        }
+    }
+
+    /// Returns a span from the start of `self` to the end of `other`
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            end: self.end.max(other.end),
+            ..self
+        }
+    }
+}
+
+/// A named piece of source code that spans point into
+#[derive(Debug)]
+pub struct SourceFile {
+    pub name: String,
+    pub text: String,
+}
+
+impl SourceFile {
+    pub fn new(name: impl Into<String>, text: impl Into<String>) -> Self {
+        SourceFile {
+            name: name.into(),
+            text: text.into(),
+        }
+    }
+}
@@ -0,0 +1,48 @@
+//! Interned names. A `Symbol` stands for a string that has been seen before,
+//! so names can be compared and hashed as small numbers.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+#[derive(Default)]
+struct Interner {
+    names: Vec<Rc<str>>,
+    symbols: HashMap<Rc<str>, Symbol>,
+}
+
+thread_local! {
+    // Chunks are never shared between threads, so neither are their symbols
+    static INTERNER: RefCell<Interner> = RefCell::new(Interner::default());
+}
+
+impl Symbol {
+    /// The symbol for `name`, the same every time it is asked for on this thread
+    pub fn intern(name: &str) -> Symbol {
+        INTERNER.with(|interner| {
+            let mut interner = interner.borrow_mut();
+            if let Some(&symbol) = interner.symbols.get(name) {
+                return symbol;
+            }
+            let symbol = Symbol(interner.names.len() as u32);
+            let name: Rc<str> = Rc::from(name);
+            interner.names.push(Rc::clone(&name));
+            interner.symbols.insert(name, symbol);
+            symbol
+        })
+    }
+
+    pub fn name(self) -> Rc<str> {
+        INTERNER.with(|interner| Rc::clone(&interner.borrow().names[self.0 as usize]))
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
@@ -1,3 +1,5 @@
+use crate::span::Span;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenType {
     // Keywords (Afrikaans) - Original
@@ -13,6 +15,7 @@ pub enum TokenType {
     Fn,         // lambda/anonymous function
     Gee,        // return
     Laat,       // let (constant binding)
+    Stel,       // var (mutable binding)
     Pas,        // match
     Geval,      // case
     Tipe,       // type definition
@@ -66,15 +69,22 @@ pub enum TokenType {
 pub struct Token {
     pub token_type: TokenType,
     pub lexeme: String,
-    pub line: usize,
+    pub span: Span,
 }
 
 impl Token {
-    pub fn new(token_type: TokenType, lexeme: String, line: usize) -> Self {
+    pub fn new(token_type: TokenType, lexeme: String, span: Span) -> Self {
         Token {
             token_type,
             lexeme,
-            line,
+            span,
         }
     }
 }
+
+/// A `//` comment. The parser never sees these; the formatter puts them back.
+#[derive(Debug, Clone)]
+pub struct Comment {
+    pub text: String,  // Including the leading "//"
+    pub span: Span,
+}
@@ -1,9 +1,13 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::fmt;
 use std::rc::Rc;
 
 use crate::bytecode::Chunk;
+use crate::error::ArkaanError;
+use crate::span::Span;
+use crate::symbol::Symbol;
+use crate::vm::VM;
 
 /// Represents a compiled function
 #[derive(Debug, Clone)]
@@ -12,6 +16,7 @@ pub struct Function {
     pub arity: usize,
     pub chunk: Rc<Chunk>,         // The function's bytecode chunk
     pub upvalue_count: usize,     // Number of upvalues this function captures
+    pub span: Span,               // Where the function was defined
 }
 
 /// Describes how to capture an upvalue
@@ -40,12 +45,17 @@ pub struct Closure {
     pub upvalues: Vec<Rc<RefCell<Upvalue>>>,
 }
 
+/// The Rust side of a native. It gets the VM so it can call back into Arkaan
+/// code; a closure, so a host program can register functions that keep state.
+pub type NativeFn = Rc<dyn Fn(&mut VM, &[Value]) -> Result<Value, ArkaanError>>;
+
 /// Represents a native (built-in) function
 #[derive(Clone)]
 pub struct NativeFunction {
     pub name: String,
     pub arity: usize,
-    pub func: fn(&[Value]) -> Result<Value, String>,
+    pub params: &'static [&'static str],  // Parameter names, for signature help
+    pub func: NativeFn,
 }
 
 impl std::fmt::Debug for NativeFunction {
@@ -60,6 +70,7 @@ pub struct TypeConstructorDef {
     pub type_name: String,          // Name of the type (e.g., "Opsie")
     pub constructor_name: String,   // Name of the constructor (e.g., "Sommige")
     pub arity: usize,               // Number of fields
+    pub tag: Symbol,                // The constructor name, which patterns compare against
 }
 
 /// An instance of an algebraic data type
@@ -67,6 +78,7 @@ pub struct TypeConstructorDef {
 pub struct AdtInstance {
     pub type_name: String,          // Name of the type
     pub constructor_name: String,   // Name of the constructor used
+    pub tag: Symbol,                // Tag of the constructor used
     pub fields: Vec<Value>,         // Field values
 }
 
@@ -75,7 +87,7 @@ pub struct AdtInstance {
 pub struct Module {
     pub name: String,                       // The alias name
     pub path: String,                       // Original file path
-    pub exports: HashMap<String, Value>,    // Exported symbols
+    pub exports: HashMap<Symbol, Value>,    // Exported symbols
 }
 
 #[derive(Debug, Clone)]
@@ -115,76 +127,168 @@ impl Value {
 }
 
 impl fmt::Display for Value {
+    /// Iterative, since lists and ADTs can nest deeper than the native stack
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Value::Number(n) => {
-                if n.fract() == 0.0 {
-                    write!(f, "{}", *n as i64)
-                } else {
-                    write!(f, "{}", n)
+        let mut pending = vec![Piece::Value(self)];
+        while let Some(piece) = pending.pop() {
+            let value = match piece {
+                Piece::Text(text) => {
+                    f.write_str(text)?;
+                    continue;
                 }
-            }
-            Value::Boolean(b) => {
-                if *b {
-                    write!(f, "waar")
-                } else {
-                    write!(f, "vals")
+                Piece::Value(value) => value,
+            };
+            match value {
+                Value::Number(n) => {
+                    if n.fract() == 0.0 {
+                        write!(f, "{}", *n as i64)?
+                    } else {
+                        write!(f, "{}", n)?
+                    }
                 }
-            }
-            Value::String(s) => write!(f, "{}", s),
-            Value::Nil => write!(f, "nil"),
-            Value::List(items) => {
-                write!(f, "[")?;
-                for (i, item) in items.iter().enumerate() {
-                    if i > 0 {
-                        write!(f, ", ")?;
+                Value::Boolean(b) => {
+                    if *b {
+                        write!(f, "waar")?
+                    } else {
+                        write!(f, "vals")?
                     }
-                    write!(f, "{}", item)?;
                 }
-                write!(f, "]")
-            }
-            Value::Function(func) => write!(f, "<funksie {}>", func.name),
-            Value::Closure(closure) => write!(f, "<funksie {}>", closure.function.name),
-            Value::NativeFunction(nf) => write!(f, "<ingeboude {}>", nf.name),
-            Value::TypeConstructor(tc) => write!(f, "<konstruktor {}.{}>", tc.type_name, tc.constructor_name),
-            Value::Adt(adt) => {
-                write!(f, "{}", adt.constructor_name)?;
-                if !adt.fields.is_empty() {
-                    write!(f, "(")?;
-                    for (i, field) in adt.fields.iter().enumerate() {
-                        if i > 0 {
-                            write!(f, ", ")?;
-                        }
-                        write!(f, "{}", field)?;
+                Value::String(s) => write!(f, "{}", s)?,
+                Value::Nil => write!(f, "nil")?,
+                Value::List(items) => {
+                    write!(f, "[")?;
+                    pending.push(Piece::Text("]"));
+                    Piece::push_separated(&mut pending, items);
+                }
+                Value::Function(func) => write!(f, "<funksie {}>", func.name)?,
+                Value::Closure(closure) => write!(f, "<funksie {}>", closure.function.name)?,
+                Value::NativeFunction(nf) => write!(f, "<ingeboude {}>", nf.name)?,
+                Value::TypeConstructor(tc) => write!(f, "<konstruktor {}.{}>", tc.type_name, tc.constructor_name)?,
+                Value::Adt(adt) => {
+                    write!(f, "{}", adt.constructor_name)?;
+                    if !adt.fields.is_empty() {
+                        write!(f, "(")?;
+                        pending.push(Piece::Text(")"));
+                        Piece::push_separated(&mut pending, &adt.fields);
                     }
-                    write!(f, ")")?;
                 }
-                Ok(())
+                Value::Module(m) => write!(f, "<module {}>", m.name)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What is still to be written while displaying a value
+enum Piece<'a> {
+    Value(&'a Value),
+    Text(&'static str),
+}
+
+impl<'a> Piece<'a> {
+    /// Queues `items` with commas between them, the first on top
+    fn push_separated(pending: &mut Vec<Piece<'a>>, items: &'a [Value]) {
+        for (i, item) in items.iter().enumerate().rev() {
+            pending.push(Piece::Value(item));
+            if i > 0 {
+                pending.push(Piece::Text(", "));
             }
-            Value::Module(m) => write!(f, "<module {}>", m.name),
         }
     }
 }
 
 impl PartialEq for Value {
+    /// Iterative, like `Display`
     fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (Value::Number(a), Value::Number(b)) => a == b,
-            (Value::Boolean(a), Value::Boolean(b)) => a == b,
-            (Value::String(a), Value::String(b)) => a == b,
-            (Value::Nil, Value::Nil) => true,
-            (Value::List(a), Value::List(b)) => a == b,
-            (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
-            (Value::Closure(a), Value::Closure(b)) => Rc::ptr_eq(a, b),
-            (Value::NativeFunction(a), Value::NativeFunction(b)) => Rc::ptr_eq(a, b),
-            (Value::TypeConstructor(a), Value::TypeConstructor(b)) => Rc::ptr_eq(a, b),
-            (Value::Adt(a), Value::Adt(b)) => {
-                a.type_name == b.type_name
-                    && a.constructor_name == b.constructor_name
-                    && a.fields == b.fields
+        let mut pending = vec![(self, other)];
+        while let Some((a, b)) = pending.pop() {
+            let equal = match (a, b) {
+                (Value::Number(a), Value::Number(b)) => a == b,
+                (Value::Boolean(a), Value::Boolean(b)) => a == b,
+                (Value::String(a), Value::String(b)) => a == b,
+                (Value::Nil, Value::Nil) => true,
+                (Value::List(a), Value::List(b)) => {
+                    pending.extend(a.iter().zip(b.iter()));
+                    a.len() == b.len()
+                }
+                (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
+                (Value::Closure(a), Value::Closure(b)) => Rc::ptr_eq(a, b),
+                (Value::NativeFunction(a), Value::NativeFunction(b)) => Rc::ptr_eq(a, b),
+                (Value::TypeConstructor(a), Value::TypeConstructor(b)) => Rc::ptr_eq(a, b),
+                (Value::Adt(a), Value::Adt(b)) => {
+                    pending.extend(a.fields.iter().zip(b.fields.iter()));
+                    a.tag == b.tag && a.type_name == b.type_name && a.fields.len() == b.fields.len()
+                }
+                (Value::Module(a), Value::Module(b)) => Rc::ptr_eq(a, b),
+                _ => false,
+            };
+            if !equal {
+                return false;
             }
-            (Value::Module(a), Value::Module(b)) => Rc::ptr_eq(a, b),
-            _ => false,
         }
+        true
+    }
+}
+
+impl Drop for Value {
+    /// Dropping a list or ADT drops what it holds, so a deeply nested one
+    /// would overflow the native stack if left to the compiler's drop glue
+    #[inline]
+    fn drop(&mut self) {
+        if matches!(self, Value::List(_) | Value::Adt(_)) {
+            drop_nested(self);
+        }
+    }
+}
+
+/// How deep dropping a list or ADT may recurse before it switches to a
+/// worklist, which is slower but needs no native stack
+const MAX_DROP_DEPTH: usize = 1_000;
+
+thread_local! {
+    static DROP_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+fn drop_nested(value: &mut Value) {
+    let depth = DROP_DEPTH.get();
+    if depth < MAX_DROP_DEPTH {
+        DROP_DEPTH.set(depth + 1);
+        drop(take_items(value));
+        DROP_DEPTH.set(depth);
+        return;
+    }
+    let mut pending = take_items(value);
+    while let Some(mut item) = pending.pop() {
+        pending.append(&mut take_items(&mut item));
+    }
+}
+
+/// Moves out the items of a list or ADT the last reference is going away from
+fn take_items(value: &mut Value) -> Vec<Value> {
+    match value {
+        Value::List(items) => Rc::get_mut(items).map(std::mem::take),
+        Value::Adt(adt) => Rc::get_mut(adt).map(|adt| std::mem::take(&mut adt.fields)),
+        _ => None,
+    }
+    .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::Value;
+
+    fn nested(depth: usize) -> Value {
+        (0..depth).fold(Value::Nil, |inner, _| Value::List(Rc::new(vec![inner, Value::Number(1.0)])))
+    }
+
+    #[test]
+    fn deeply_nested_lists_display_compare_and_drop() {
+        // Deep enough to overflow a test thread's stack if any of these recursed
+        let (a, b) = (nested(200_000), nested(200_000));
+        assert_eq!(a.to_string(), format!("{}nil{}", "[".repeat(200_000), ", 1]".repeat(200_000)));
+        assert!(a == b);
+        assert!(a != nested(199_999));
     }
 }
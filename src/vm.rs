@@ -1,54 +1,195 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fmt::{self, Write as _};
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
+use crate::arkc::{self, CompiledFile};
 use crate::bytecode::{Chunk, OpCode};
 use crate::compiler::Compiler;
+use crate::debugger::{DebugHandler, Debugger, Location};
+use crate::error::{ArkaanError, ErrorKind, TraceFrame};
+use crate::gc::{GcStats, Heap, Marker};
 use crate::lexer::Lexer;
 use crate::parser::Parser;
-use crate::value::{AdtInstance, Closure, Function, Module, NativeFunction, TypeConstructorDef, Upvalue, UpvalueLocation, Value};
+use crate::sandbox::{Budget, Limits};
+use crate::span::SourceFile;
+use crate::symbol::Symbol;
+use crate::value::{AdtInstance, Closure, Function, Module, NativeFunction, Upvalue, UpvalueLocation, Value};
+
+/// How deeply calls may nest before the VM gives up with a stack overflow
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 100_000;
+
+/// How deeply the dispatch loop may be re-entered, through natives like
+/// `kaart` calling back into Arkaan or through modules loading modules. Each
+/// level costs native stack, unlike an ordinary call: some 6 KB in a release
+/// build and 35 KB in a debug build.
+pub const DEFAULT_MAX_CALLBACK_DEPTH: usize = 1_000;
 
 #[derive(Debug, Clone)]
 struct CallFrame {
     closure: Option<Rc<Closure>>,  // None for plain functions, Some for closures
-    function: Rc<Function>,
-    ip: usize,
+    function: Option<Rc<Function>>, // None for the script itself
+    chunk: Rc<Chunk>,
+    ip: usize,          // Next instruction; for callers, where they resume
     slots_start: usize, // Where this frame's locals start on the stack
 }
 
+/// The global variables of the program or of one module
+#[derive(Default)]
+struct Globals {
+    values: Vec<Option<Value>>,  // By slot; None until defined
+    names: Vec<String>,          // The compiler's name for each slot
+}
+
 pub struct VM {
-    chunk: Chunk,              // The main/script chunk
+    chunk: Rc<Chunk>,          // The main/script chunk
     functions: Vec<Rc<Chunk>>, // Compiled function chunks (Rc for cheap cloning)
     frames: Vec<CallFrame>,    // Call stack
     stack: Vec<Value>,
-    globals: HashMap<String, Value>,
+    globals: Vec<Globals>,        // The program's, then one table per loaded module
+    builtins: HashMap<String, Value>,  // Natives and `argumente`, which every program starts with
     open_upvalues: Vec<Rc<RefCell<Upvalue>>>,  // Open upvalues pointing to stack
+    heap: Heap,                                  // Closures and upvalues, for the cycle collector
+    pinned: Vec<Value>,                          // Values natives hold while they call back into the VM
     module_cache: HashMap<PathBuf, Rc<Module>>,  // Cached modules
     current_file: Option<PathBuf>,               // Current file path for relative imports
+    cache_modules: bool,                         // Keep compiled modules as .arkc next to their source
+    limits: Limits,
+    budget: Budget,                              // What the current run has left of its fuel and time
+    debugger: Option<Debugger>,
+    nested_runs: usize,                          // Dispatch loops currently running, innermost included
 }
 
 impl VM {
-    pub fn new(chunk: Chunk, functions: Vec<Rc<Chunk>>) -> Self {
+    /// A VM for a compiled script; `global_names` is the compiler's table of global slots
+    pub fn new(chunk: Chunk, functions: Vec<Rc<Chunk>>, global_names: Vec<String>) -> Self {
         let mut vm = VM {
-            chunk,
+            chunk: Rc::new(chunk),
             functions,
             frames: Vec::new(),
             stack: Vec::new(),
-            globals: HashMap::new(),
+            globals: vec![Globals { values: Vec::new(), names: global_names }],
+            builtins: HashMap::new(),
             open_upvalues: Vec::new(),
+            heap: Heap::new(),
+            pinned: Vec::new(),
             module_cache: HashMap::new(),
             current_file: None,
+            cache_modules: false,
+            limits: Limits::default(),
+            budget: Budget::start(&Limits::default(), false),
+            debugger: None,
+            nested_runs: 0,
         };
         vm.define_natives();
         vm
     }
 
+    /// The built-in functions every program can call
+    pub fn natives() -> Vec<Rc<NativeFunction>> {
+        // A fresh VM holds nothing but the natives
+        let vm = VM::new(Chunk::new(), Vec::new(), Vec::new());
+        vm.builtins
+            .into_values()
+            .filter_map(|value| match &value {
+                Value::NativeFunction(native) => Some(Rc::clone(native)),
+                _ => None,
+            })
+            .collect()
+    }
+
     pub fn set_current_file(&mut self, path: PathBuf) {
         self.current_file = Some(path);
     }
 
-    fn resolve_module_path(&self, path: &str) -> Result<PathBuf, String> {
+    /// Whether modules are compiled once and then loaded from an `.arkc` file
+    /// next to their source, for as long as the source stays the same
+    pub fn set_module_caching(&mut self, enabled: bool) {
+        self.cache_modules = enabled;
+    }
+
+    /// Collects cycles before every closure is made instead of only now and
+    /// then. Slow; meant for finding values the collector fails to see.
+    pub fn set_gc_stress(&mut self, enabled: bool) {
+        self.heap.set_stress(enabled);
+    }
+
+    pub fn gc_stats(&self) -> GcStats {
+        self.heap.stats()
+    }
+
+    /// Frees closures and upvalues that only keep each other alive. Runs by
+    /// itself as closures are made; everything the VM still holds is a root.
+    pub fn collect_garbage(&mut self) {
+        let mut marker = Marker::default();
+        let globals = self.globals.iter().flat_map(|table| table.values.iter().flatten());
+        let modules = self.module_cache.values().map(|module| Value::Module(Rc::clone(module)));
+        for value in self.stack.iter().chain(globals).chain(self.builtins.values()).chain(&self.pinned) {
+            marker.value(value);
+        }
+        for value in modules {
+            marker.value(&value);
+        }
+        for closure in self.frames.iter().filter_map(|frame| frame.closure.as_ref()) {
+            marker.value(&Value::Closure(Rc::clone(closure)));
+        }
+        for upvalue in &self.open_upvalues {
+            marker.upvalue(upvalue);
+        }
+        self.heap.sweep(marker);
+    }
+
+    /// How many calls may be in progress at once; a program that nests deeper
+    /// stops with a stack overflow error
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.limits.max_call_depth = depth;
+    }
+
+    /// Limits what each run may use, for programs that are not trusted; they
+    /// apply from the next run on
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Checks a list or string about to be made against the length limit, and
+    /// pays fuel for copying it
+    pub fn allocate(&mut self, len: usize) -> Result<(), ArkaanError> {
+        if let Some(max) = self.limits.max_len {
+            if len > max {
+                return Err(ArkaanError::new(
+                    ErrorKind::ValueTooLarge,
+                    format!("'n Lys of string van {} elemente is langer as die {} wat toegelaat word.", len, max),
+                ));
+            }
+        }
+        self.budget.spend(len as u64);
+        Ok(())
+    }
+
+    /// `a` and `b` written one after the other, as `+` joins a string to any
+    /// value. A nested list can be far longer as text than as a value, so
+    /// writing stops as soon as the string would be too long.
+    fn concat(&mut self, a: &Value, b: &Value) -> Result<String, ArkaanError> {
+        let mut text = CappedString { text: String::new(), max: self.limits.max_len };
+        if write!(text, "{}{}", a, b).is_err() {
+            return Err(ArkaanError::new(
+                ErrorKind::ValueTooLarge,
+                format!("'n String van meer as {} grepe is langer as wat toegelaat word.", self.limits.max_len.unwrap_or_default()),
+            ));
+        }
+        self.allocate(text.text.len())?;
+        Ok(text.text)
+    }
+
+    /// Makes the command line arguments after the script available to it as
+    /// the list `argumente`
+    pub fn set_args(&mut self, args: Vec<String>) {
+        let args = args.into_iter().map(|arg| Value::String(Rc::new(arg))).collect();
+        self.builtins.insert(String::from("argumente"), Value::List(Rc::new(args)));
+    }
+
+    fn resolve_module_path(&self, path: &str) -> Result<PathBuf, ArkaanError> {
         let path = Path::new(path);
 
         if path.is_absolute() {
@@ -63,64 +204,59 @@ impl VM {
         }
     }
 
-    fn load_module(&mut self, path: &str, alias: &str) -> Result<Value, String> {
+    fn load_module(&mut self, path: &str, alias: &str) -> Result<Value, ArkaanError> {
         // Resolve the path relative to current file
         let resolved_path = self.resolve_module_path(path)?;
+        self.limits.modules.check(&resolved_path, path)?;
 
         // Check cache first
         if let Some(cached) = self.module_cache.get(&resolved_path) {
             return Ok(Value::Module(Rc::clone(cached)));
         }
 
-        // Read the source file
-        let source = std::fs::read_to_string(&resolved_path)
-            .map_err(|e| format!("Kon nie module '{}' laai nie: {}", path, e))?;
-
-        // Save current state
-        let old_file = self.current_file.take();
-        self.current_file = Some(resolved_path.clone());
-
-        // Compile the module
-        let mut lexer = Lexer::new(&source);
-        let tokens = lexer.scan_tokens()?;
-
-        let mut parser = Parser::new(tokens);
-        let statements = parser.parse()?;
+        let CompiledFile {
+            chunk,
+            functions,
+            exports,
+            globals,
+            ..
+        } = self.compile_module(&resolved_path, path)?;
 
-        let mut compiler = Compiler::new();
-        let (chunk, functions) = compiler.compile(statements)?;
-        let exports = compiler.get_exports().clone();
+        // The module's code refers to a global table of its own, so its
+        // functions still see the module's globals once they are exported
+        let table = self.globals.len();
+        mark_module(&chunk, table);
+        self.globals.push(Globals { values: Vec::new(), names: globals });
 
         // Execute the module to populate globals
         // Save current VM state
-        let old_globals = std::mem::take(&mut self.globals);
+        // It runs above the importer's stack, where the collector still sees both
         let old_functions = std::mem::replace(&mut self.functions, functions);
-        let old_stack = std::mem::take(&mut self.stack);
-        let old_frames = std::mem::take(&mut self.frames);
+        let stack_base = self.stack.len();
+        let old_file = self.current_file.replace(resolved_path.clone());
 
-        // Re-add native functions for the module
-        self.define_natives();
+        // The module starts out with the natives, like any program
+        self.link_globals(table);
 
         // Run the module's initialization code
-        self.run_chunk(&chunk)?;
-
-        // Capture the module's globals
-        let module_globals = std::mem::take(&mut self.globals);
+        let result = self.run_chunk(Rc::new(chunk));
 
-        // Restore original state
-        self.globals = old_globals;
+        // Restore original state, even if the module failed
         self.functions = old_functions;
-        self.stack = old_stack;
-        self.frames = old_frames;
-
-        // Restore current file
         self.current_file = old_file;
+        if result.is_err() {
+            self.close_upvalues(stack_base);
+            self.stack.truncate(stack_base);
+        }
+        result?;
 
         // Extract only exported symbols
         let mut exported_values = HashMap::new();
+        let module_globals = &self.globals[table];
         for name in &exports {
-            if let Some(value) = module_globals.get(name) {
-                exported_values.insert(name.clone(), value.clone());
+            let slot = module_globals.names.iter().position(|global| global == name);
+            if let Some(value) = slot.and_then(|slot| module_globals.values[slot].as_ref()) {
+                exported_values.insert(Symbol::intern(name), value.clone());
             }
         }
 
@@ -137,340 +273,837 @@ impl VM {
         Ok(Value::Module(module))
     }
 
+    /// A module's bytecode: read from the `.arkc` file that `laai` names, or
+    /// from the cached one next to the source, or else compiled from source
+    fn compile_module(&self, resolved_path: &Path, path: &str) -> Result<CompiledFile, ArkaanError> {
+        let read_error = |e: std::io::Error| {
+            ArkaanError::new(ErrorKind::ModuleError, format!("Kon nie module '{}' laai nie: {}", path, e))
+        };
+
+        if resolved_path.extension().is_some_and(|ext| ext == "arkc") {
+            let bytes = std::fs::read(resolved_path).map_err(read_error)?;
+            return arkc::decode(&bytes);
+        }
+
+        let source = std::fs::read_to_string(resolved_path).map_err(read_error)?;
+        let source_hash = arkc::source_hash(&source);
+        let cached_path = resolved_path.with_extension("arkc");
+        if self.cache_modules {
+            let cached = std::fs::read(&cached_path).ok().and_then(|bytes| arkc::decode(&bytes).ok());
+            if let Some(cached) = cached.filter(|cached| cached.source_hash == source_hash) {
+                return Ok(cached);
+            }
+        }
+
+        let file = Rc::new(SourceFile::new(resolved_path.display().to_string(), source));
+        let mut lexer = Lexer::new(&file.text);
+        let tokens = lexer.scan_tokens().map_err(|e| e.in_file(&file))?;
+
+        let mut parser = Parser::new(tokens);
+        let statements = parser.parse().map_err(|e| e.in_file(&file))?;
+
+        let mut compiler = Compiler::new();
+        compiler.set_file(Rc::clone(&file));
+        let (chunk, functions) = compiler.compile(statements)?;
+        let mut exports: Vec<String> = compiler.get_exports().iter().cloned().collect();
+        exports.sort();
+
+        let compiled = CompiledFile {
+            chunk,
+            functions,
+            exports,
+            globals: compiler.global_names().to_vec(),
+            source_hash,
+        };
+        if self.cache_modules {
+            // A cache that cannot be written only costs the next run a compile
+            if let Ok(bytes) = arkc::encode(&compiled) {
+                let _ = std::fs::write(&cached_path, bytes);
+            }
+        }
+        Ok(compiled)
+    }
+
     fn define_natives(&mut self) {
         // lengte(lys) - returns the length of a list or string
-        self.globals.insert(
+        self.builtins.insert(
             String::from("lengte"),
             Value::NativeFunction(Rc::new(NativeFunction {
                 name: String::from("lengte"),
                 arity: 1,
-                func: |args| {
+                params: &["lys"],
+                func: Rc::new(|_, args| {
                     match &args[0] {
                         Value::List(items) => Ok(Value::Number(items.len() as f64)),
                         Value::String(s) => Ok(Value::Number(s.chars().count() as f64)),
-                        _ => Err("lengte() verwag 'n lys of string.".to_string()),
+                        _ => Err(ArkaanError::new(ErrorKind::TypeMismatch, "lengte() verwag 'n lys of string.")),
                     }
-                },
+                }),
             })),
         );
 
         // kop(lys) - returns the first element of a list
-        self.globals.insert(
+        self.builtins.insert(
             String::from("kop"),
             Value::NativeFunction(Rc::new(NativeFunction {
                 name: String::from("kop"),
                 arity: 1,
-                func: |args| {
+                params: &["lys"],
+                func: Rc::new(|_, args| {
                     match &args[0] {
                         Value::List(items) => {
                             if items.is_empty() {
-                                Err("Kan nie kop van leë lys kry nie.".to_string())
+                                Err(ArkaanError::new(ErrorKind::EmptyList, "Kan nie kop van leë lys kry nie."))
                             } else {
                                 Ok(items[0].clone())
                             }
                         }
-                        _ => Err("kop() verwag 'n lys.".to_string()),
+                        _ => Err(ArkaanError::new(ErrorKind::TypeMismatch, "kop() verwag 'n lys.")),
                     }
-                },
+                }),
             })),
         );
 
         // stert(lys) - returns all but the first element of a list
-        self.globals.insert(
+        self.builtins.insert(
             String::from("stert"),
             Value::NativeFunction(Rc::new(NativeFunction {
                 name: String::from("stert"),
                 arity: 1,
-                func: |args| {
+                params: &["lys"],
+                func: Rc::new(|vm, args| {
                     match &args[0] {
                         Value::List(items) => {
                             if items.is_empty() {
-                                Err("Kan nie stert van leë lys kry nie.".to_string())
+                                Err(ArkaanError::new(ErrorKind::EmptyList, "Kan nie stert van leë lys kry nie."))
                             } else {
+                                vm.allocate(items.len() - 1)?;
                                 let tail: Vec<Value> = items[1..].to_vec();
                                 Ok(Value::List(Rc::new(tail)))
                             }
                         }
-                        _ => Err("stert() verwag 'n lys.".to_string()),
+                        _ => Err(ArkaanError::new(ErrorKind::TypeMismatch, "stert() verwag 'n lys.")),
                     }
-                },
+                }),
             })),
         );
 
         // leeg(lys) - returns true if list is empty
-        self.globals.insert(
+        self.builtins.insert(
             String::from("leeg"),
             Value::NativeFunction(Rc::new(NativeFunction {
                 name: String::from("leeg"),
                 arity: 1,
-                func: |args| {
+                params: &["lys"],
+                func: Rc::new(|_, args| {
                     match &args[0] {
                         Value::List(items) => Ok(Value::Boolean(items.is_empty())),
                         Value::String(s) => Ok(Value::Boolean(s.is_empty())),
-                        _ => Err("leeg() verwag 'n lys of string.".to_string()),
+                        _ => Err(ArkaanError::new(ErrorKind::TypeMismatch, "leeg() verwag 'n lys of string.")),
                     }
-                },
+                }),
             })),
         );
 
         // voeg_by(element, lys) - prepends element to list (cons)
-        self.globals.insert(
+        self.builtins.insert(
             String::from("voeg_by"),
             Value::NativeFunction(Rc::new(NativeFunction {
                 name: String::from("voeg_by"),
                 arity: 2,
-                func: |args| {
+                params: &["element", "lys"],
+                func: Rc::new(|vm, args| {
                     match &args[1] {
                         Value::List(items) => {
+                            vm.allocate(items.len() + 1)?;
                             let mut new_list = vec![args[0].clone()];
                             new_list.extend(items.iter().cloned());
                             Ok(Value::List(Rc::new(new_list)))
                         }
-                        _ => Err("voeg_by() verwag 'n lys as tweede argument.".to_string()),
+                        _ => Err(ArkaanError::new(ErrorKind::TypeMismatch, "voeg_by() verwag 'n lys as tweede argument.")),
                     }
-                },
+                }),
             })),
         );
 
         // heg_aan(lys, element) - appends element to list
-        self.globals.insert(
+        self.builtins.insert(
             String::from("heg_aan"),
             Value::NativeFunction(Rc::new(NativeFunction {
                 name: String::from("heg_aan"),
                 arity: 2,
-                func: |args| {
+                params: &["lys", "element"],
+                func: Rc::new(|vm, args| {
                     match &args[0] {
                         Value::List(items) => {
+                            vm.allocate(items.len() + 1)?;
                             let mut new_list = items.as_ref().clone();
                             new_list.push(args[1].clone());
                             Ok(Value::List(Rc::new(new_list)))
                         }
-                        _ => Err("heg_aan() verwag 'n lys as eerste argument.".to_string()),
+                        _ => Err(ArkaanError::new(ErrorKind::TypeMismatch, "heg_aan() verwag 'n lys as eerste argument.")),
                     }
-                },
+                }),
             })),
         );
 
         // ketting(lys1, lys2) - concatenates two lists
-        self.globals.insert(
+        self.builtins.insert(
             String::from("ketting"),
             Value::NativeFunction(Rc::new(NativeFunction {
                 name: String::from("ketting"),
                 arity: 2,
-                func: |args| {
+                params: &["lys1", "lys2"],
+                func: Rc::new(|vm, args| {
                     match (&args[0], &args[1]) {
                         (Value::List(a), Value::List(b)) => {
+                            vm.allocate(a.len() + b.len())?;
                             let mut new_list = a.as_ref().clone();
                             new_list.extend(b.iter().cloned());
                             Ok(Value::List(Rc::new(new_list)))
                         }
-                        _ => Err("ketting() verwag twee lyste.".to_string()),
+                        _ => Err(ArkaanError::new(ErrorKind::TypeMismatch, "ketting() verwag twee lyste.")),
                     }
-                },
+                }),
             })),
         );
 
         // omgekeer(lys) - reverses a list
-        self.globals.insert(
+        self.builtins.insert(
             String::from("omgekeer"),
             Value::NativeFunction(Rc::new(NativeFunction {
                 name: String::from("omgekeer"),
                 arity: 1,
-                func: |args| {
+                params: &["lys"],
+                func: Rc::new(|vm, args| {
                     match &args[0] {
                         Value::List(items) => {
+                            vm.allocate(items.len())?;
                             let reversed: Vec<Value> = items.iter().rev().cloned().collect();
                             Ok(Value::List(Rc::new(reversed)))
                         }
-                        _ => Err("omgekeer() verwag 'n lys.".to_string()),
+                        _ => Err(ArkaanError::new(ErrorKind::TypeMismatch, "omgekeer() verwag 'n lys.")),
                     }
-                },
+                }),
             })),
         );
 
-        // Higher-order functions are handled specially in Call opcode
-        // These are placeholder registrations so they're recognized as functions
-
+        // Higher-order functions call back into the VM
         // kaart(lys, fn) - map function over list
-        self.globals.insert(
+        self.builtins.insert(
             String::from("kaart"),
             Value::NativeFunction(Rc::new(NativeFunction {
                 name: String::from("kaart"),
                 arity: 2,
-                func: |_| Err("kaart() moet spesiaal hanteer word.".to_string()),
+                params: &["lys", "fn"],
+                func: Rc::new(|vm, args| {
+                    match &args[0] {
+                        Value::List(list) => vm.hof_kaart(Rc::clone(list), args[1].clone()),
+                        _ => Err(ArkaanError::new(ErrorKind::TypeMismatch, "kaart() verwag 'n lys as eerste argument.")),
+                    }
+                }),
             })),
         );
 
         // filter(lys, fn) - filter list by predicate
-        self.globals.insert(
+        self.builtins.insert(
             String::from("filter"),
             Value::NativeFunction(Rc::new(NativeFunction {
                 name: String::from("filter"),
                 arity: 2,
-                func: |_| Err("filter() moet spesiaal hanteer word.".to_string()),
+                params: &["lys", "fn"],
+                func: Rc::new(|vm, args| {
+                    match &args[0] {
+                        Value::List(list) => vm.hof_filter(Rc::clone(list), args[1].clone()),
+                        _ => Err(ArkaanError::new(ErrorKind::TypeMismatch, "filter() verwag 'n lys as eerste argument.")),
+                    }
+                }),
             })),
         );
 
         // vou(lys, begin, fn) - fold/reduce list
-        self.globals.insert(
+        self.builtins.insert(
             String::from("vou"),
             Value::NativeFunction(Rc::new(NativeFunction {
                 name: String::from("vou"),
                 arity: 3,
-                func: |_| Err("vou() moet spesiaal hanteer word.".to_string()),
+                params: &["lys", "begin", "fn"],
+                func: Rc::new(|vm, args| {
+                    match &args[0] {
+                        Value::List(list) => vm.hof_vou(Rc::clone(list), args[1].clone(), args[2].clone()),
+                        _ => Err(ArkaanError::new(ErrorKind::TypeMismatch, "vou() verwag 'n lys as eerste argument.")),
+                    }
+                }),
             })),
         );
 
         // vir_elk(lys, fn) - for each element, call function (returns nil)
-        self.globals.insert(
+        self.builtins.insert(
             String::from("vir_elk"),
             Value::NativeFunction(Rc::new(NativeFunction {
                 name: String::from("vir_elk"),
                 arity: 2,
-                func: |_| Err("vir_elk() moet spesiaal hanteer word.".to_string()),
+                params: &["lys", "fn"],
+                func: Rc::new(|vm, args| {
+                    match &args[0] {
+                        Value::List(list) => vm.hof_vir_elk(Rc::clone(list), args[1].clone()),
+                        _ => Err(ArkaanError::new(ErrorKind::TypeMismatch, "vir_elk() verwag 'n lys as eerste argument.")),
+                    }
+                }),
             })),
         );
     }
 
-    pub fn run(&mut self) -> Result<(), String> {
-        // Start executing the main chunk directly (not as a function call)
-        self.run_chunk(&self.chunk.clone())
+    pub fn run(&mut self) -> Result<(), ArkaanError> {
+        self.link_globals(0);
+        // The main chunk runs in a frame of its own, below every call
+        self.run_chunk(Rc::clone(&self.chunk))?;
+        Ok(())
+    }
+
+    /// Runs another chunk against this VM's existing globals and module cache.
+    /// Used by the REPL, where every entry is compiled separately by the same
+    /// compiler, whose global table only grows.
+    pub fn interpret(&mut self, chunk: Chunk, functions: Vec<Rc<Chunk>>, global_names: &[String]) -> Result<Value, ArkaanError> {
+        if !global_names.starts_with(&self.globals[0].names) {
+            return Err(ArkaanError::new(
+                ErrorKind::InvalidBytecode,
+                "Die kode is nie vir hierdie VM gekompileer nie.",
+            ));
+        }
+        self.chunk = Rc::new(chunk);
+        self.functions = functions;
+        self.link_program_globals(global_names);
+
+        let result = self.run_chunk(Rc::clone(&self.chunk));
+        if result.is_err() {
+            // Discard whatever the failed entry left behind, after closures
+            // it made have taken the values they captured
+            self.close_upvalues(0);
+            self.stack.clear();
+            self.frames.clear();
+            self.open_upvalues.clear();
+        }
+        result
+    }
+
+    /// Takes on a longer table of program global slots, as the compiler grew it
+    pub(crate) fn link_program_globals(&mut self, global_names: &[String]) {
+        self.globals[0].names = global_names.to_vec();
+        self.link_globals(0);
+    }
+
+    /// A global of the program, if it has been given a value
+    pub fn global(&self, name: &str) -> Option<Value> {
+        let table = &self.globals[0];
+        let slot = table.names.iter().position(|known| known == name)?;
+        table.values[slot].clone()
+    }
+
+    /// Sets a global the program has a slot for
+    pub fn set_global(&mut self, name: &str, value: Value) -> Result<(), ArkaanError> {
+        let table = &mut self.globals[0];
+        match table.names.iter().position(|known| known == name) {
+            Some(slot) => {
+                table.values[slot] = Some(value);
+                Ok(())
+            }
+            None => Err(ArkaanError::new(
+                ErrorKind::UndefinedVariable,
+                format!("Ongedefinieerde veranderlike: '{}'", name),
+            )),
+        }
+    }
+
+    /// Makes a native available under its name to everything this VM runs,
+    /// including code already linked, unless that code gave the name a value
+    pub fn define_native(&mut self, native: NativeFunction) {
+        let name = native.name.clone();
+        let value = Value::NativeFunction(Rc::new(native));
+        for table in &mut self.globals {
+            for (known, global) in table.names.iter().zip(&mut table.values) {
+                if known == &name && global.is_none() {
+                    *global = Some(value.clone());
+                }
+            }
+        }
+        self.builtins.insert(name, value);
+    }
+
+    /// Pauses the next run at its first line and hands it to `handler`
+    pub fn attach_debugger(&mut self, handler: impl DebugHandler + 'static) {
+        self.debugger = Some(Debugger::new(Box::new(handler)));
+    }
+
+    /// The attached debugger, for setting breakpoints
+    pub fn debugger(&mut self) -> Option<&mut Debugger> {
+        self.debugger.as_mut()
+    }
+
+    /// The calls in progress, innermost first. Meant for a paused program.
+    pub fn call_stack(&self) -> Vec<TraceFrame> {
+        (0..self.frames.len())
+            .filter_map(|index| {
+                let (frame, position) = self.frame_position(index)?;
+                Some(TraceFrame {
+                    function: frame.function.as_deref().map_or_else(|| "<script>".to_string(), Self::describe_function),
+                    file: frame.chunk.file.as_ref().map(|file| file.name.clone()),
+                    line: frame.chunk.line_at(position),
+                })
+            })
+            .collect()
+    }
+
+    /// The frame `index` calls from the top, and the offset of the
+    /// instruction it is at: paused before it, or waiting for its call
+    fn frame_position(&self, index: usize) -> Option<(&CallFrame, usize)> {
+        let frame = self.frames.iter().rev().nth(index)?;
+        let position = if index == 0 { frame.ip } else { frame.ip.saturating_sub(1) };
+        Some((frame, position))
+    }
+
+    /// The named locals in scope in frame `index` from the top, by name
+    pub fn frame_locals(&self, index: usize) -> Vec<(String, Value)> {
+        let Some((frame, position)) = self.frame_position(index) else {
+            return Vec::new();
+        };
+        let mut in_scope: Vec<_> = frame.chunk.locals.iter().filter(|local| (local.start..local.end).contains(&position)).collect();
+        in_scope.sort_by_key(|local| local.slot);
+
+        let mut locals: Vec<(String, Value)> = Vec::new();
+        for local in in_scope {
+            let Some(value) = self.stack.get(frame.slots_start + local.slot) else {
+                continue;
+            };
+            // An inner block's local hides an outer one of the same name
+            let name = local.name.name().to_string();
+            locals.retain(|(known, _)| *known != name);
+            locals.push((name, value.clone()));
+        }
+        locals
+    }
+
+    /// The variables the closure of frame `index` from the top captured
+    pub fn frame_upvalues(&self, index: usize) -> Vec<(String, Value)> {
+        let Some((frame, _)) = self.frame_position(index) else {
+            return Vec::new();
+        };
+        let Some(closure) = &frame.closure else {
+            return Vec::new();
+        };
+        frame
+            .chunk
+            .upvalue_names
+            .iter()
+            .zip(&closure.upvalues)
+            .filter_map(|(name, upvalue)| {
+                let value = match &upvalue.borrow().location {
+                    UpvalueLocation::Open(slot) => self.stack.get(*slot)?.clone(),
+                    UpvalueLocation::Closed(value) => value.clone(),
+                };
+                Some((name.name().to_string(), value))
+            })
+            .collect()
+    }
+
+    /// The globals defined so far for the code of frame `index` from the top,
+    /// without the natives
+    pub fn frame_globals(&self, index: usize) -> Vec<(String, Value)> {
+        let Some((frame, _)) = self.frame_position(index) else {
+            return Vec::new();
+        };
+        let table = &self.globals[frame.chunk.module.get()];
+        table
+            .names
+            .iter()
+            .zip(&table.values)
+            .filter_map(|(name, value)| match value {
+                Some(Value::NativeFunction(_)) | None => None,
+                Some(value) => Some((name.clone(), value.clone())),
+            })
+            .collect()
+    }
+
+    /// Evaluates an expression as if it were written in frame `index` from the
+    /// top: it sees that frame's locals, upvalues and globals. Assigning to a
+    /// local or upvalue does not change the frame.
+    pub fn eval_in_frame(&mut self, index: usize, expression: &str) -> Result<Value, ArkaanError> {
+        let Some((frame, _)) = self.frame_position(index) else {
+            return Err(ArkaanError::new(ErrorKind::Internal, format!("Daar is geen raam {} nie.", index)));
+        };
+        let table = frame.chunk.module.get();
+
+        let locals = self.frame_locals(index);
+        let mut variables = self.frame_upvalues(index);
+        variables.retain(|(name, _)| !locals.iter().any(|(local, _)| local == name));
+        variables.extend(locals);
+
+        // A lambda taking the variables as parameters, called with their values
+        let (mut lambda, natives) = self.compile_in_table(table, &variables, expression)?;
+        if !natives.is_empty() {
+            // Natives the frame's code never used have no global slot there, so they are passed in too
+            variables.extend(natives);
+            lambda = self.compile_in_table(table, &variables, expression)?.0;
+        }
+
+        let base = self.stack.len();
+        let result = self.run_chunk(Rc::new(lambda)).and_then(|lambda| {
+            let values = variables.into_iter().map(|(_, value)| value).collect();
+            self.call_value(lambda, values)
+        });
+        if result.is_err() {
+            self.close_upvalues(base);
+            self.stack.truncate(base);
+        }
+        result
+    }
+
+    /// Compiles `fn(<variables>) { gee <expression> }` against global table
+    /// `table`. Also returns the natives it uses that the table has no slot for.
+    fn compile_in_table(
+        &self,
+        table: usize,
+        variables: &[(String, Value)],
+        expression: &str,
+    ) -> Result<(Chunk, Vec<(String, Value)>), ArkaanError> {
+        let names: Vec<&str> = variables.iter().map(|(name, _)| name.as_str()).collect();
+        let file = Rc::new(SourceFile::new("<eval>", format!("fn({}) {{ gee {} }}", names.join(", "), expression)));
+        let tokens = Lexer::new(&file.text).scan_tokens().map_err(|e| e.in_file(&file))?;
+        let statements = Parser::new(tokens).parse().map_err(|e| e.in_file(&file))?;
+
+        let mut compiler = Compiler::new();
+        compiler.set_file(Rc::clone(&file));
+        let known = &self.globals[table].names;
+        for name in known {
+            compiler.global_slot(name);
+        }
+        let (chunk, _) = compiler.compile_repl(statements)?;
+        mark_module(&chunk, table);
+
+        let mut natives = Vec::new();
+        for name in &compiler.global_names()[known.len()..] {
+            match self.builtins.get(name) {
+                Some(native) => natives.push((name.clone(), native.clone())),
+                None => {
+                    return Err(ArkaanError::new(
+                        ErrorKind::UndefinedVariable,
+                        format!("Ongedefinieerde veranderlike: '{}'", name),
+                    ))
+                }
+            }
+        }
+        Ok((chunk, natives))
+    }
+
+    /// Gives the slots added to a global table since the last run their first
+    /// value: the built-in of that name, if there is one
+    fn link_globals(&mut self, table: usize) {
+        let Globals { values, names } = &mut self.globals[table];
+        for name in &names[values.len()..] {
+            values.push(self.builtins.get(name).cloned());
+        }
+    }
+
+    fn undefined_global(&self, table: usize, slot: usize) -> ArkaanError {
+        ArkaanError::new(
+            ErrorKind::UndefinedVariable,
+            format!("Ongedefinieerde veranderlike: '{}'", self.globals[table].names[slot]),
+        )
+    }
+
+    fn run_chunk(&mut self, chunk: Rc<Chunk>) -> Result<Value, ArkaanError> {
+        self.frames.push(CallFrame {
+            closure: None,
+            function: None,
+            chunk,
+            ip: 0,
+            slots_start: self.stack.len(),
+        });
+        self.execute()
+    }
+
+    /// Adds a frame's call to the trace of an error unwinding through it,
+    /// locating the error there if nothing inside it did
+    fn unwind(error: ArkaanError, frame: &CallFrame) -> ArkaanError {
+        let span = frame.chunk.span_at(frame.ip.saturating_sub(1));
+        let trace = TraceFrame {
+            function: frame.function.as_deref().map_or_else(|| "<script>".to_string(), Self::describe_function),
+            file: frame.chunk.file.as_ref().map(|file| file.name.clone()),
+            line: span.line,
+        };
+        error.located(span, frame.chunk.file.as_ref()).with_frame(trace)
+    }
+
+    /// Name of a function as shown in stack traces; lambdas are told apart by where they were defined
+    fn describe_function(function: &Function) -> String {
+        if function.name == "<lambda>" {
+            format!("<lambda van lyn {}>", function.span.line)
+        } else {
+            function.name.clone()
+        }
     }
 
-    fn run_chunk(&mut self, chunk: &Chunk) -> Result<(), String> {
+    /// Runs the topmost frame, and every call it makes, until it returns
+    fn execute(&mut self) -> Result<Value, ArkaanError> {
+        let base_depth = self.frames.len() - 1;
+        if self.nested_runs > self.limits.max_callback_depth {
+            self.frames.truncate(base_depth);
+            return Err(ArkaanError::new(
+                ErrorKind::StackOverflow,
+                format!(
+                    "Stapeloorloop: meer as {} geneste oproepe deur ingeboude funksies soos kaart.",
+                    self.limits.max_callback_depth
+                ),
+            )
+            .with_help("Skryf die rekursie met 'n gewone oproep in plaas van deur kaart, filter, vou of vir_elk, of verhoog die limiet met --max-callback-depth."));
+        }
+
+        if self.nested_runs == 0 {
+            self.budget = Budget::start(&self.limits, self.debugger.is_some());
+        }
+
         let mut ip = 0;
+        self.nested_runs += 1;
+        let result = self.dispatch(base_depth, &mut ip);
+        self.nested_runs -= 1;
+
+        result.map_err(|error| {
+            // The failing instruction is in the top frame; the frames below it are stopped at their calls
+            if let Some(frame) = self.frames.last_mut() {
+                frame.ip = ip;
+            }
+            let error = self.frames[base_depth..].iter().rev().fold(error, Self::unwind);
+            self.frames.truncate(base_depth);
+            error
+        })
+    }
+
+    /// The chunk, stack base and closure of the frame on top of the call stack
+    fn frame_state(&self) -> (Rc<Chunk>, usize, Option<Rc<Closure>>) {
+        let frame = self.frames.last().expect("dispatch requires a call frame");
+        (Rc::clone(&frame.chunk), frame.slots_start, frame.closure.clone())
+    }
+
+    /// Pushes a frame for a call whose callee and arguments are on top of the stack
+    fn push_frame(&mut self, function: Rc<Function>, closure: Option<Rc<Closure>>, arg_count: usize) -> Result<(), ArkaanError> {
+        Self::check_arity(&function, arg_count)?;
+        // The frame of the script itself is not a call
+        if self.frames.len() > self.limits.max_call_depth {
+            return Err(ArkaanError::new(
+                ErrorKind::StackOverflow,
+                format!("Stapeloorloop: meer as {} geneste funksie-oproepe.", self.limits.max_call_depth),
+            )
+            .with_help("Maak die rekursie stert-rekursief, of verhoog die limiet met --max-call-depth."));
+        }
+
+        self.frames.push(CallFrame {
+            chunk: Rc::clone(&function.chunk),
+            function: Some(function),
+            closure,
+            ip: 0,
+            slots_start: self.stack.len() - arg_count - 1,
+        });
+        Ok(())
+    }
+
+    fn check_arity(function: &Function, arg_count: usize) -> Result<(), ArkaanError> {
+        if arg_count != function.arity {
+            return Err(ArkaanError::new(
+                ErrorKind::ArityMismatch,
+                format!("Verwag {} argumente maar het {} ontvang.", function.arity, arg_count),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Pops the top frame and its stack slots. Gives back the result once the
+    /// frame `execute` started with has returned; otherwise the caller carries on
+    /// with the result on its stack.
+    fn return_from_frame(&mut self, result: Value, base_depth: usize) -> Option<Value> {
+        let frame = self.frames.pop().expect("return requires a call frame");
+        self.close_upvalues(frame.slots_start);
+        self.stack.truncate(frame.slots_start);
+
+        if self.frames.len() == base_depth {
+            return Some(result);
+        }
+        self.push(result);
+        None
+    }
+
+    /// Checks the limits of the run and, with a debugger attached, whether to
+    /// pause before the instruction at `ip`
+    #[cold]
+    #[inline(never)]
+    fn checkpoint(&mut self, chunk: &Rc<Chunk>, ip: usize) -> Result<(), ArkaanError> {
+        // Code the debugger's handler evaluates is paid for from the same
+        // budget, so only refill once it is done
+        if self.debugger.is_some() {
+            self.debug_pause(chunk, ip);
+        }
+        self.budget.refill()
+    }
+
+    fn debug_pause(&mut self, chunk: &Rc<Chunk>, ip: usize) {
+        let Some(debugger) = self.debugger.as_mut() else {
+            return;
+        };
+        // Jumps and the pops that end a block carry the line of the loop or
+        // block they belong to; stopping there as well as at the loop's
+        // condition would show that line twice
+        if matches!(OpCode::from_byte(chunk.code[ip]), Some(OpCode::Jump | OpCode::Pop | OpCode::CloseUpvalue)) {
+            return;
+        }
+        let here = Location { depth: self.frames.len(), chunk: Rc::as_ptr(chunk), line: chunk.line_at(ip), ip };
+        let Some(reason) = debugger.should_pause(here, chunk.file.as_deref()) else {
+            return;
+        };
+        let Some(mut handler) = debugger.take_handler() else {
+            return;
+        };
+
+        // The handler sees where the paused frame is
+        if let Some(frame) = self.frames.last_mut() {
+            frame.ip = ip;
+        }
+        let step = handler.paused(self, reason);
+        if let Some(debugger) = self.debugger.as_mut() {
+            debugger.resume(handler, step, here);
+        }
+    }
+
+    fn dispatch(&mut self, base_depth: usize, ip: &mut usize) -> Result<Value, ArkaanError> {
+        let (mut chunk, mut slots_start, mut closure) = self.frame_state();
+        *ip = self.frames[base_depth].ip;
 
         loop {
-            if ip >= chunk.code.len() {
-                return Ok(());
+            if *ip >= chunk.code.len() {
+                // Falling off the end of a chunk returns nil
+                if let Some(result) = self.return_from_frame(Value::Nil, base_depth) {
+                    return Ok(result);
+                }
+                (chunk, slots_start, closure) = self.frame_state();
+                *ip = self.frames.last().unwrap().ip;
+                continue;
             }
 
-            let instruction = &chunk.code[ip];
-            ip += 1;
+            if self.budget.is_due() {
+                self.checkpoint(&chunk, *ip)?;
+            }
+            self.budget.tick();
+            let instruction = OpCode::from_byte(chunk.code[*ip])
+                .ok_or_else(|| ArkaanError::new(ErrorKind::Internal, "Onbekende instruksie."))?;
+            *ip += 1;
 
             match instruction {
-                OpCode::Constant(idx) => {
-                    let value = chunk.constants[*idx].clone();
+                OpCode::Constant => {
+                    let value = chunk.constants[read_u16(&chunk, ip)].clone();
                     self.push(value);
                 }
                 OpCode::Pop => {
                     self.pop()?;
                 }
-                OpCode::GetVar(name) | OpCode::GetGlobal(name) => {
-                    let value = self
-                        .globals
-                        .get(name)
-                        .cloned()
-                        .ok_or_else(|| format!("Ongedefinieerde veranderlike: '{}'", name))?;
+                OpCode::GetGlobal => {
+                    let (table, slot) = (chunk.module.get(), read_u16(&chunk, ip));
+                    let value = self.globals[table].values[slot]
+                        .clone()
+                        .ok_or_else(|| self.undefined_global(table, slot))?;
                     self.push(value);
                 }
-                OpCode::SetVar(name) | OpCode::SetGlobal(name) => {
+                OpCode::SetGlobal => {
+                    let (table, slot) = (chunk.module.get(), read_u16(&chunk, ip));
                     let value = self.peek()?.clone();
-                    if !self.globals.contains_key(name) {
-                        return Err(format!("Ongedefinieerde veranderlike: '{}'", name));
+                    if self.globals[table].values[slot].is_none() {
+                        return Err(self.undefined_global(table, slot));
                     }
-                    self.globals.insert(name.clone(), value);
+                    self.globals[table].values[slot] = Some(value);
                 }
-                OpCode::DefineGlobal(name) => {
+                OpCode::DefineGlobal => {
+                    let (table, slot) = (chunk.module.get(), read_u16(&chunk, ip));
                     let value = self.pop()?;
-                    self.globals.insert(name.clone(), value);
+                    self.globals[table].values[slot] = Some(value);
                 }
-                OpCode::GetLocal(slot) => {
-                    let base = if self.frames.is_empty() {
-                        0
-                    } else {
-                        self.frames.last().unwrap().slots_start
-                    };
-                    let value = self.stack[base + *slot].clone();
+                OpCode::GetLocal => {
+                    let slot = read_u16(&chunk, ip);
+                    let value = self.slot(slots_start + slot)?.clone();
                     self.push(value);
                 }
-                OpCode::SetLocal(slot) => {
-                    let base = if self.frames.is_empty() {
-                        0
-                    } else {
-                        self.frames.last().unwrap().slots_start
-                    };
+                OpCode::SetLocal => {
+                    let slot = read_u16(&chunk, ip);
                     let value = self.peek()?.clone();
-                    self.stack[base + *slot] = value;
+                    *self.slot_mut(slots_start + slot)? = value;
                 }
-                OpCode::GetUpvalue(slot) => {
-                    if let Some(frame) = self.frames.last() {
-                        if let Some(ref closure) = frame.closure {
-                            let value = {
-                                let upvalue = closure.upvalues[*slot].borrow();
-                                match &upvalue.location {
-                                    UpvalueLocation::Open(idx) => self.stack[*idx].clone(),
-                                    UpvalueLocation::Closed(val) => val.clone(),
-                                }
-                            };
-                            self.push(value);
-                        } else {
-                            return Err("GetUpvalue called on non-closure function".to_string());
-                        }
+                OpCode::GetUpvalue => {
+                    let slot = read_u16(&chunk, ip);
+                    if let Some(ref cl) = closure {
+                        let value = {
+                            let upvalue = cl.upvalues[slot].borrow();
+                            match &upvalue.location {
+                                UpvalueLocation::Open(idx) => self.slot(*idx)?.clone(),
+                                UpvalueLocation::Closed(val) => val.clone(),
+                            }
+                        };
+                        self.push(value);
                     } else {
-                        return Err("GetUpvalue called outside of function".to_string());
+                        return Err(ArkaanError::new(ErrorKind::Internal, "GetUpvalue called on non-closure function"));
                     }
                 }
-                OpCode::SetUpvalue(slot) => {
-                    if let Some(frame) = self.frames.last() {
-                        if let Some(ref closure) = frame.closure {
-                            let value = self.peek()?.clone();
-                            let mut upvalue = closure.upvalues[*slot].borrow_mut();
-                            match &mut upvalue.location {
-                                UpvalueLocation::Open(idx) => {
-                                    self.stack[*idx] = value;
-                                }
-                                UpvalueLocation::Closed(val) => {
-                                    *val = value;
-                                }
+                OpCode::SetUpvalue => {
+                    let slot = read_u16(&chunk, ip);
+                    if let Some(ref cl) = closure {
+                        let value = self.peek()?.clone();
+                        let mut upvalue = cl.upvalues[slot].borrow_mut();
+                        match &mut upvalue.location {
+                            UpvalueLocation::Open(idx) => {
+                                *self.slot_mut(*idx)? = value;
+                            }
+                            UpvalueLocation::Closed(val) => {
+                                *val = value;
                             }
-                        } else {
-                            return Err("SetUpvalue called on non-closure function".to_string());
                         }
                     } else {
-                        return Err("SetUpvalue called outside of function".to_string());
+                        return Err(ArkaanError::new(ErrorKind::Internal, "SetUpvalue called on non-closure function"));
                     }
                 }
-                OpCode::Closure(const_idx, upvalue_descs) => {
-                    let value = chunk.constants[*const_idx].clone();
-                    if let Value::Function(func) = value {
-                        let base = if self.frames.is_empty() {
-                            0
-                        } else {
-                            self.frames.last().unwrap().slots_start
-                        };
-
-                        let mut upvalues = Vec::new();
-                        for desc in upvalue_descs {
-                            let upvalue = if desc.is_local {
+                OpCode::Closure => {
+                    // Everything in use is on the stack or in a frame here, so this is where cycles are collected
+                    if self.heap.should_collect() {
+                        self.collect_garbage();
+                    }
+                    let value = chunk.constants[read_u16(&chunk, ip)].clone();
+                    let count = read_u16(&chunk, ip);
+                    if let Value::Function(func) = &value {
+                        let mut upvalues = Vec::with_capacity(func.upvalue_count);
+                        for _ in 0..count {
+                            let is_local = read_u8(&chunk, ip) != 0;
+                            let index = read_u16(&chunk, ip);
+                            let upvalue = if is_local {
                                 // Capture from stack
-                                self.capture_upvalue(base + desc.index)
+                                self.slot(slots_start + index)?;
+                                self.capture_upvalue(slots_start + index)
                             } else {
                                 // Capture from enclosing closure's upvalue
-                                if let Some(frame) = self.frames.last() {
-                                    if let Some(ref closure) = frame.closure {
-                                        Rc::clone(&closure.upvalues[desc.index])
-                                    } else {
-                                        return Err("Cannot capture upvalue from non-closure".to_string());
-                                    }
+                                if let Some(ref cl) = closure {
+                                    Rc::clone(&cl.upvalues[index])
                                 } else {
-                                    return Err("Cannot capture upvalue outside of function".to_string());
+                                    return Err(ArkaanError::new(ErrorKind::Internal, "Cannot capture upvalue from non-closure"));
                                 }
                             };
                             upvalues.push(upvalue);
                         }
 
-                        let closure = Closure {
-                            function: func,
+                        let new_closure = Rc::new(Closure {
+                            function: Rc::clone(func),
                             upvalues,
-                        };
-                        self.push(Value::Closure(Rc::new(closure)));
+                        });
+                        self.heap.track_closure(&new_closure);
+                        self.push(Value::Closure(new_closure));
                     } else {
-                        return Err("Closure constant is not a function".to_string());
+                        return Err(ArkaanError::new(ErrorKind::Internal, "Closure constant is not a function"));
                     }
                 }
                 OpCode::CloseUpvalue => {
-                    let top = self.stack.len() - 1;
+                    let top = self.operands(1)?;
                     self.close_upvalues(top);
                     self.pop()?;
                 }
@@ -482,18 +1115,15 @@ impl VM {
                             self.push(Value::Number(x + y));
                         }
                         (Value::String(x), Value::String(y)) => {
+                            self.allocate(x.len() + y.len())?;
                             let result = format!("{}{}", x, y);
                             self.push(Value::String(Rc::new(result)));
                         }
-                        (Value::String(x), _) => {
-                            let result = format!("{}{}", x, b);
+                        (Value::String(_), _) | (_, Value::String(_)) => {
+                            let result = self.concat(&a, &b)?;
                             self.push(Value::String(Rc::new(result)));
                         }
-                        (_, Value::String(y)) => {
-                            let result = format!("{}{}", a, y);
-                            self.push(Value::String(Rc::new(result)));
-                        }
-                        _ => return Err("Operande moet nommers of stringe wees vir '+'.".to_string()),
+                        _ => return Err(ArkaanError::new(ErrorKind::TypeMismatch, "Operande moet nommers of stringe wees vir '+'.")),
                     }
                 }
                 OpCode::Subtract => {
@@ -503,7 +1133,7 @@ impl VM {
                         (Value::Number(x), Value::Number(y)) => {
                             self.push(Value::Number(x - y));
                         }
-                        _ => return Err("Operande moet nommers wees vir '-'.".to_string()),
+                        _ => return Err(ArkaanError::new(ErrorKind::TypeMismatch, "Operande moet nommers wees vir '-'.")),
                     }
                 }
                 OpCode::Multiply => {
@@ -513,7 +1143,7 @@ impl VM {
                         (Value::Number(x), Value::Number(y)) => {
                             self.push(Value::Number(x * y));
                         }
-                        _ => return Err("Operande moet nommers wees vir '*'.".to_string()),
+                        _ => return Err(ArkaanError::new(ErrorKind::TypeMismatch, "Operande moet nommers wees vir '*'.")),
                     }
                 }
                 OpCode::Divide => {
@@ -522,11 +1152,11 @@ impl VM {
                     match (a, b) {
                         (Value::Number(x), Value::Number(y)) => {
                             if y == 0.0 {
-                                return Err("Deling deur nul.".to_string());
+                                return Err(ArkaanError::new(ErrorKind::DivisionByZero, "Deling deur nul."));
                             }
                             self.push(Value::Number(x / y));
                         }
-                        _ => return Err("Operande moet nommers wees vir '/'.".to_string()),
+                        _ => return Err(ArkaanError::new(ErrorKind::TypeMismatch, "Operande moet nommers wees vir '/'.")),
                     }
                 }
                 OpCode::Modulo => {
@@ -535,29 +1165,29 @@ impl VM {
                     match (a, b) {
                         (Value::Number(x), Value::Number(y)) => {
                             if y == 0.0 {
-                                return Err("Modulo deur nul.".to_string());
+                                return Err(ArkaanError::new(ErrorKind::DivisionByZero, "Modulo deur nul."));
                             }
                             self.push(Value::Number(x % y));
                         }
-                        _ => return Err("Operande moet nommers wees vir '%'.".to_string()),
+                        _ => return Err(ArkaanError::new(ErrorKind::TypeMismatch, "Operande moet nommers wees vir '%'.")),
                     }
                 }
                 OpCode::Negate => {
                     let value = self.pop()?;
                     match value {
                         Value::Number(n) => self.push(Value::Number(-n)),
-                        _ => return Err("Operand moet 'n nommer wees vir negasie.".to_string()),
+                        _ => return Err(ArkaanError::new(ErrorKind::TypeMismatch, "Operand moet 'n nommer wees vir negasie.")),
                     }
                 }
                 OpCode::Equal => {
                     let b = self.pop()?;
                     let a = self.pop()?;
-                    self.push(Value::Boolean(self.values_equal(&a, &b)));
+                    self.push(Value::Boolean(a == b));
                 }
                 OpCode::NotEqual => {
                     let b = self.pop()?;
                     let a = self.pop()?;
-                    self.push(Value::Boolean(!self.values_equal(&a, &b)));
+                    self.push(Value::Boolean(a != b));
                 }
                 OpCode::Less => {
                     let b = self.pop()?;
@@ -566,7 +1196,7 @@ impl VM {
                         (Value::Number(x), Value::Number(y)) => {
                             self.push(Value::Boolean(x < y));
                         }
-                        _ => return Err("Operande moet nommers wees vir '<'.".to_string()),
+                        _ => return Err(ArkaanError::new(ErrorKind::TypeMismatch, "Operande moet nommers wees vir '<'.")),
                     }
                 }
                 OpCode::LessEqual => {
@@ -576,7 +1206,7 @@ impl VM {
                         (Value::Number(x), Value::Number(y)) => {
                             self.push(Value::Boolean(x <= y));
                         }
-                        _ => return Err("Operande moet nommers wees vir '<='.".to_string()),
+                        _ => return Err(ArkaanError::new(ErrorKind::TypeMismatch, "Operande moet nommers wees vir '<='.")),
                     }
                 }
                 OpCode::Greater => {
@@ -586,7 +1216,7 @@ impl VM {
                         (Value::Number(x), Value::Number(y)) => {
                             self.push(Value::Boolean(x > y));
                         }
-                        _ => return Err("Operande moet nommers wees vir '>'.".to_string()),
+                        _ => return Err(ArkaanError::new(ErrorKind::TypeMismatch, "Operande moet nommers wees vir '>'.")),
                     }
                 }
                 OpCode::GreaterEqual => {
@@ -596,297 +1226,213 @@ impl VM {
                         (Value::Number(x), Value::Number(y)) => {
                             self.push(Value::Boolean(x >= y));
                         }
-                        _ => return Err("Operande moet nommers wees vir '>='.".to_string()),
+                        _ => return Err(ArkaanError::new(ErrorKind::TypeMismatch, "Operande moet nommers wees vir '>='.")),
                     }
                 }
                 OpCode::Not => {
                     let value = self.pop()?;
                     self.push(Value::Boolean(!value.is_truthy()));
                 }
-                OpCode::And | OpCode::Or => {
-                    // Handled by jump instructions
-                }
                 OpCode::Print => {
                     let value = self.pop()?;
                     println!("{}", value);
                 }
-                OpCode::Jump(target) => {
-                    ip = *target;
+                OpCode::Jump => {
+                    *ip = read_u32(&chunk, ip);
                 }
-                OpCode::JumpIfFalse(target) => {
+                OpCode::JumpIfFalse => {
+                    let target = read_u32(&chunk, ip);
                     let condition = self.peek()?;
                     if !condition.is_truthy() {
-                        ip = *target;
+                        *ip = target;
                     }
                 }
-                OpCode::Call(arg_count) => {
-                    let callee_idx = self.stack.len() - *arg_count - 1;
+                OpCode::Call => {
+                    let arg_count = read_u8(&chunk, ip);
+                    let callee_idx = self.operands(arg_count + 1)?;
+
+                    // The caller resumes after this instruction once the callee returns
+                    self.frames.last_mut().unwrap().ip = *ip;
                     let callee = self.stack[callee_idx].clone();
+                    match &callee {
+                        Value::Function(func) => self.push_frame(Rc::clone(func), None, arg_count)?,
+                        Value::Closure(cl) => self.push_frame(Rc::clone(&cl.function), Some(Rc::clone(cl)), arg_count)?,
+                        _ => {
+                            // Natives and constructors finish before the next instruction
+                            let args = self.stack.split_off(callee_idx + 1);
+                            self.stack.pop();
+                            let result = self.call_value(callee, args)?;
+                            self.push(result);
+                            continue;
+                        }
+                    }
+                    (chunk, slots_start, closure) = self.frame_state();
+                    *ip = 0;
+                }
+                OpCode::Return => {
+                    let result = self.pop()?;
+                    if let Some(result) = self.return_from_frame(result, base_depth) {
+                        return Ok(result);
+                    }
+                    (chunk, slots_start, closure) = self.frame_state();
+                    *ip = self.frames.last().unwrap().ip;
+                }
+                OpCode::TailCall => {
+                    let arg_count = read_u8(&chunk, ip);
+                    let callee_idx = self.operands(arg_count + 1)?;
+                    if self.frames.last().unwrap().function.is_none() {
+                        return Err(ArkaanError::new(ErrorKind::Internal, "TailCall kan nie in die hoofskrip gebruik word nie."));
+                    }
 
-                    match callee {
-                        Value::Function(func) => {
-                            if *arg_count != func.arity {
-                                return Err(format!(
-                                    "Verwag {} argumente maar het {} ontvang.",
-                                    func.arity, arg_count
-                                ));
+                    let callee = self.stack[callee_idx].clone();
+                    let (func, cl) = match &callee {
+                        Value::Function(func) => (Rc::clone(func), None),
+                        Value::Closure(cl) => (Rc::clone(&cl.function), Some(Rc::clone(cl))),
+                        _ => {
+                            // Natives and constructors can't reuse the frame; call them and return their result
+                            let args = self.stack.split_off(callee_idx + 1);
+                            self.stack.pop();
+                            let result = self.call_value(callee, args)?;
+                            if let Some(result) = self.return_from_frame(result, base_depth) {
+                                return Ok(result);
                             }
-
-                            // Get the function's chunk
-                            let func_chunk = Rc::clone(&func.chunk);
-
-                            // Push a new call frame
-                            self.frames.push(CallFrame {
-                                closure: None,
-                                function: func.clone(),
-                                ip,
-                                slots_start: callee_idx,
-                            });
-
-                            // Execute the function
-                            let result = self.run_function(func_chunk.clone(), callee_idx, None)?;
-
-                            // Pop the call frame
-                            let frame = self.frames.pop().unwrap();
-
-                            // Restore IP
-                            ip = frame.ip;
-
-                            // Pop arguments and callee, push result
-                            self.stack.truncate(callee_idx);
-                            self.push(result);
+                            (chunk, slots_start, closure) = self.frame_state();
+                            *ip = self.frames.last().unwrap().ip;
+                            continue;
                         }
-                        Value::Closure(closure) => {
-                            if *arg_count != closure.function.arity {
-                                return Err(format!(
-                                    "Verwag {} argumente maar het {} ontvang.",
-                                    closure.function.arity, arg_count
+                    };
+                    Self::check_arity(&func, arg_count)?;
+
+                    // Reuse the current frame: the callee and its arguments
+                    // replace this function's slots
+                    self.close_upvalues(slots_start);
+                    let args = self.stack.split_off(callee_idx);
+                    self.stack.truncate(slots_start);
+                    self.stack.extend(args);
+
+                    let frame = self.frames.last_mut().unwrap();
+                    frame.chunk = Rc::clone(&func.chunk);
+                    frame.function = Some(func);
+                    frame.closure = cl;
+                    (chunk, slots_start, closure) = self.frame_state();
+                    *ip = 0;
+                }
+                OpCode::MakeList => {
+                    let count = read_u16(&chunk, ip);
+                    self.allocate(count)?;
+                    let start = self.operands(count)?;
+                    let elements: Vec<Value> = self.stack.drain(start..).collect();
+                    self.push(Value::List(Rc::new(elements)));
+                }
+                OpCode::GetIndex => {
+                    let index = self.pop()?;
+                    let list = self.pop()?;
+
+                    match (&list, &index) {
+                        (Value::List(items), &Value::Number(n)) => {
+                            let idx = n as i64;
+                            let len = items.len() as i64;
+                            // Support negative indexing
+                            let actual_idx = if idx < 0 { len + idx } else { idx };
+                            if actual_idx < 0 || actual_idx >= len {
+                                return Err(ArkaanError::new(
+                                    ErrorKind::IndexOutOfBounds,
+                                    format!(
+                                        "Lys indeks buite perke: {} (lengte {})",
+                                        idx, len
+                                    ),
                                 ));
                             }
-
-                            // Get the function's chunk
-                            let func_chunk = Rc::clone(&closure.function.chunk);
-
-                            // Push a new call frame with the closure
-                            self.frames.push(CallFrame {
-                                closure: Some(Rc::clone(&closure)),
-                                function: Rc::clone(&closure.function),
-                                ip,
-                                slots_start: callee_idx,
-                            });
-
-                            // Execute the function with closure context
-                            let result = self.run_function(func_chunk.clone(), callee_idx, Some(Rc::clone(&closure)))?;
-
-                            // Pop the call frame
-                            let frame = self.frames.pop().unwrap();
-
-                            // Restore IP
-                            ip = frame.ip;
-
-                            // Pop arguments and callee, push result
-                            self.stack.truncate(callee_idx);
-                            self.push(result);
+                            self.push(items[actual_idx as usize].clone());
                         }
-                        Value::NativeFunction(nf) => {
-                            if *arg_count != nf.arity {
-                                return Err(format!(
-                                    "Verwag {} argumente maar het {} ontvang.",
-                                    nf.arity, arg_count
-                                ));
-                            }
-
-                            let args: Vec<Value> = self.stack[callee_idx + 1..].to_vec();
-
-                            // Handle higher-order functions specially
-                            let result = match nf.name.as_str() {
-                                "kaart" => {
-                                    match &args[0] {
-                                        Value::List(list) => self.hof_kaart(Rc::clone(list), args[1].clone())?,
-                                        _ => return Err("kaart() verwag 'n lys as eerste argument.".to_string()),
-                                    }
-                                }
-                                "filter" => {
-                                    match &args[0] {
-                                        Value::List(list) => self.hof_filter(Rc::clone(list), args[1].clone())?,
-                                        _ => return Err("filter() verwag 'n lys as eerste argument.".to_string()),
-                                    }
-                                }
-                                "vou" => {
-                                    match &args[0] {
-                                        Value::List(list) => self.hof_vou(Rc::clone(list), args[1].clone(), args[2].clone())?,
-                                        _ => return Err("vou() verwag 'n lys as eerste argument.".to_string()),
-                                    }
-                                }
-                                "vir_elk" => {
-                                    match &args[0] {
-                                        Value::List(list) => self.hof_vir_elk(Rc::clone(list), args[1].clone())?,
-                                        _ => return Err("vir_elk() verwag 'n lys as eerste argument.".to_string()),
-                                    }
-                                }
-                                _ => (nf.func)(&args)?,
-                            };
-
-                            self.stack.truncate(callee_idx);
-                            self.push(result);
-                        }
-                        Value::TypeConstructor(tc) => {
-                            // Check arity
-                            if *arg_count != tc.arity {
-                                return Err(format!(
-                                    "Konstruktor '{}' verwag {} argumente maar het {} ontvang.",
-                                    tc.constructor_name, tc.arity, arg_count
-                                ));
-                            }
-
-                            // For unit constructors (arity 0), they're already values themselves
-                            // For constructors with fields, create an AdtInstance
-                            let result = if tc.arity == 0 {
-                                Value::Adt(Rc::new(AdtInstance {
-                                    type_name: tc.type_name.clone(),
-                                    constructor_name: tc.constructor_name.clone(),
-                                    fields: Vec::new(),
-                                }))
-                            } else {
-                                let fields: Vec<Value> = self.stack[callee_idx + 1..].to_vec();
-                                Value::Adt(Rc::new(AdtInstance {
-                                    type_name: tc.type_name.clone(),
-                                    constructor_name: tc.constructor_name.clone(),
-                                    fields,
-                                }))
-                            };
-
-                            self.stack.truncate(callee_idx);
-                            self.push(result);
-                        }
-                        _ => {
-                            return Err("Kan slegs funksies oproep.".to_string());
-                        }
-                    }
-                }
-                OpCode::Return => {
-                    // Return from main chunk
-                    return Ok(());
-                }
-                OpCode::MakeList(count) => {
-                    let start = self.stack.len() - *count;
-                    let elements: Vec<Value> = self.stack.drain(start..).collect();
-                    self.push(Value::List(Rc::new(elements)));
-                }
-                OpCode::GetIndex => {
-                    let index = self.pop()?;
-                    let list = self.pop()?;
-
-                    match (list, index) {
-                        (Value::List(items), Value::Number(n)) => {
-                            let idx = n as i64;
-                            let len = items.len() as i64;
-                            // Support negative indexing
-                            let actual_idx = if idx < 0 { len + idx } else { idx };
-                            if actual_idx < 0 || actual_idx >= len {
-                                return Err(format!(
-                                    "Lys indeks buite perke: {} (lengte {})",
-                                    idx, len
-                                ));
-                            }
-                            self.push(items[actual_idx as usize].clone());
-                        }
-                        (Value::String(s), Value::Number(n)) => {
-                            let idx = n as i64;
-                            let len = s.chars().count() as i64;
-                            let actual_idx = if idx < 0 { len + idx } else { idx };
-                            if actual_idx < 0 || actual_idx >= len {
-                                return Err(format!(
-                                    "String indeks buite perke: {} (lengte {})",
-                                    idx, len
+                        (Value::String(s), &Value::Number(n)) => {
+                            let idx = n as i64;
+                            let len = s.chars().count() as i64;
+                            let actual_idx = if idx < 0 { len + idx } else { idx };
+                            if actual_idx < 0 || actual_idx >= len {
+                                return Err(ArkaanError::new(
+                                    ErrorKind::IndexOutOfBounds,
+                                    format!(
+                                        "String indeks buite perke: {} (lengte {})",
+                                        idx, len
+                                    ),
                                 ));
                             }
                             let ch: String = s.chars().nth(actual_idx as usize).unwrap().to_string();
                             self.push(Value::String(Rc::new(ch)));
                         }
                         _ => {
-                            return Err("Kan slegs lyste en stringe indekseer.".to_string());
+                            return Err(ArkaanError::new(ErrorKind::TypeMismatch, "Kan slegs lyste en stringe indekseer."));
                         }
                     }
                 }
-                OpCode::CheckConstructor(name, arity) => {
+                OpCode::CheckConstructor => {
+                    let tag = chunk.names[read_u16(&chunk, ip)];
+                    let arity = read_u16(&chunk, ip);
                     let value = self.peek()?;
                     let matches = match value {
                         Value::Adt(adt) => {
-                            adt.constructor_name == *name && adt.fields.len() == *arity
+                            adt.tag == tag && adt.fields.len() == arity
                         }
                         // Unit constructors might be TypeConstructor values
                         Value::TypeConstructor(tc) => {
-                            tc.constructor_name == *name && tc.arity == *arity && *arity == 0
+                            tc.tag == tag && tc.arity == arity && arity == 0
                         }
                         _ => false,
                     };
                     self.push(Value::Boolean(matches));
                 }
-                OpCode::GetField(index) => {
-                    let value = self.peek()?;
-                    match value {
-                        Value::Adt(adt) => {
-                            if *index < adt.fields.len() {
-                                let field_value = adt.fields[*index].clone();
-                                self.push(field_value);
-                            } else {
-                                return Err(format!(
-                                    "Veld indeks {} buite perke vir konstruktor '{}' met {} velde.",
-                                    index, adt.constructor_name, adt.fields.len()
-                                ));
-                            }
-                        }
-                        _ => {
-                            return Err("Kan slegs velde van ADT-waardes kry.".to_string());
-                        }
-                    }
-                }
                 OpCode::Dup => {
                     let value = self.peek()?.clone();
                     self.push(value);
                 }
-                OpCode::GetFieldPop(index) => {
+                OpCode::GetFieldPop => {
+                    let index = read_u16(&chunk, ip);
                     let value = self.pop()?;
-                    match value {
+                    match &value {
                         Value::Adt(adt) => {
-                            if *index < adt.fields.len() {
-                                self.push(adt.fields[*index].clone());
+                            if index < adt.fields.len() {
+                                self.push(adt.fields[index].clone());
                             } else {
-                                return Err(format!(
-                                    "Veld indeks {} buite perke vir konstruktor '{}' met {} velde.",
-                                    index, adt.constructor_name, adt.fields.len()
+                                return Err(ArkaanError::new(
+                                    ErrorKind::IndexOutOfBounds,
+                                    format!(
+                                        "Veld indeks {} buite perke vir konstruktor '{}' met {} velde.",
+                                        index, adt.constructor_name, adt.fields.len()
+                                    ),
                                 ));
                             }
                         }
                         _ => {
-                            return Err("Kan slegs velde van ADT-waardes kry.".to_string());
+                            return Err(ArkaanError::new(ErrorKind::TypeMismatch, "Kan slegs velde van ADT-waardes kry."));
                         }
                     }
                 }
-                OpCode::TailCall(_) => {
-                    // TailCall should never appear in the main script chunk
-                    return Err("TailCall kan nie in die hoofskrip gebruik word nie.".to_string());
-                }
-                OpCode::LoadModule(path, alias) => {
-                    let module = self.load_module(path, alias)?;
+                OpCode::LoadModule => {
+                    let path = chunk.names[read_u16(&chunk, ip)].name();
+                    let alias = chunk.names[read_u16(&chunk, ip)].name();
+                    let module = self.load_module(&path, &alias)?;
                     self.push(module);
                 }
-                OpCode::GetMember(member) => {
+                OpCode::GetMember => {
+                    let member = chunk.names[read_u16(&chunk, ip)];
                     let object = self.pop()?;
-                    match object {
+                    match &object {
                         Value::Module(module) => {
-                            if let Some(value) = module.exports.get(member) {
+                            if let Some(value) = module.exports.get(&member) {
                                 self.push(value.clone());
                             } else {
-                                return Err(format!(
-                                    "Module '{}' het nie lid '{}' nie.",
-                                    module.name, member
+                                return Err(ArkaanError::new(
+                                    ErrorKind::ModuleError,
+                                    format!(
+                                        "Module '{}' (\"{}\") het nie lid '{}' nie.",
+                                        module.name, module.path, member
+                                    ),
                                 ));
                             }
                         }
                         _ => {
-                            return Err("Kan slegs lede van modules kry.".to_string());
+                            return Err(ArkaanError::new(ErrorKind::TypeMismatch, "Kan slegs lede van modules kry."));
                         }
                     }
                 }
@@ -894,643 +1440,43 @@ impl VM {
         }
     }
 
-    fn run_function(&mut self, chunk: Rc<Chunk>, slots_start: usize, closure: Option<Rc<Closure>>) -> Result<Value, String> {
-        // Use mutable variables to support tail call optimization
-        let mut current_chunk = chunk;
-        let mut current_slots_start = slots_start;
-        let mut current_closure = closure;
-        let mut ip = 0;
-
-        loop {
-            if ip >= current_chunk.code.len() {
-                return Ok(Value::Nil);
-            }
-
-            let instruction = &current_chunk.code[ip];
-            ip += 1;
-
-            match instruction {
-                OpCode::Constant(idx) => {
-                    let value = current_chunk.constants[*idx].clone();
-                    self.push(value);
-                }
-                OpCode::Pop => {
-                    self.pop()?;
-                }
-                OpCode::GetVar(name) | OpCode::GetGlobal(name) => {
-                    let value = self
-                        .globals
-                        .get(name)
-                        .cloned()
-                        .ok_or_else(|| format!("Ongedefinieerde veranderlike: '{}'", name))?;
-                    self.push(value);
-                }
-                OpCode::SetVar(name) | OpCode::SetGlobal(name) => {
-                    let value = self.peek()?.clone();
-                    if !self.globals.contains_key(name) {
-                        return Err(format!("Ongedefinieerde veranderlike: '{}'", name));
-                    }
-                    self.globals.insert(name.clone(), value);
-                }
-                OpCode::DefineGlobal(name) => {
-                    let value = self.pop()?;
-                    self.globals.insert(name.clone(), value);
-                }
-                OpCode::GetLocal(slot) => {
-                    let value = self.stack[current_slots_start + *slot].clone();
-                    self.push(value);
-                }
-                OpCode::SetLocal(slot) => {
-                    let value = self.peek()?.clone();
-                    self.stack[current_slots_start + *slot] = value;
-                }
-                OpCode::GetUpvalue(slot) => {
-                    if let Some(ref cl) = current_closure {
-                        let value = {
-                            let upvalue = cl.upvalues[*slot].borrow();
-                            match &upvalue.location {
-                                UpvalueLocation::Open(idx) => self.stack[*idx].clone(),
-                                UpvalueLocation::Closed(val) => val.clone(),
-                            }
-                        };
-                        self.push(value);
-                    } else {
-                        return Err("GetUpvalue called on non-closure function".to_string());
-                    }
-                }
-                OpCode::SetUpvalue(slot) => {
-                    if let Some(ref cl) = current_closure {
-                        let value = self.peek()?.clone();
-                        let mut upvalue = cl.upvalues[*slot].borrow_mut();
-                        match &mut upvalue.location {
-                            UpvalueLocation::Open(idx) => {
-                                self.stack[*idx] = value;
-                            }
-                            UpvalueLocation::Closed(val) => {
-                                *val = value;
-                            }
-                        }
-                    } else {
-                        return Err("SetUpvalue called on non-closure function".to_string());
-                    }
-                }
-                OpCode::Closure(const_idx, upvalue_descs) => {
-                    let value = current_chunk.constants[*const_idx].clone();
-                    if let Value::Function(func) = value {
-                        let mut upvalues = Vec::new();
-                        for desc in upvalue_descs {
-                            let upvalue = if desc.is_local {
-                                // Capture from stack
-                                self.capture_upvalue(current_slots_start + desc.index)
-                            } else {
-                                // Capture from enclosing closure's upvalue
-                                if let Some(ref cl) = current_closure {
-                                    Rc::clone(&cl.upvalues[desc.index])
-                                } else {
-                                    return Err("Cannot capture upvalue from non-closure".to_string());
-                                }
-                            };
-                            upvalues.push(upvalue);
-                        }
-
-                        let new_closure = Closure {
-                            function: func,
-                            upvalues,
-                        };
-                        self.push(Value::Closure(Rc::new(new_closure)));
-                    } else {
-                        return Err("Closure constant is not a function".to_string());
-                    }
-                }
-                OpCode::CloseUpvalue => {
-                    let top = self.stack.len() - 1;
-                    self.close_upvalues(top);
-                    self.pop()?;
-                }
-                OpCode::Add => {
-                    let b = self.pop()?;
-                    let a = self.pop()?;
-                    match (&a, &b) {
-                        (Value::Number(x), Value::Number(y)) => {
-                            self.push(Value::Number(x + y));
-                        }
-                        (Value::String(x), Value::String(y)) => {
-                            let result = format!("{}{}", x, y);
-                            self.push(Value::String(Rc::new(result)));
-                        }
-                        (Value::String(x), _) => {
-                            let result = format!("{}{}", x, b);
-                            self.push(Value::String(Rc::new(result)));
-                        }
-                        (_, Value::String(y)) => {
-                            let result = format!("{}{}", a, y);
-                            self.push(Value::String(Rc::new(result)));
-                        }
-                        _ => return Err("Operande moet nommers of stringe wees vir '+'.".to_string()),
-                    }
-                }
-                OpCode::Subtract => {
-                    let b = self.pop()?;
-                    let a = self.pop()?;
-                    match (a, b) {
-                        (Value::Number(x), Value::Number(y)) => {
-                            self.push(Value::Number(x - y));
-                        }
-                        _ => return Err("Operande moet nommers wees vir '-'.".to_string()),
-                    }
-                }
-                OpCode::Multiply => {
-                    let b = self.pop()?;
-                    let a = self.pop()?;
-                    match (a, b) {
-                        (Value::Number(x), Value::Number(y)) => {
-                            self.push(Value::Number(x * y));
-                        }
-                        _ => return Err("Operande moet nommers wees vir '*'.".to_string()),
-                    }
-                }
-                OpCode::Divide => {
-                    let b = self.pop()?;
-                    let a = self.pop()?;
-                    match (a, b) {
-                        (Value::Number(x), Value::Number(y)) => {
-                            if y == 0.0 {
-                                return Err("Deling deur nul.".to_string());
-                            }
-                            self.push(Value::Number(x / y));
-                        }
-                        _ => return Err("Operande moet nommers wees vir '/'.".to_string()),
-                    }
-                }
-                OpCode::Modulo => {
-                    let b = self.pop()?;
-                    let a = self.pop()?;
-                    match (a, b) {
-                        (Value::Number(x), Value::Number(y)) => {
-                            if y == 0.0 {
-                                return Err("Modulo deur nul.".to_string());
-                            }
-                            self.push(Value::Number(x % y));
-                        }
-                        _ => return Err("Operande moet nommers wees vir '%'.".to_string()),
-                    }
-                }
-                OpCode::Negate => {
-                    let value = self.pop()?;
-                    match value {
-                        Value::Number(n) => self.push(Value::Number(-n)),
-                        _ => return Err("Operand moet 'n nommer wees vir negasie.".to_string()),
-                    }
-                }
-                OpCode::Equal => {
-                    let b = self.pop()?;
-                    let a = self.pop()?;
-                    self.push(Value::Boolean(self.values_equal(&a, &b)));
-                }
-                OpCode::NotEqual => {
-                    let b = self.pop()?;
-                    let a = self.pop()?;
-                    self.push(Value::Boolean(!self.values_equal(&a, &b)));
-                }
-                OpCode::Less => {
-                    let b = self.pop()?;
-                    let a = self.pop()?;
-                    match (a, b) {
-                        (Value::Number(x), Value::Number(y)) => {
-                            self.push(Value::Boolean(x < y));
-                        }
-                        _ => return Err("Operande moet nommers wees vir '<'.".to_string()),
-                    }
-                }
-                OpCode::LessEqual => {
-                    let b = self.pop()?;
-                    let a = self.pop()?;
-                    match (a, b) {
-                        (Value::Number(x), Value::Number(y)) => {
-                            self.push(Value::Boolean(x <= y));
-                        }
-                        _ => return Err("Operande moet nommers wees vir '<='.".to_string()),
-                    }
-                }
-                OpCode::Greater => {
-                    let b = self.pop()?;
-                    let a = self.pop()?;
-                    match (a, b) {
-                        (Value::Number(x), Value::Number(y)) => {
-                            self.push(Value::Boolean(x > y));
-                        }
-                        _ => return Err("Operande moet nommers wees vir '>'.".to_string()),
-                    }
-                }
-                OpCode::GreaterEqual => {
-                    let b = self.pop()?;
-                    let a = self.pop()?;
-                    match (a, b) {
-                        (Value::Number(x), Value::Number(y)) => {
-                            self.push(Value::Boolean(x >= y));
-                        }
-                        _ => return Err("Operande moet nommers wees vir '>='.".to_string()),
-                    }
-                }
-                OpCode::Not => {
-                    let value = self.pop()?;
-                    self.push(Value::Boolean(!value.is_truthy()));
-                }
-                OpCode::And | OpCode::Or => {
-                    // Handled by jump instructions
-                }
-                OpCode::Print => {
-                    let value = self.pop()?;
-                    println!("{}", value);
-                }
-                OpCode::Jump(target) => {
-                    ip = *target;
-                }
-                OpCode::JumpIfFalse(target) => {
-                    let condition = self.peek()?;
-                    if !condition.is_truthy() {
-                        ip = *target;
-                    }
-                }
-                OpCode::Call(arg_count) => {
-                    let callee_idx = self.stack.len() - *arg_count - 1;
-                    let callee = self.stack[callee_idx].clone();
-
-                    match callee {
-                        Value::Function(func) => {
-                            if *arg_count != func.arity {
-                                return Err(format!(
-                                    "Verwag {} argumente maar het {} ontvang.",
-                                    func.arity, arg_count
-                                ));
-                            }
-
-                            let func_chunk = Rc::clone(&func.chunk);
-
-                            self.frames.push(CallFrame {
-                                closure: None,
-                                function: func.clone(),
-                                ip,
-                                slots_start: callee_idx,
-                            });
-
-                            let result = self.run_function(func_chunk.clone(), callee_idx, None)?;
-
-                            let frame = self.frames.pop().unwrap();
-                            ip = frame.ip;
-
-                            self.stack.truncate(callee_idx);
-                            self.push(result);
-                        }
-                        Value::Closure(cl) => {
-                            if *arg_count != cl.function.arity {
-                                return Err(format!(
-                                    "Verwag {} argumente maar het {} ontvang.",
-                                    cl.function.arity, arg_count
-                                ));
-                            }
-
-                            let func_chunk = Rc::clone(&cl.function.chunk);
-
-                            self.frames.push(CallFrame {
-                                closure: Some(Rc::clone(&cl)),
-                                function: Rc::clone(&cl.function),
-                                ip,
-                                slots_start: callee_idx,
-                            });
-
-                            let result = self.run_function(func_chunk.clone(), callee_idx, Some(Rc::clone(&cl)))?;
-
-                            let frame = self.frames.pop().unwrap();
-                            ip = frame.ip;
-
-                            self.stack.truncate(callee_idx);
-                            self.push(result);
-                        }
-                        Value::NativeFunction(nf) => {
-                            if *arg_count != nf.arity {
-                                return Err(format!(
-                                    "Verwag {} argumente maar het {} ontvang.",
-                                    nf.arity, arg_count
-                                ));
-                            }
-
-                            let args: Vec<Value> = self.stack[callee_idx + 1..].to_vec();
-
-                            // Handle higher-order functions specially
-                            let result = match nf.name.as_str() {
-                                "kaart" => {
-                                    match &args[0] {
-                                        Value::List(list) => self.hof_kaart(Rc::clone(list), args[1].clone())?,
-                                        _ => return Err("kaart() verwag 'n lys as eerste argument.".to_string()),
-                                    }
-                                }
-                                "filter" => {
-                                    match &args[0] {
-                                        Value::List(list) => self.hof_filter(Rc::clone(list), args[1].clone())?,
-                                        _ => return Err("filter() verwag 'n lys as eerste argument.".to_string()),
-                                    }
-                                }
-                                "vou" => {
-                                    match &args[0] {
-                                        Value::List(list) => self.hof_vou(Rc::clone(list), args[1].clone(), args[2].clone())?,
-                                        _ => return Err("vou() verwag 'n lys as eerste argument.".to_string()),
-                                    }
-                                }
-                                "vir_elk" => {
-                                    match &args[0] {
-                                        Value::List(list) => self.hof_vir_elk(Rc::clone(list), args[1].clone())?,
-                                        _ => return Err("vir_elk() verwag 'n lys as eerste argument.".to_string()),
-                                    }
-                                }
-                                _ => (nf.func)(&args)?,
-                            };
-
-                            self.stack.truncate(callee_idx);
-                            self.push(result);
-                        }
-                        Value::TypeConstructor(tc) => {
-                            // Check arity
-                            if *arg_count != tc.arity {
-                                return Err(format!(
-                                    "Konstruktor '{}' verwag {} argumente maar het {} ontvang.",
-                                    tc.constructor_name, tc.arity, arg_count
-                                ));
-                            }
-
-                            // For unit constructors (arity 0), they're already values themselves
-                            // For constructors with fields, create an AdtInstance
-                            let result = if tc.arity == 0 {
-                                Value::Adt(Rc::new(AdtInstance {
-                                    type_name: tc.type_name.clone(),
-                                    constructor_name: tc.constructor_name.clone(),
-                                    fields: Vec::new(),
-                                }))
-                            } else {
-                                let fields: Vec<Value> = self.stack[callee_idx + 1..].to_vec();
-                                Value::Adt(Rc::new(AdtInstance {
-                                    type_name: tc.type_name.clone(),
-                                    constructor_name: tc.constructor_name.clone(),
-                                    fields,
-                                }))
-                            };
-
-                            self.stack.truncate(callee_idx);
-                            self.push(result);
-                        }
-                        _ => {
-                            return Err("Kan slegs funksies oproep.".to_string());
-                        }
-                    }
-                }
-                OpCode::Return => {
-                    // Get return value
-                    let result = self.pop()?;
-
-                    // Close all upvalues for locals being removed
-                    self.close_upvalues(current_slots_start);
-
-                    // Clean up local variables
-                    self.stack.truncate(current_slots_start);
-
-                    return Ok(result);
-                }
-                OpCode::TailCall(arg_count) => {
-                    // Tail call optimization: reuse the current stack frame
-                    let callee_idx = self.stack.len() - *arg_count - 1;
-                    let callee = self.stack[callee_idx].clone();
-
-                    match callee {
-                        Value::Function(func) => {
-                            if *arg_count != func.arity {
-                                return Err(format!(
-                                    "Verwag {} argumente maar het {} ontvang.",
-                                    func.arity, arg_count
-                                ));
-                            }
-
-                            // Close upvalues for current locals
-                            self.close_upvalues(current_slots_start);
-
-                            // Move arguments to current frame's slots
-                            // Stack: [old_locals..., callee, arg1, arg2, ...]
-                            // We want: [callee, arg1, arg2, ...]
-                            let args: Vec<Value> = self.stack[callee_idx..].to_vec();
-                            self.stack.truncate(current_slots_start);
-                            for arg in args {
-                                self.push(arg);
-                            }
-
-                            // Update chunk and reset IP
-                            current_chunk = Rc::clone(&func.chunk);
-                            current_closure = None;
-                            ip = 0;
-                            // Continue the loop with the new function
-                        }
-                        Value::Closure(cl) => {
-                            if *arg_count != cl.function.arity {
-                                return Err(format!(
-                                    "Verwag {} argumente maar het {} ontvang.",
-                                    cl.function.arity, arg_count
-                                ));
-                            }
-
-                            // Close upvalues for current locals
-                            self.close_upvalues(current_slots_start);
-
-                            // Move arguments to current frame's slots
-                            let args: Vec<Value> = self.stack[callee_idx..].to_vec();
-                            self.stack.truncate(current_slots_start);
-                            for arg in args {
-                                self.push(arg);
-                            }
-
-                            // Update chunk, closure, and reset IP
-                            current_chunk = Rc::clone(&cl.function.chunk);
-                            current_closure = Some(Rc::clone(&cl));
-                            ip = 0;
-                            // Continue the loop with the new function
-                        }
-                        Value::NativeFunction(nf) => {
-                            // Native functions can't be tail-called in the same way,
-                            // just call them and return the result
-                            if *arg_count != nf.arity {
-                                return Err(format!(
-                                    "Verwag {} argumente maar het {} ontvang.",
-                                    nf.arity, arg_count
-                                ));
-                            }
-
-                            let args: Vec<Value> = self.stack[callee_idx + 1..].to_vec();
-                            let result = (nf.func)(&args)?;
-
-                            self.close_upvalues(current_slots_start);
-                            self.stack.truncate(current_slots_start);
-                            return Ok(result);
-                        }
-                        Value::TypeConstructor(tc) => {
-                            // Type constructors just create a value and return it
-                            if *arg_count != tc.arity {
-                                return Err(format!(
-                                    "Konstruktor '{}' verwag {} argumente maar het {} ontvang.",
-                                    tc.constructor_name, tc.arity, arg_count
-                                ));
-                            }
+    fn push(&mut self, value: Value) {
+        self.stack.push(value);
+    }
 
-                            let result = if tc.arity == 0 {
-                                Value::Adt(Rc::new(AdtInstance {
-                                    type_name: tc.type_name.clone(),
-                                    constructor_name: tc.constructor_name.clone(),
-                                    fields: Vec::new(),
-                                }))
-                            } else {
-                                let fields: Vec<Value> = self.stack[callee_idx + 1..].to_vec();
-                                Value::Adt(Rc::new(AdtInstance {
-                                    type_name: tc.type_name.clone(),
-                                    constructor_name: tc.constructor_name.clone(),
-                                    fields,
-                                }))
-                            };
+    fn pop(&mut self) -> Result<Value, ArkaanError> {
+        self.stack.pop().ok_or_else(|| ArkaanError::new(ErrorKind::Internal, "Stapel onderloop."))
+    }
 
-                            self.close_upvalues(current_slots_start);
-                            self.stack.truncate(current_slots_start);
-                            return Ok(result);
-                        }
-                        _ => {
-                            return Err("Kan slegs funksies oproep.".to_string());
-                        }
-                    }
-                }
-                OpCode::MakeList(count) => {
-                    let start = self.stack.len() - *count;
-                    let elements: Vec<Value> = self.stack.drain(start..).collect();
-                    self.push(Value::List(Rc::new(elements)));
-                }
-                OpCode::GetIndex => {
-                    let index = self.pop()?;
-                    let list = self.pop()?;
+    fn peek(&self) -> Result<&Value, ArkaanError> {
+        self.stack.last().ok_or_else(|| ArkaanError::new(ErrorKind::Internal, "Stapel is leeg."))
+    }
 
-                    match (list, index) {
-                        (Value::List(items), Value::Number(n)) => {
-                            let idx = n as i64;
-                            let len = items.len() as i64;
-                            let actual_idx = if idx < 0 { len + idx } else { idx };
-                            if actual_idx < 0 || actual_idx >= len {
-                                return Err(format!(
-                                    "Lys indeks buite perke: {} (lengte {})",
-                                    idx, len
-                                ));
-                            }
-                            self.push(items[actual_idx as usize].clone());
-                        }
-                        (Value::String(s), Value::Number(n)) => {
-                            let idx = n as i64;
-                            let len = s.chars().count() as i64;
-                            let actual_idx = if idx < 0 { len + idx } else { idx };
-                            if actual_idx < 0 || actual_idx >= len {
-                                return Err(format!(
-                                    "String indeks buite perke: {} (lengte {})",
-                                    idx, len
-                                ));
-                            }
-                            let ch: String = s.chars().nth(actual_idx as usize).unwrap().to_string();
-                            self.push(Value::String(Rc::new(ch)));
-                        }
-                        _ => {
-                            return Err("Kan slegs lyste en stringe indekseer.".to_string());
-                        }
-                    }
-                }
-                OpCode::CheckConstructor(name, arity) => {
-                    let value = self.peek()?;
-                    let matches = match value {
-                        Value::Adt(adt) => {
-                            adt.constructor_name == *name && adt.fields.len() == *arity
-                        }
-                        Value::TypeConstructor(tc) => {
-                            tc.constructor_name == *name && tc.arity == *arity && *arity == 0
-                        }
-                        _ => false,
-                    };
-                    self.push(Value::Boolean(matches));
-                }
-                OpCode::GetField(index) => {
-                    let value = self.peek()?;
-                    match value {
-                        Value::Adt(adt) => {
-                            if *index < adt.fields.len() {
-                                let field_value = adt.fields[*index].clone();
-                                self.push(field_value);
-                            } else {
-                                return Err(format!(
-                                    "Veld indeks {} buite perke vir konstruktor '{}' met {} velde.",
-                                    index, adt.constructor_name, adt.fields.len()
-                                ));
-                            }
-                        }
-                        _ => {
-                            return Err("Kan slegs velde van ADT-waardes kry.".to_string());
-                        }
-                    }
-                }
-                OpCode::Dup => {
-                    let value = self.peek()?.clone();
-                    self.push(value);
-                }
-                OpCode::GetFieldPop(index) => {
-                    let value = self.pop()?;
-                    match value {
-                        Value::Adt(adt) => {
-                            if *index < adt.fields.len() {
-                                self.push(adt.fields[*index].clone());
-                            } else {
-                                return Err(format!(
-                                    "Veld indeks {} buite perke vir konstruktor '{}' met {} velde.",
-                                    index, adt.constructor_name, adt.fields.len()
-                                ));
-                            }
-                        }
-                        _ => {
-                            return Err("Kan slegs velde van ADT-waardes kry.".to_string());
-                        }
-                    }
-                }
-                OpCode::LoadModule(path, alias) => {
-                    let module = self.load_module(path, alias)?;
-                    self.push(module);
-                }
-                OpCode::GetMember(member) => {
-                    let object = self.pop()?;
-                    match object {
-                        Value::Module(module) => {
-                            if let Some(value) = module.exports.get(member) {
-                                self.push(value.clone());
-                            } else {
-                                return Err(format!(
-                                    "Module '{}' het nie lid '{}' nie.",
-                                    module.name, member
-                                ));
-                            }
-                        }
-                        _ => {
-                            return Err("Kan slegs lede van modules kry.".to_string());
-                        }
-                    }
-                }
-            }
-        }
+    /// The stack slot an instruction names. Only damaged bytecode names one
+    /// past the top, so that is an error instead of a panic.
+    fn slot(&self, index: usize) -> Result<&Value, ArkaanError> {
+        self.stack.get(index).ok_or_else(|| Self::past_top(index))
     }
 
-    fn push(&mut self, value: Value) {
-        self.stack.push(value);
+    fn slot_mut(&mut self, index: usize) -> Result<&mut Value, ArkaanError> {
+        self.stack.get_mut(index).ok_or_else(|| Self::past_top(index))
     }
 
-    fn pop(&mut self) -> Result<Value, String> {
-        self.stack.pop().ok_or_else(|| "Stapel onderloop.".to_string())
+    /// Where the top `count` values an instruction works on start
+    fn operands(&self, count: usize) -> Result<usize, ArkaanError> {
+        self.stack.len().checked_sub(count).ok_or_else(|| {
+            ArkaanError::new(
+                ErrorKind::InvalidBytecode,
+                format!("Ongeldige greepkode: die instruksie gebruik {} waardes, maar die stapel het {}.", count, self.stack.len()),
+            )
+        })
     }
 
-    fn peek(&self) -> Result<&Value, String> {
-        self.stack.last().ok_or_else(|| "Stapel is leeg.".to_string())
+    fn past_top(index: usize) -> ArkaanError {
+        ArkaanError::new(
+            ErrorKind::InvalidBytecode,
+            format!("Ongeldige greepkode: stapelgleuf {} is bo-op die stapel.", index),
+        )
     }
 
     fn capture_upvalue(&mut self, stack_index: usize) -> Rc<RefCell<Upvalue>> {
@@ -1547,6 +1493,7 @@ impl VM {
         let upvalue = Rc::new(RefCell::new(Upvalue {
             location: UpvalueLocation::Open(stack_index),
         }));
+        self.heap.track_upvalue(&upvalue);
         self.open_upvalues.push(Rc::clone(&upvalue));
         upvalue
     }
@@ -1573,7 +1520,8 @@ impl VM {
                         unreachable!()
                     }
                 };
-                let value = self.stack[stack_idx].clone();
+                // Only damaged bytecode pops a captured slot without closing it first
+                let value = self.stack.get(stack_idx).cloned().unwrap_or(Value::Nil);
                 self.open_upvalues[i].borrow_mut().location = UpvalueLocation::Closed(value);
                 self.open_upvalues.remove(i);
             } else {
@@ -1582,132 +1530,102 @@ impl VM {
         }
     }
 
-    fn values_equal(&self, a: &Value, b: &Value) -> bool {
-        match (a, b) {
-            (Value::Number(x), Value::Number(y)) => x == y,
-            (Value::Boolean(x), Value::Boolean(y)) => x == y,
-            (Value::String(x), Value::String(y)) => x == y,
-            (Value::Nil, Value::Nil) => true,
-            (Value::List(x), Value::List(y)) => x == y,
-            (Value::Function(x), Value::Function(y)) => Rc::ptr_eq(x, y),
-            (Value::Closure(x), Value::Closure(y)) => Rc::ptr_eq(x, y),
-            (Value::NativeFunction(x), Value::NativeFunction(y)) => Rc::ptr_eq(x, y),
-            (Value::TypeConstructor(x), Value::TypeConstructor(y)) => Rc::ptr_eq(x, y),
-            (Value::Adt(x), Value::Adt(y)) => {
-                x.type_name == y.type_name
-                    && x.constructor_name == y.constructor_name
-                    && x.fields.len() == y.fields.len()
-                    && x.fields.iter().zip(y.fields.iter()).all(|(a, b)| self.values_equal(a, b))
-            }
-            (Value::Module(x), Value::Module(y)) => Rc::ptr_eq(x, y),
-            _ => false,
+    /// Runs an Arkaan function to completion. On an error, what it left on the
+    /// stack is dropped, so a host can keep using the VM.
+    fn run_function(
+        &mut self,
+        callee: Value,
+        function: Rc<Function>,
+        closure: Option<Rc<Closure>>,
+        args: Vec<Value>,
+    ) -> Result<Value, ArkaanError> {
+        let base = self.stack.len();
+        let arg_count = args.len();
+        self.push(callee);
+        self.stack.extend(args);
+        let result = self.push_frame(function, closure, arg_count).and_then(|()| self.execute());
+        if result.is_err() {
+            self.close_upvalues(base);
+            self.stack.truncate(base);
         }
+        result
     }
 
-    /// Call a callable value with given arguments
-    fn call_value(&mut self, callee: Value, args: Vec<Value>) -> Result<Value, String> {
-        match callee {
-            Value::Function(func) => {
-                if args.len() != func.arity {
-                    return Err(format!(
-                        "Verwag {} argumente maar het {} ontvang.",
-                        func.arity, args.len()
-                    ));
-                }
-
-                // Set up the call
-                let callee_idx = self.stack.len();
-                self.push(Value::Function(Rc::clone(&func)));
-                for arg in args {
-                    self.push(arg);
-                }
-
-                let func_chunk = Rc::clone(&func.chunk);
-
-                self.frames.push(CallFrame {
-                    closure: None,
-                    function: func.clone(),
-                    ip: 0,
-                    slots_start: callee_idx,
-                });
-
-                let result = self.run_function(func_chunk.clone(), callee_idx, None)?;
-
-                self.frames.pop();
-                self.stack.truncate(callee_idx);
-
-                Ok(result)
-            }
+    /// Calls a function, closure, native or constructor with the given
+    /// arguments and waits for its result. Natives use this to call back into
+    /// Arkaan code.
+    pub fn call_value(&mut self, callee: Value, args: Vec<Value>) -> Result<Value, ArkaanError> {
+        match &callee {
+            Value::Function(func) => self.run_function(callee.clone(), Rc::clone(func), None, args),
             Value::Closure(closure) => {
-                if args.len() != closure.function.arity {
-                    return Err(format!(
-                        "Verwag {} argumente maar het {} ontvang.",
-                        closure.function.arity, args.len()
-                    ));
-                }
-
-                // Set up the call
-                let callee_idx = self.stack.len();
-                self.push(Value::Closure(Rc::clone(&closure)));
-                for arg in args {
-                    self.push(arg);
-                }
-
-                let func_chunk = Rc::clone(&closure.function.chunk);
-
-                self.frames.push(CallFrame {
-                    closure: Some(Rc::clone(&closure)),
-                    function: Rc::clone(&closure.function),
-                    ip: 0,
-                    slots_start: callee_idx,
-                });
-
-                let result = self.run_function(func_chunk.clone(), callee_idx, Some(Rc::clone(&closure)))?;
-
-                self.frames.pop();
-                self.stack.truncate(callee_idx);
-
-                Ok(result)
+                let function = Rc::clone(&closure.function);
+                self.run_function(callee.clone(), function, Some(Rc::clone(closure)), args)
             }
             Value::NativeFunction(nf) => {
                 if args.len() != nf.arity {
-                    return Err(format!(
-                        "Verwag {} argumente maar het {} ontvang.",
-                        nf.arity, args.len()
+                    return Err(ArkaanError::new(
+                        ErrorKind::ArityMismatch,
+                        format!(
+                            "Verwag {} argumente maar het {} ontvang.",
+                            nf.arity, args.len()
+                        ),
                     ));
                 }
-                (nf.func)(&args)
+                self.call_native(nf, &args)
             }
             Value::TypeConstructor(tc) => {
                 if args.len() != tc.arity {
-                    return Err(format!(
-                        "Konstruktor '{}' verwag {} argumente maar het {} ontvang.",
-                        tc.constructor_name, tc.arity, args.len()
+                    return Err(ArkaanError::new(
+                        ErrorKind::ArityMismatch,
+                        format!(
+                            "Konstruktor '{}' verwag {} argumente maar het {} ontvang.",
+                            tc.constructor_name, tc.arity, args.len()
+                        ),
                     ));
                 }
 
                 Ok(Value::Adt(Rc::new(AdtInstance {
                     type_name: tc.type_name.clone(),
                     constructor_name: tc.constructor_name.clone(),
+                    tag: tc.tag,
                     fields: args,
                 })))
             }
-            _ => Err("Kan slegs funksies oproep.".to_string()),
+            _ => Err(ArkaanError::new(ErrorKind::NotCallable, "Kan slegs funksies oproep.")
+                .with_note(format!("'{}' is nie 'n funksie nie.", callee))),
         }
     }
 
+    /// Runs a native, which gets this VM so it can call back into Arkaan code
+    fn call_native(&mut self, nf: &NativeFunction, args: &[Value]) -> Result<Value, ArkaanError> {
+        // Popped off the stack already, but still in use if the native calls back
+        let pinned = self.pinned.len();
+        self.pinned.extend_from_slice(args);
+        let result = (nf.func)(self, args);
+        self.pinned.truncate(pinned);
+
+        result.map_err(|e| {
+            e.with_frame(TraceFrame {
+                function: nf.name.clone(),
+                file: None,
+                line: 0,
+            })
+        })
+    }
+
     /// Higher-order function: kaart (map)
-    fn hof_kaart(&mut self, list: Rc<Vec<Value>>, func: Value) -> Result<Value, String> {
-        let mut results = Vec::with_capacity(list.len());
+    fn hof_kaart(&mut self, list: Rc<Vec<Value>>, func: Value) -> Result<Value, ArkaanError> {
+        // Results are pinned until the list is made, so later calls cannot collect them
+        let start = self.pinned.len();
         for item in list.iter() {
             let result = self.call_value(func.clone(), vec![item.clone()])?;
-            results.push(result);
+            self.pinned.push(result);
         }
-        Ok(Value::List(Rc::new(results)))
+        Ok(Value::List(Rc::new(self.pinned.split_off(start))))
     }
 
     /// Higher-order function: filter
-    fn hof_filter(&mut self, list: Rc<Vec<Value>>, func: Value) -> Result<Value, String> {
+    fn hof_filter(&mut self, list: Rc<Vec<Value>>, func: Value) -> Result<Value, ArkaanError> {
         let mut results = Vec::new();
         for item in list.iter() {
             let result = self.call_value(func.clone(), vec![item.clone()])?;
@@ -1719,7 +1637,7 @@ impl VM {
     }
 
     /// Higher-order function: vou (fold/reduce)
-    fn hof_vou(&mut self, list: Rc<Vec<Value>>, initial: Value, func: Value) -> Result<Value, String> {
+    fn hof_vou(&mut self, list: Rc<Vec<Value>>, initial: Value, func: Value) -> Result<Value, ArkaanError> {
         let mut acc = initial;
         for item in list.iter() {
             acc = self.call_value(func.clone(), vec![acc, item.clone()])?;
@@ -1728,10 +1646,73 @@ impl VM {
     }
 
     /// Higher-order function: vir_elk (for each)
-    fn hof_vir_elk(&mut self, list: Rc<Vec<Value>>, func: Value) -> Result<Value, String> {
+    fn hof_vir_elk(&mut self, list: Rc<Vec<Value>>, func: Value) -> Result<Value, ArkaanError> {
         for item in list.iter() {
             self.call_value(func.clone(), vec![item.clone()])?;
         }
         Ok(Value::Nil)
     }
 }
+
+/// Points `chunk` and every function declared in it at global table `table`
+fn mark_module(chunk: &Chunk, table: usize) {
+    chunk.module.set(table);
+    for constant in &chunk.constants {
+        if let Value::Function(function) = constant {
+            mark_module(&function.chunk, table);
+        }
+    }
+}
+
+/// Reads a one-byte operand and moves past it
+fn read_u8(chunk: &Chunk, ip: &mut usize) -> usize {
+    let value = chunk.code[*ip];
+    *ip += 1;
+    value as usize
+}
+
+/// A string that refuses to grow past `max` bytes
+struct CappedString {
+    text: String,
+    max: Option<usize>,
+}
+
+impl fmt::Write for CappedString {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.max.is_some_and(|max| self.text.len() + s.len() > max) {
+            return Err(fmt::Error);
+        }
+        self.text.push_str(s);
+        Ok(())
+    }
+}
+
+/// Reads a two-byte operand and moves past it
+fn read_u16(chunk: &Chunk, ip: &mut usize) -> usize {
+    let value = chunk.read_u16(*ip);
+    *ip += 2;
+    value as usize
+}
+
+/// Reads a jump target and moves past it
+fn read_u32(chunk: &Chunk, ip: &mut usize) -> usize {
+    let value = chunk.read_u32(*ip);
+    *ip += 4;
+    value as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::Engine;
+
+    #[test]
+    fn closures_from_a_failed_entry_keep_what_they_captured() {
+        let mut engine = Engine::new();
+        engine.eval("stel hou = 0").unwrap();
+        let error = engine
+            .eval("{\n    stel a = 1\n    stel b = 2\n    stel c = 5\n    hou = fn() c\n    laat boom = 1 - \"a\"\n}")
+            .unwrap_err();
+        assert_eq!(error.code(), "E0401");
+        assert_eq!(engine.eval("hou()").unwrap().to_string(), "5");
+    }
+}